@@ -0,0 +1,20 @@
+-- Spaced-repetition state per card, updated by every flash-card review.
+-- SM-2 uses ease_factor/interval_days; FSRS uses stability/difficulty.
+-- Both algorithms share reps, lapses and due_at so the scheduler can be
+-- switched without losing history.
+CREATE TABLE card_schedules (
+  card_id          BIGINT      NOT NULL PRIMARY KEY,
+  algorithm        VARCHAR(16) NOT NULL,
+  ease_factor      DOUBLE      NOT NULL DEFAULT 2.5,
+  interval_days    DOUBLE      NOT NULL DEFAULT 0,
+  stability        DOUBLE      NOT NULL DEFAULT 0,
+  difficulty       DOUBLE      NOT NULL DEFAULT 0,
+  reps             INT         NOT NULL DEFAULT 0,
+  lapses           INT         NOT NULL DEFAULT 0,
+  due_at           DATETIME    NULL,
+  last_reviewed_at DATETIME    NULL,
+  created_at       DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at       DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  INDEX idx_card_schedules_due (due_at),
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE
+);
@@ -0,0 +1,16 @@
+-- One row per flash-card answer, so review history survives ok_count and
+-- updated_at being overwritten. scope_tag / scope_parent_id record the
+-- query scope the card was reviewed under.
+CREATE TABLE card_reviews (
+  id              BIGINT AUTO_INCREMENT PRIMARY KEY,
+  card_id         BIGINT       NOT NULL,
+  reviewed_at     DATETIME     NOT NULL,
+  is_ok           BOOLEAN      NOT NULL,
+  response_ms     INT          NULL,
+  scope_tag       VARCHAR(100) NULL,
+  scope_parent_id BIGINT       NULL,
+  created_at      DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  INDEX idx_card_reviews_card (card_id, reviewed_at),
+  INDEX idx_card_reviews_reviewed_at (reviewed_at),
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+ALTER TABLE card_reviews
+  DROP COLUMN item_key;
+
+DELETE FROM card_schedules WHERE item_key <> 'basic';
+ALTER TABLE card_schedules
+  DROP PRIMARY KEY,
+  DROP COLUMN item_key,
+  ADD PRIMARY KEY (card_id);
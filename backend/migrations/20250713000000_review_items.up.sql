@@ -0,0 +1,10 @@
+-- A card can produce several review items (one per cloze number), each with
+-- its own spaced-repetition state. Cards without cloze deletions have a
+-- single 'basic' item, so existing rows keep their meaning.
+ALTER TABLE card_schedules
+  ADD COLUMN item_key VARCHAR(32) NOT NULL DEFAULT 'basic' AFTER card_id,
+  DROP PRIMARY KEY,
+  ADD PRIMARY KEY (card_id, item_key);
+
+ALTER TABLE card_reviews
+  ADD COLUMN item_key VARCHAR(32) NOT NULL DEFAULT 'basic' AFTER card_id;
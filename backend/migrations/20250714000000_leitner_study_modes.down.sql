@@ -0,0 +1,4 @@
+DROP TABLE study_modes;
+
+ALTER TABLE card_schedules
+  DROP COLUMN leitner_box;
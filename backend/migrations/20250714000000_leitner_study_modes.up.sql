@@ -0,0 +1,19 @@
+-- Leitner box of each review item (1-based).
+ALTER TABLE card_schedules
+  ADD COLUMN leitner_box INT NOT NULL DEFAULT 1 AFTER lapses;
+
+-- Scheduler chosen for a tag or a frame card, overriding MEMOAPP_SCHEDULER.
+-- Exactly one of tag_id / frame_id is set.
+CREATE TABLE study_modes (
+  id         BIGINT AUTO_INCREMENT PRIMARY KEY,
+  tag_id     BIGINT      NULL,
+  frame_id   BIGINT      NULL,
+  mode       VARCHAR(16) NOT NULL,
+  created_at DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  UNIQUE KEY uq_study_modes_tag (tag_id),
+  UNIQUE KEY uq_study_modes_frame (frame_id),
+  FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE CASCADE,
+  FOREIGN KEY (frame_id) REFERENCES cards (id) ON DELETE CASCADE,
+  CHECK ((tag_id IS NULL) <> (frame_id IS NULL))
+);
@@ -0,0 +1,6 @@
+ALTER TABLE card_reviews
+  DROP FOREIGN KEY fk_card_reviews_session,
+  DROP INDEX idx_card_reviews_session,
+  DROP COLUMN session_id;
+
+DROP TABLE study_sessions;
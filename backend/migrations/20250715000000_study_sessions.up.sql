@@ -0,0 +1,26 @@
+-- A drill over a flash-card scope. The server hands out one review item at a
+-- time (current_*) and stops serving a queue once its limit is reached.
+-- scope holds the flash-card query the session was started with, as JSON.
+CREATE TABLE study_sessions (
+  id               BIGINT AUTO_INCREMENT PRIMARY KEY,
+  scope            TEXT        NOT NULL,
+  new_limit        INT         NOT NULL,
+  learning_limit   INT         NOT NULL,
+  review_limit     INT         NOT NULL,
+  new_count        INT         NOT NULL DEFAULT 0,
+  learning_count   INT         NOT NULL DEFAULT 0,
+  review_count     INT         NOT NULL DEFAULT 0,
+  current_card_id  BIGINT      NULL,
+  current_item_key VARCHAR(32) NULL,
+  current_queue    VARCHAR(16) NULL,
+  served_at        DATETIME(3) NULL,
+  started_at       DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  finished_at      DATETIME    NULL,
+  FOREIGN KEY (current_card_id) REFERENCES cards (id) ON DELETE SET NULL
+);
+
+ALTER TABLE card_reviews
+  ADD COLUMN session_id BIGINT NULL AFTER scope_parent_id,
+  ADD INDEX idx_card_reviews_session (session_id),
+  ADD CONSTRAINT fk_card_reviews_session
+    FOREIGN KEY (session_id) REFERENCES study_sessions (id) ON DELETE SET NULL;
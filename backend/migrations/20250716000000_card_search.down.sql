@@ -0,0 +1,2 @@
+DROP TABLE card_search_terms;
+DROP TABLE card_search_docs;
@@ -0,0 +1,20 @@
+-- Full-text index over card titles and contents, filled by the backend
+-- (see search.rs): MariaDB has no n-gram parser for Japanese text.
+-- Cards without a card_search_docs row are indexed at startup.
+CREATE TABLE card_search_docs (
+  card_id         BIGINT   NOT NULL PRIMARY KEY,
+  title_length    INT      NOT NULL,
+  contents_length INT      NOT NULL,
+  indexed_at      DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE
+);
+
+CREATE TABLE card_search_terms (
+  card_id        BIGINT      NOT NULL,
+  term           VARCHAR(64) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+  title_count    INT         NOT NULL,
+  contents_count INT         NOT NULL,
+  PRIMARY KEY (card_id, term),
+  INDEX idx_card_search_terms_term (term, card_id),
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE
+);
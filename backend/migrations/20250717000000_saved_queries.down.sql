@@ -0,0 +1,5 @@
+ALTER TABLE cards
+  DROP FOREIGN KEY fk_cards_saved_query,
+  DROP COLUMN saved_query_id;
+
+DROP TABLE saved_queries;
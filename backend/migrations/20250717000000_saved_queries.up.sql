@@ -0,0 +1,16 @@
+-- Named card queries (see card_query.rs for the syntax). A card with
+-- card_type 'smart_frame' points at one; its children are the query's
+-- current results instead of card_card rows.
+CREATE TABLE saved_queries (
+  id         BIGINT       AUTO_INCREMENT PRIMARY KEY,
+  name       VARCHAR(100) NOT NULL,
+  query      TEXT         NOT NULL,
+  created_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  UNIQUE KEY uq_saved_queries_name (name)
+);
+
+ALTER TABLE cards
+  ADD COLUMN saved_query_id BIGINT NULL,
+  ADD CONSTRAINT fk_cards_saved_query
+    FOREIGN KEY (saved_query_id) REFERENCES saved_queries (id);
@@ -0,0 +1,25 @@
+-- Title/contents history of every card. A row is written whenever a change
+-- leaves the card's title or contents different from its latest revision,
+-- recording who made it (user_id) and through which path (source: 'ui' for
+-- a browser session, 'api_token', 'flash_card' or 'import'; 'initial' for
+-- the rows backfilled below).
+CREATE TABLE card_revisions (
+  id            BIGINT      AUTO_INCREMENT PRIMARY KEY,
+  card_id       BIGINT      NOT NULL,
+  title         VARCHAR(100),
+  contents      TEXT,
+  user_id       BIGINT      NULL,
+  source        VARCHAR(16) NOT NULL,
+  -- Set when the revision was written by restoring an older one.
+  restored_from BIGINT      NULL,
+  created_at    DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+  INDEX idx_card_revisions_card (card_id, id),
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE,
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL,
+  FOREIGN KEY (restored_from) REFERENCES card_revisions (id) ON DELETE SET NULL
+);
+
+-- Existing cards start with their current text as the first revision, so
+-- the first edit after this migration can be undone.
+INSERT INTO card_revisions (card_id, title, contents, source, created_at)
+SELECT id, title, contents, 'initial', updated_at FROM cards;
@@ -0,0 +1,5 @@
+DROP TABLE trashed_card_tag;
+DROP TABLE trashed_card_card;
+ALTER TABLE cards
+  DROP INDEX idx_cards_deleted_at,
+  DROP COLUMN deleted_at;
@@ -0,0 +1,33 @@
+-- Soft delete. A deleted card keeps its row with deleted_at set and is hidden
+-- from every listing; its card_card and card_tag rows are moved to the
+-- trashed_* tables below so the live relation graph never reaches it, and
+-- moved back on restore. Cards trashed longer than the retention period
+-- (MEMOAPP_TRASH_RETENTION_DAYS) are deleted for good in the background.
+ALTER TABLE cards
+  ADD COLUMN deleted_at DATETIME NULL,
+  ADD INDEX idx_cards_deleted_at (deleted_at);
+
+-- card_card rows removed with a trashed card. trashed_card_id is the card
+-- whose deletion removed the row; a relation between two trashed cards is
+-- handed to the other card when the first is restored.
+CREATE TABLE trashed_card_card (
+  trashed_card_id BIGINT   NOT NULL,
+  card_parent_id  BIGINT   NOT NULL,
+  card_child_id   BIGINT   NOT NULL,
+  connector       TEXT,
+  created_at      DATETIME NOT NULL,
+  PRIMARY KEY (card_parent_id, card_child_id),
+  INDEX idx_trashed_card_card_card (trashed_card_id),
+  FOREIGN KEY (trashed_card_id) REFERENCES cards (id) ON DELETE CASCADE,
+  FOREIGN KEY (card_parent_id) REFERENCES cards (id) ON DELETE CASCADE,
+  FOREIGN KEY (card_child_id) REFERENCES cards (id) ON DELETE CASCADE
+);
+
+CREATE TABLE trashed_card_tag (
+  card_id    BIGINT   NOT NULL,
+  tag_id     BIGINT   NOT NULL,
+  created_at DATETIME NOT NULL,
+  PRIMARY KEY (card_id, tag_id),
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE,
+  FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE CASCADE
+);
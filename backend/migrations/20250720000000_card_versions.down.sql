@@ -0,0 +1,3 @@
+ALTER TABLE trashed_card_card DROP COLUMN version;
+ALTER TABLE card_card DROP COLUMN version;
+ALTER TABLE cards DROP COLUMN version;
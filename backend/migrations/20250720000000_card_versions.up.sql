@@ -0,0 +1,7 @@
+-- Optimistic concurrency. Every change to a card or relation row bumps its
+-- version; the API returns it as the ETag and refuses If-Match writes made
+-- against an older one. Trashed relations keep theirs so a restore can
+-- continue from it.
+ALTER TABLE cards ADD COLUMN version INT NOT NULL DEFAULT 1;
+ALTER TABLE card_card ADD COLUMN version INT NOT NULL DEFAULT 1;
+ALTER TABLE trashed_card_card ADD COLUMN version INT NOT NULL DEFAULT 1;
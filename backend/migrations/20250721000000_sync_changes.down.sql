@@ -0,0 +1,14 @@
+DROP TRIGGER card_tag_sync_delete;
+DROP TRIGGER card_tag_sync_insert;
+DROP TRIGGER tags_sync_delete;
+DROP TRIGGER tags_sync_update;
+DROP TRIGGER tags_sync_insert;
+DROP TRIGGER card_card_sync_delete;
+DROP TRIGGER card_card_sync_update;
+DROP TRIGGER card_card_sync_insert;
+DROP TRIGGER cards_sync_delete;
+DROP TRIGGER cards_sync_update;
+DROP TRIGGER cards_sync_insert;
+DROP TABLE sync_lock;
+DROP TABLE sync_changes;
+DROP TABLE sync_pending;
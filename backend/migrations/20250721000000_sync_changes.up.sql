@@ -0,0 +1,71 @@
+-- Change feed behind /changes. Triggers add a sync_pending row for every
+-- insert, update or delete of a card, relation or tag, inside the writing
+-- transaction. stamp_changes moves committed pending rows into sync_changes
+-- under the sync_lock row, so seq follows the order changes became visible
+-- and a client that read up to some seq can never miss a smaller one
+-- committed later. It runs after every successful write request (the
+-- stamp_after_writes middleware), after each trash purge, and in /sync
+-- before it returns the feed; GET /changes only reads. sync_changes keeps
+-- one row per entity (its latest change); the feed returns current state,
+-- and a tombstone for what no longer exists.
+--
+-- entity is 'card', 'relation' or 'tag'. entity_id is the card or tag id,
+-- or the parent of a relation, whose child is child_id (0 otherwise).
+-- card_tag changes are recorded as changes of their card.
+CREATE TABLE sync_pending (
+  id        BIGINT     AUTO_INCREMENT PRIMARY KEY,
+  entity    VARCHAR(8) NOT NULL,
+  entity_id BIGINT     NOT NULL,
+  child_id  BIGINT     NOT NULL DEFAULT 0
+);
+
+CREATE TABLE sync_changes (
+  seq       BIGINT     AUTO_INCREMENT PRIMARY KEY,
+  entity    VARCHAR(8) NOT NULL,
+  entity_id BIGINT     NOT NULL,
+  child_id  BIGINT     NOT NULL DEFAULT 0,
+  UNIQUE KEY uq_sync_changes_entity (entity, entity_id, child_id)
+);
+
+CREATE TABLE sync_lock (
+  id TINYINT PRIMARY KEY
+);
+INSERT INTO sync_lock (id) VALUES (1);
+
+CREATE TRIGGER cards_sync_insert AFTER INSERT ON cards FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('card', NEW.id);
+CREATE TRIGGER cards_sync_update AFTER UPDATE ON cards FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('card', NEW.id);
+CREATE TRIGGER cards_sync_delete AFTER DELETE ON cards FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('card', OLD.id);
+
+CREATE TRIGGER card_card_sync_insert AFTER INSERT ON card_card FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id, child_id)
+  VALUES ('relation', NEW.card_parent_id, NEW.card_child_id);
+CREATE TRIGGER card_card_sync_update AFTER UPDATE ON card_card FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id, child_id)
+  VALUES ('relation', NEW.card_parent_id, NEW.card_child_id);
+CREATE TRIGGER card_card_sync_delete AFTER DELETE ON card_card FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id, child_id)
+  VALUES ('relation', OLD.card_parent_id, OLD.card_child_id);
+
+CREATE TRIGGER tags_sync_insert AFTER INSERT ON tags FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('tag', NEW.id);
+CREATE TRIGGER tags_sync_update AFTER UPDATE ON tags FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('tag', NEW.id);
+CREATE TRIGGER tags_sync_delete AFTER DELETE ON tags FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('tag', OLD.id);
+
+CREATE TRIGGER card_tag_sync_insert AFTER INSERT ON card_tag FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('card', NEW.card_id);
+CREATE TRIGGER card_tag_sync_delete AFTER DELETE ON card_tag FOR EACH ROW
+  INSERT INTO sync_pending (entity, entity_id) VALUES ('card', OLD.card_id);
+
+-- Everything that exists now is in the feed, so since=0 returns the whole
+-- board.
+INSERT INTO sync_changes (entity, entity_id)
+SELECT 'card', id FROM cards WHERE deleted_at IS NULL ORDER BY id;
+INSERT INTO sync_changes (entity, entity_id, child_id)
+SELECT 'relation', card_parent_id, card_child_id FROM card_card;
+INSERT INTO sync_changes (entity, entity_id)
+SELECT 'tag', id FROM tags ORDER BY id;
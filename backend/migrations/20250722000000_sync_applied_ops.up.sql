@@ -0,0 +1,11 @@
+-- Operations from /sync that were applied, by the client's op_id, so a queue
+-- uploaded again after a lost response is not applied twice. card_id is the
+-- card the operation created: the new card of a create, or the conflict copy
+-- of an update. Rows older than 30 days are pruned by /sync itself.
+CREATE TABLE sync_applied_ops (
+  op_id      VARCHAR(64) PRIMARY KEY,
+  card_id    BIGINT      NULL,
+  created_at DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  INDEX idx_sync_applied_ops_created (created_at),
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE
+);
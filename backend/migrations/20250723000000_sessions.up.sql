@@ -0,0 +1,14 @@
+-- Login sessions, shared by every backend instance and kept across restarts.
+-- token_hash is the SHA-256 (hex) of the cookie value, so the table alone
+-- cannot be used to log in. Expiry slides: each use pushes expires_at to
+-- MEMOAPP_SESSION_TTL_DAYS after last_seen_at. Expired rows are swept in the
+-- background.
+CREATE TABLE sessions (
+  token_hash   CHAR(64) PRIMARY KEY,
+  user_id      BIGINT   NOT NULL,
+  created_at   DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_seen_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at   DATETIME NOT NULL,
+  INDEX idx_sessions_expires (expires_at),
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+DROP TABLE card_acl;
+
+ALTER TABLE cards
+  DROP FOREIGN KEY fk_cards_owner,
+  DROP COLUMN owner_id;
@@ -0,0 +1,26 @@
+-- Card ownership and per-frame access control lists.
+-- owner_id is the user who created the card. NULL for cards created before
+-- cards had owners, or whose owner was deleted; only admins may change the
+-- ACL of those. An owner can always edit their own cards.
+-- card_acl grants one user or everyone with one role read or write access to
+-- a frame and everything under it in card_card. On each path up card_card
+-- the nearest frame that has ACL rows decides; a card reachable on a path
+-- with no ACL above it is open to every logged-in user, as before. Public
+-- cards stay readable by everyone.
+ALTER TABLE cards
+  ADD COLUMN owner_id BIGINT NULL,
+  ADD CONSTRAINT fk_cards_owner FOREIGN KEY (owner_id) REFERENCES users (id) ON DELETE SET NULL;
+
+CREATE TABLE card_acl (
+  id         BIGINT AUTO_INCREMENT PRIMARY KEY,
+  card_id    BIGINT      NOT NULL,
+  user_id    BIGINT      NULL,
+  role       VARCHAR(32) NULL,
+  permission VARCHAR(8)  NOT NULL,
+  created_at DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  INDEX idx_card_acl_card (card_id),
+  FOREIGN KEY (card_id) REFERENCES cards (id) ON DELETE CASCADE,
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+  CHECK ((user_id IS NULL) <> (role IS NULL)),
+  CHECK (permission IN ('read', 'write'))
+);
@@ -0,0 +1,12 @@
+ALTER TABLE users
+  ADD COLUMN api_key_hash VARCHAR(255) NULL,
+  ADD COLUMN api_key_prefix VARCHAR(16) NULL;
+
+-- Each user keeps their newest token.
+UPDATE users u
+JOIN api_tokens t ON t.id = (
+  SELECT MAX(id) FROM api_tokens WHERE user_id = u.id
+)
+SET u.api_key_hash = t.token_hash, u.api_key_prefix = t.token_prefix;
+
+DROP TABLE api_tokens;
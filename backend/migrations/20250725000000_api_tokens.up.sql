@@ -0,0 +1,30 @@
+-- Named API tokens, several per user, replacing users.api_key_hash. A token
+-- is found by its first characters (token_prefix) and checked against the
+-- argon2 token_hash. scopes limits what it may do on top of the owner's
+-- role: read (private cards and flash cards), flashcards (reading and
+-- answering flash cards), write (editing) and admin (user management).
+-- last_used_at is refreshed at most every few minutes.
+CREATE TABLE api_tokens (
+  id           BIGINT       PRIMARY KEY AUTO_INCREMENT,
+  user_id      BIGINT       NOT NULL,
+  name         VARCHAR(100) NOT NULL,
+  token_prefix VARCHAR(16)  NOT NULL,
+  token_hash   VARCHAR(255) NOT NULL,
+  scopes       SET('read', 'write', 'flashcards', 'admin') NOT NULL,
+  expires_at   DATETIME     NULL,
+  last_used_at DATETIME     NULL,
+  created_at   DATETIME     NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  INDEX idx_api_tokens_prefix (token_prefix),
+  INDEX idx_api_tokens_user (user_id),
+  FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Existing keys keep working with every scope, as before.
+INSERT INTO api_tokens (user_id, name, token_prefix, token_hash, scopes)
+SELECT id, 'default', api_key_prefix, api_key_hash, 'read,write,flashcards,admin'
+FROM users
+WHERE api_key_hash IS NOT NULL AND api_key_prefix IS NOT NULL;
+
+ALTER TABLE users
+  DROP COLUMN api_key_hash,
+  DROP COLUMN api_key_prefix;
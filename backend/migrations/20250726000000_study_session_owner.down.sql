@@ -0,0 +1,3 @@
+ALTER TABLE study_sessions
+  DROP FOREIGN KEY fk_study_sessions_user,
+  DROP COLUMN user_id;
@@ -0,0 +1,7 @@
+-- Study sessions belong to the user who started them; only they (and
+-- admins) may read or drive one. Sessions started before this have no
+-- owner and are left to admins.
+ALTER TABLE study_sessions
+  ADD COLUMN user_id BIGINT NULL AFTER id,
+  ADD CONSTRAINT fk_study_sessions_user
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
@@ -0,0 +1,12 @@
+-- Keep the first application of each op id, so op_id can be the key again.
+DELETE later FROM sync_applied_ops later
+JOIN sync_applied_ops earlier ON earlier.op_id = later.op_id AND earlier.id < later.id;
+
+ALTER TABLE sync_applied_ops
+  DROP FOREIGN KEY fk_sync_applied_ops_user;
+
+ALTER TABLE sync_applied_ops
+  DROP INDEX uq_sync_applied_ops_user_op,
+  DROP COLUMN user_id,
+  DROP COLUMN id,
+  ADD PRIMARY KEY (op_id);
@@ -0,0 +1,11 @@
+-- Op ids are made by each client, so two users can pick the same one. An
+-- operation is a duplicate only when the same user applied it before.
+-- Operations recorded before this have no user and only match uploads
+-- without one; they are pruned after 30 days anyway.
+ALTER TABLE sync_applied_ops
+  DROP PRIMARY KEY,
+  ADD COLUMN id BIGINT AUTO_INCREMENT PRIMARY KEY FIRST,
+  ADD COLUMN user_id BIGINT NULL AFTER id,
+  ADD UNIQUE INDEX uq_sync_applied_ops_user_op (user_id, op_id),
+  ADD CONSTRAINT fk_sync_applied_ops_user
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
@@ -0,0 +1,585 @@
+//! Who may read or write which card.
+//!
+//! Cards have an owner, and frames may carry an ACL (`card_acl`) granting a
+//! user or a role `read` or `write`. A card's access comes from the frames
+//! above it in `card_card`: on each path up, the nearest frame with an ACL
+//! decides, and the most permissive path wins. A path that reaches the top
+//! without meeting an ACL leaves the card open to every logged-in user.
+//! On top of that:
+//! - admins may do anything;
+//! - owners may always write their own cards, and frame owners the cards
+//!   their frame's ACL governs;
+//! - public cards are readable by everyone, anonymous visitors included;
+//! - roles below editor never write, whatever an ACL grants.
+//!
+//! Reads check against `AccessCache`, a snapshot of owners, ACLs and
+//! `card_card` shared by all requests and dropped after every write that
+//! could change them. Writes check against the database as it is.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::Request,
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    auth::{Access, RequestUser, SessionUser},
+    db::{fetch_card_access_rows, fetch_card_acl_rules, fetch_card_parent_edges},
+    title_index::writes_cards,
+};
+
+/// Visibility that lets anyone read a card.
+const VISIBILITY_PUBLIC: &str = "public";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    None,
+    Read,
+    Write,
+}
+
+impl Permission {
+    /// Parse a `card_acl.permission` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(Permission::Read),
+            "write" => Some(Permission::Write),
+            _ => None,
+        }
+    }
+}
+
+struct CardInfo {
+    owner_id: Option<i64>,
+    public: bool,
+}
+
+struct AclRule {
+    user_id: Option<i64>,
+    role: Option<String>,
+    permission: Permission,
+}
+
+/// Owners, visibility, ACLs and parents of every card, trashed ones
+/// included.
+#[derive(Default)]
+struct AccessGraph {
+    cards: HashMap<i64, CardInfo>,
+    /// ACL rules by frame. Frames without rules have no entry.
+    rules: HashMap<i64, Vec<AclRule>>,
+    /// Parents of each card. Only loaded when some frame has an ACL.
+    parents: HashMap<i64, Vec<i64>>,
+}
+
+impl AccessGraph {
+    async fn fetch(pool: &Pool<MySql>) -> Result<Self, sqlx::Error> {
+        let (cards, rules) =
+            tokio::try_join!(fetch_card_access_rows(pool), fetch_card_acl_rules(pool))?;
+        let mut graph = AccessGraph {
+            cards: cards
+                .into_iter()
+                .map(|card| {
+                    let info = CardInfo {
+                        owner_id: card.owner_id,
+                        public: card.visibility == VISIBILITY_PUBLIC,
+                    };
+                    (card.id, info)
+                })
+                .collect(),
+            ..AccessGraph::default()
+        };
+        for rule in rules {
+            let Some(permission) = Permission::parse(&rule.permission) else {
+                continue;
+            };
+            graph.rules.entry(rule.card_id).or_default().push(AclRule {
+                user_id: rule.user_id,
+                role: rule.role,
+                permission,
+            });
+        }
+
+        // Without any ACL every card is open, so the edges are not needed.
+        if !graph.rules.is_empty() {
+            for (parent_id, child_id) in fetch_card_parent_edges(pool).await? {
+                graph.parents.entry(child_id).or_default().push(parent_id);
+            }
+        }
+        Ok(graph)
+    }
+}
+
+#[derive(Default)]
+struct CachedGraph {
+    /// Bumped by every `invalidate`, so a load that overlapped a write is
+    /// not kept.
+    generation: u64,
+    graph: Option<Arc<AccessGraph>>,
+}
+
+/// The access graph for read requests, loaded on first use and dropped by
+/// `refresh_after_writes`.
+#[derive(Clone)]
+pub struct AccessCache {
+    pool: Pool<MySql>,
+    cached: Arc<Mutex<CachedGraph>>,
+}
+
+impl AccessCache {
+    pub fn new(pool: Pool<MySql>) -> Self {
+        Self {
+            pool,
+            cached: Arc::default(),
+        }
+    }
+
+    /// Drop the snapshot; the next read loads it again.
+    pub fn invalidate(&self) {
+        let mut cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        cached.generation += 1;
+        cached.graph = None;
+    }
+
+    async fn graph(&self) -> Result<Arc<AccessGraph>, sqlx::Error> {
+        let generation = {
+            let cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(graph) = &cached.graph {
+                return Ok(graph.clone());
+            }
+            cached.generation
+        };
+
+        let graph = Arc::new(AccessGraph::fetch(&self.pool).await?);
+        let mut cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        if cached.generation == generation {
+            cached.graph = Some(graph.clone());
+        }
+        Ok(graph)
+    }
+}
+
+/// Routes that can change owners, visibility, ACLs or `card_card`.
+fn writes_access(method: &Method, path: &str) -> bool {
+    writes_cards(method, path)
+        || (method == Method::PUT && path.starts_with("/cards/") && path.ends_with("/acl"))
+}
+
+/// Drop the access snapshot after every write that may have changed it,
+/// whatever the outcome, so no read is checked against a stale ACL.
+pub async fn refresh_after_writes(
+    Extension(cache): Extension<AccessCache>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_write = writes_access(req.method(), req.uri().path());
+    let response = next.run(req).await;
+    if is_write {
+        cache.invalidate();
+    }
+    response
+}
+
+/// Everything needed to answer access questions for one request.
+pub struct CardAccess {
+    user: Option<SessionUser>,
+    graph: Arc<AccessGraph>,
+    /// Cards created during this request, which `graph` does not know yet.
+    created: HashMap<i64, CardInfo>,
+    /// Parents set with `set_parents`, in place of those in `graph`.
+    parents: HashMap<i64, Vec<i64>>,
+    /// `inherited` results, which only depend on the card. A `Mutex` so the
+    /// access can be borrowed across `.await`s.
+    inherited: Mutex<HashMap<i64, Permission>>,
+}
+
+impl CardAccess {
+    fn new(user: Option<SessionUser>, graph: Arc<AccessGraph>) -> Self {
+        CardAccess {
+            user,
+            graph,
+            created: HashMap::new(),
+            parents: HashMap::new(),
+            inherited: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Access for a read by `user`, or by an anonymous visitor, from the
+    /// cached graph. Admins need none.
+    pub async fn load(cache: &AccessCache, user: Option<SessionUser>) -> Result<Self, sqlx::Error> {
+        let access = Self::new(user, Arc::default());
+        if access.is_admin() {
+            return Ok(access);
+        }
+        Ok(Self::new(access.user, cache.graph().await?))
+    }
+
+    /// Access for a write by the user `require_write_auth` found, read from
+    /// the database rather than the cache.
+    pub async fn for_request(
+        pool: &Pool<MySql>,
+        user: Option<&RequestUser>,
+    ) -> Result<Self, sqlx::Error> {
+        let access = Self::new(user.map(|user| user.user.clone()), Arc::default());
+        if access.is_admin() {
+            return Ok(access);
+        }
+        Ok(Self::new(
+            access.user,
+            Arc::new(AccessGraph::fetch(pool).await?),
+        ))
+    }
+
+    fn card(&self, card_id: i64) -> Option<&CardInfo> {
+        self.created
+            .get(&card_id)
+            .or_else(|| self.graph.cards.get(&card_id))
+    }
+
+    /// No one is logged in; only public cards are readable.
+    pub fn is_anonymous(&self) -> bool {
+        self.user.is_none()
+    }
+
+    fn is_admin(&self) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|user| user.can(Access::Admin))
+    }
+
+    /// What the user may do with a card. Unknown cards get `None`.
+    pub fn permission(&self, card_id: i64) -> Permission {
+        if self.is_admin() {
+            return Permission::Write;
+        }
+        let Some(card) = self.card(card_id) else {
+            return Permission::None;
+        };
+        let public = if card.public {
+            Permission::Read
+        } else {
+            Permission::None
+        };
+        let Some(user) = &self.user else {
+            return public;
+        };
+
+        let granted = if card.owner_id == Some(user.user_id) {
+            Permission::Write
+        } else {
+            self.inherited(card_id)
+        };
+        let granted = if user.can(Access::Write) {
+            granted
+        } else {
+            granted.min(Permission::Read)
+        };
+        granted.max(public)
+    }
+
+    pub fn can_read(&self, card_id: i64) -> bool {
+        self.permission(card_id) >= Permission::Read
+    }
+
+    /// `Err` is the status and message to answer with when the user may not
+    /// write every one of `card_ids`: `404` for cards they cannot even see.
+    pub fn check_write(&self, card_ids: &[i64]) -> Result<(), (StatusCode, String)> {
+        for &card_id in card_ids {
+            match self.permission(card_id) {
+                Permission::Write => {}
+                Permission::Read => {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        format!("no write access to card {card_id}"),
+                    ))
+                }
+                Permission::None => {
+                    return Err((StatusCode::NOT_FOUND, format!("card {card_id} not found")))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `check_write`, for reading.
+    pub fn check_read(&self, card_id: i64) -> Result<(), (StatusCode, String)> {
+        if self.can_read(card_id) {
+            Ok(())
+        } else {
+            Err((StatusCode::NOT_FOUND, format!("card {card_id} not found")))
+        }
+    }
+
+    /// Whether the user owns the card. Owners (and admins) manage its ACL.
+    pub fn owns(&self, card_id: i64) -> bool {
+        self.is_admin()
+            || self.user.as_ref().is_some_and(|user| {
+                self.card(card_id)
+                    .is_some_and(|card| card.owner_id == Some(user.user_id))
+            })
+    }
+
+    /// Record a card the user just created, so later checks in the same
+    /// request see it as theirs.
+    pub fn add_created(&mut self, card_id: i64) {
+        let owner_id = self.user.as_ref().map(|user| user.user_id);
+        self.created.insert(
+            card_id,
+            CardInfo {
+                owner_id,
+                public: false,
+            },
+        );
+    }
+
+    /// Check a card as if it had `parents`, e.g. a trashed card about to get
+    /// its relations back.
+    pub fn set_parents(&mut self, card_id: i64, parents: Vec<i64>) {
+        if !self.graph.rules.is_empty() {
+            self.parents.insert(card_id, parents);
+            self.inherited
+                .get_mut()
+                .unwrap_or_else(|e| e.into_inner())
+                .clear();
+        }
+    }
+
+    /// Access the ACLs above a card (itself included) give the user.
+    fn inherited(&self, card_id: i64) -> Permission {
+        let cached = self
+            .inherited
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&card_id)
+            .copied();
+        if let Some(permission) = cached {
+            return permission;
+        }
+        let permission = match self.graph.rules.get(&card_id) {
+            Some(rules) => self.granted(card_id, rules),
+            None => self
+                .parents
+                .get(&card_id)
+                .or_else(|| self.graph.parents.get(&card_id))
+                // `card_card` is kept acyclic by `has_cycle`.
+                .and_then(|parents| {
+                    parents
+                        .iter()
+                        .map(|parent_id| self.inherited(*parent_id))
+                        .max()
+                })
+                .unwrap_or(Permission::Write),
+        };
+        self.inherited
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(card_id, permission);
+        permission
+    }
+
+    /// Access a frame's own ACL gives the user.
+    fn granted(&self, frame_id: i64, rules: &[AclRule]) -> Permission {
+        let Some(user) = &self.user else {
+            return Permission::None;
+        };
+        let owner = self
+            .card(frame_id)
+            .is_some_and(|frame| frame.owner_id == Some(user.user_id));
+        if owner {
+            return Permission::Write;
+        }
+        rules
+            .iter()
+            .filter(|rule| {
+                rule.user_id == Some(user.user_id) || rule.role.as_deref() == Some(&user.role)
+            })
+            .map(|rule| rule.permission)
+            .max()
+            .unwrap_or(Permission::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN_FRAME: i64 = 20;
+
+    /// Frame 1 grants user 2 read; card 10 is under it and card 11 (owned
+    /// by user 4) under 10. Frame 3 is owned by user 5, grants user 2 write
+    /// and holds card 31. Card 12 is under frame 1 and the open frame 20.
+    /// Card 13 is public, under frame 1.
+    fn graph() -> Arc<AccessGraph> {
+        let mut graph = AccessGraph::default();
+        for (id, owner_id, public) in [
+            (1, None, false),
+            (3, Some(5), false),
+            (10, None, false),
+            (11, Some(4), false),
+            (12, None, false),
+            (13, None, true),
+            (OPEN_FRAME, None, false),
+            (31, None, false),
+        ] {
+            graph.cards.insert(id, CardInfo { owner_id, public });
+        }
+        for (frame_id, user_id, permission) in [(1, 2, Permission::Read), (3, 2, Permission::Write)]
+        {
+            graph.rules.entry(frame_id).or_default().push(AclRule {
+                user_id: Some(user_id),
+                role: None,
+                permission,
+            });
+        }
+        for (parent_id, child_id) in [
+            (1, 10),
+            (10, 11),
+            (1, 12),
+            (OPEN_FRAME, 12),
+            (1, 13),
+            (3, 31),
+        ] {
+            graph.parents.entry(child_id).or_default().push(parent_id);
+        }
+        Arc::new(graph)
+    }
+
+    fn access(user: Option<(i64, &str)>) -> CardAccess {
+        let user = user.map(|(user_id, role)| SessionUser {
+            user_id,
+            role: role.to_string(),
+        });
+        CardAccess::new(user, graph())
+    }
+
+    fn access_of(user_id: i64) -> CardAccess {
+        access(Some((user_id, "editor")))
+    }
+
+    #[test]
+    fn acl_is_inherited_down_card_card() {
+        let granted = access(Some((2, "editor")));
+        assert!(granted.permission(1) == Permission::Read);
+        assert!(granted.permission(11) == Permission::Read);
+
+        let other = access(Some((6, "editor")));
+        assert!(other.permission(10) == Permission::None);
+        assert!(other.permission(11) == Permission::None);
+    }
+
+    #[test]
+    fn cards_without_acl_are_open_to_logged_in_users() {
+        assert!(access(Some((6, "editor"))).permission(OPEN_FRAME) == Permission::Write);
+        assert!(access(Some((6, "viewer"))).permission(OPEN_FRAME) == Permission::Read);
+        assert!(access(None).permission(OPEN_FRAME) == Permission::None);
+    }
+
+    #[test]
+    fn most_permissive_path_wins() {
+        // Card 12 is also under the open frame.
+        assert!(access(Some((6, "editor"))).permission(12) == Permission::Write);
+        assert!(access(Some((2, "editor"))).permission(12) == Permission::Write);
+    }
+
+    #[test]
+    fn role_rules_match_the_users_role() {
+        let mut graph = AccessGraph::default();
+        graph.cards.insert(
+            1,
+            CardInfo {
+                owner_id: None,
+                public: false,
+            },
+        );
+        graph.rules.entry(1).or_default().push(AclRule {
+            user_id: None,
+            role: Some("reviewer".to_string()),
+            permission: Permission::Read,
+        });
+        let graph = Arc::new(graph);
+        let user = |role: &str| {
+            Some(SessionUser {
+                user_id: 7,
+                role: role.to_string(),
+            })
+        };
+
+        assert!(CardAccess::new(user("reviewer"), graph.clone()).can_read(1));
+        assert!(!CardAccess::new(user("editor"), graph).can_read(1));
+    }
+
+    #[test]
+    fn owners_write_their_cards_and_frames() {
+        // User 4 owns card 11 though frame 1 shuts them out.
+        assert!(access(Some((4, "editor"))).permission(11) == Permission::Write);
+        // User 5 owns frame 3, so its ACL does not apply to them.
+        assert!(access(Some((5, "editor"))).permission(31) == Permission::Write);
+        assert!(access(Some((2, "editor"))).permission(31) == Permission::Write);
+        // Roles below editor never write, not even their own cards.
+        assert!(access(Some((4, "viewer"))).permission(11) == Permission::Read);
+        assert!(access(Some((2, "viewer"))).permission(31) == Permission::Read);
+    }
+
+    #[test]
+    fn public_cards_are_readable_by_everyone() {
+        assert!(access(None).permission(13) == Permission::Read);
+        assert!(access(Some((6, "editor"))).permission(13) == Permission::Read);
+        assert!(access(None).permission(10) == Permission::None);
+    }
+
+    #[test]
+    fn admins_may_do_anything() {
+        let admin = access(Some((1, "admin")));
+        assert!(admin.permission(11) == Permission::Write);
+        assert!(admin.permission(999) == Permission::Write);
+        assert!(admin.owns(31));
+    }
+
+    #[test]
+    fn unknown_cards_are_hidden() {
+        let access = access(Some((6, "editor")));
+        assert!(access.permission(999) == Permission::None);
+        assert_eq!(access.check_read(999).unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn check_write_tells_hidden_from_read_only() {
+        let access = access(Some((2, "editor")));
+        assert!(access.check_write(&[31, OPEN_FRAME]).is_ok());
+        assert_eq!(
+            access.check_write(&[31, 10]).unwrap_err().0,
+            StatusCode::FORBIDDEN
+        );
+
+        let other = access_of(6);
+        assert_eq!(
+            other.check_write(&[10]).unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn created_cards_belong_to_their_creator() {
+        let mut access = access_of(6);
+        assert!(access.permission(100) == Permission::None);
+        access.add_created(100);
+        assert!(access.permission(100) == Permission::Write);
+        assert!(access.owns(100));
+    }
+
+    #[test]
+    fn set_parents_checks_a_card_under_new_parents() {
+        let mut access = access_of(6);
+        // Card 31 sits under frame 3, which shuts user 6 out.
+        assert!(access.permission(31) == Permission::None);
+        access.set_parents(31, vec![OPEN_FRAME]);
+        assert!(access.permission(31) == Permission::Write);
+    }
+}
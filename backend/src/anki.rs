@@ -0,0 +1,79 @@
+//! Anki `.apkg` packages: a zip holding a legacy (schema 11)
+//! `collection.anki2` SQLite database and a `media` manifest. Imports also
+//! read the newer `collection.anki21` (same schema) when present.
+
+use sha1::{Digest, Sha1};
+
+mod export;
+pub use export::{build_apkg, AnkiNote};
+
+mod import;
+pub use import::{read_apkg, ImportedNote};
+
+/// Id of the "Basic" note type written by the exporter. Anki matches note
+/// types by id on import, so this must never change.
+const MODEL_ID: i64 = 1_720_569_600_000;
+
+/// Note and card ids are derived from the card id so that they are stable
+/// across exports. The offset keeps them in Anki's millisecond-timestamp range.
+const NOTE_ID_OFFSET: i64 = 1_500_000_000_000;
+
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+const COLLECTION_SCHEMA: &str = r#"
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null,
+    scm integer not null, ver integer not null, dty integer not null,
+    usn integer not null, ls integer not null, conf text not null,
+    models text not null, decks text not null, dconf text not null,
+    tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null,
+    mod integer not null, usn integer not null, tags text not null,
+    flds text not null, sfld integer not null, csum integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null,
+    ord integer not null, mod integer not null, usn integer not null,
+    type integer not null, queue integer not null, due integer not null,
+    ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null,
+    odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null,
+    ease integer not null, ivl integer not null, lastIvl integer not null,
+    factor integer not null, time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+"#;
+
+/// Anki note GUID for a card. Re-exporting the same card produces the same
+/// GUID, which makes Anki update the existing note instead of adding a copy.
+pub fn note_guid(card_id: i64) -> String {
+    format!("mdmap-card-{card_id}")
+}
+
+/// Stable deck id for a deck name.
+fn deck_id(name: &str) -> i64 {
+    let digest = Sha1::digest(name.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes[2..].copy_from_slice(&digest[..6]);
+    i64::from_be_bytes(bytes).max(2)
+}
+
+/// Anki's duplicate-check checksum: the first 32 bits of the SHA-1 of the
+/// sort field.
+fn field_checksum(field: &str) -> i64 {
+    let digest = Sha1::digest(field.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as i64
+}
@@ -0,0 +1,279 @@
+use std::io::Write;
+
+use chrono::{NaiveDateTime, Utc};
+use rand::{distr::Alphanumeric, Rng};
+use serde_json::json;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    ConnectOptions, Connection,
+};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use super::{
+    deck_id, field_checksum, note_guid, COLLECTION_SCHEMA, FIELD_SEPARATOR, MODEL_ID,
+    NOTE_ID_OFFSET,
+};
+use crate::markdown::escape_html;
+
+/// One card to be written as an Anki note.
+pub struct AnkiNote {
+    pub card_id: i64,
+    /// Plain-text front (the card's first H1).
+    pub front: String,
+    /// HTML back (the rest of the card's markdown, rendered).
+    pub back_html: String,
+    pub tags: Vec<String>,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Build an `.apkg` containing `notes` in a deck called `deck_name`.
+pub async fn build_apkg(deck_name: &str, notes: &[AnkiNote]) -> Result<Vec<u8>, String> {
+    let suffix: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+    let path = std::env::temp_dir().join(format!("mdmap-export-{suffix}.anki2"));
+
+    let result = write_collection(&path, deck_name, notes).await;
+    let collection = result.and_then(|_| std::fs::read(&path).map_err(|e| e.to_string()));
+    let _ = std::fs::remove_file(&path);
+
+    package(&collection?)
+}
+
+async fn write_collection(
+    path: &std::path::Path,
+    deck_name: &str,
+    notes: &[AnkiNote],
+) -> Result<(), String> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Delete)
+        .connect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let now = Utc::now();
+    let now_ms = now.timestamp_millis();
+    let did = deck_id(deck_name);
+
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+
+    for statement in COLLECTION_SCHEMA
+        .split(';')
+        .filter(|statement| !statement.trim().is_empty())
+    {
+        sqlx::query(statement)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    sqlx::query(
+        "INSERT INTO col (id, crt, mod, scm, ver, dty, usn, ls, conf, models, decks, dconf, tags) \
+         VALUES (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}')",
+    )
+    .bind(now.timestamp() - now.timestamp() % 86400)
+    .bind(now_ms)
+    .bind(now_ms)
+    .bind(collection_conf(notes.len()).to_string())
+    .bind(models_json(did, now.timestamp()).to_string())
+    .bind(decks_json(did, deck_name, now.timestamp()).to_string())
+    .bind(deck_conf_json().to_string())
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    for (position, note) in notes.iter().enumerate() {
+        let id = NOTE_ID_OFFSET + note.card_id;
+        let modified = note.modified_at.and_utc().timestamp();
+        let fields = format!(
+            "{}{}{}",
+            escape_html(&note.front),
+            FIELD_SEPARATOR,
+            note.back_html
+        );
+        let tags = if note.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", note.tags.join(" "))
+        };
+
+        sqlx::query(
+            "INSERT INTO notes (id, guid, mid, mod, usn, tags, flds, sfld, csum, flags, data) \
+             VALUES (?, ?, ?, ?, -1, ?, ?, ?, ?, 0, '')",
+        )
+        .bind(id)
+        .bind(note_guid(note.card_id))
+        .bind(MODEL_ID)
+        .bind(modified)
+        .bind(&tags)
+        .bind(&fields)
+        .bind(&note.front)
+        .bind(field_checksum(&note.front))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // New card: type 0 / queue 0, `due` is the position in the new queue.
+        sqlx::query(
+            "INSERT INTO cards (id, nid, did, ord, mod, usn, type, queue, due, ivl, factor, \
+             reps, lapses, left, odue, odid, flags, data) \
+             VALUES (?, ?, ?, 0, ?, -1, 0, 0, ?, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+        )
+        .bind(id)
+        .bind(id)
+        .bind(did)
+        .bind(modified)
+        .bind(position as i64 + 1)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    conn.close().await.map_err(|e| e.to_string())
+}
+
+/// Zip the collection together with an (empty) media manifest.
+fn package(collection: &[u8]) -> Result<Vec<u8>, String> {
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("collection.anki2", options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(collection).map_err(|e| e.to_string())?;
+    zip.start_file("media", options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(b"{}").map_err(|e| e.to_string())?;
+
+    zip.finish()
+        .map(|cursor| cursor.into_inner())
+        .map_err(|e| e.to_string())
+}
+
+fn collection_conf(note_count: usize) -> serde_json::Value {
+    json!({
+        "activeDecks": [1],
+        "curDeck": 1,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": MODEL_ID.to_string(),
+        "nextPos": note_count + 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    })
+}
+
+fn models_json(did: i64, now: i64) -> serde_json::Value {
+    let field = |name: &str, ord: i64| {
+        json!({
+            "name": name,
+            "ord": ord,
+            "sticky": false,
+            "rtl": false,
+            "font": "Arial",
+            "size": 20,
+            "media": [],
+        })
+    };
+
+    json!({
+        MODEL_ID.to_string(): {
+            "id": MODEL_ID,
+            "name": "mdmap Basic",
+            "type": 0,
+            "mod": now,
+            "usn": -1,
+            "sortf": 0,
+            "did": did,
+            "tmpls": [{
+                "name": "Card 1",
+                "ord": 0,
+                "qfmt": "{{Front}}",
+                "afmt": "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}",
+                "did": null,
+                "bqfmt": "",
+                "bafmt": "",
+            }],
+            "flds": [field("Front", 0), field("Back", 1)],
+            "css": ".card {\n font-family: arial;\n font-size: 20px;\n color: black;\n background-color: white;\n}\n",
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "tags": [],
+            "vers": [],
+            "req": [[0, "all", [0]]],
+        }
+    })
+}
+
+fn decks_json(did: i64, name: &str, now: i64) -> serde_json::Value {
+    let deck = |id: i64, name: &str| {
+        json!({
+            "id": id,
+            "name": name,
+            "mod": now,
+            "usn": -1,
+            "lrnToday": [0, 0],
+            "revToday": [0, 0],
+            "newToday": [0, 0],
+            "timeToday": [0, 0],
+            "collapsed": false,
+            "browserCollapsed": false,
+            "desc": "",
+            "dyn": 0,
+            "conf": 1,
+            "extendNew": 0,
+            "extendRev": 0,
+        })
+    };
+
+    json!({
+        "1": deck(1, "Default"),
+        did.to_string(): deck(did, name),
+    })
+}
+
+fn deck_conf_json() -> serde_json::Value {
+    json!({
+        "1": {
+            "id": 1,
+            "name": "Default",
+            "mod": 0,
+            "usn": 0,
+            "maxTaken": 60,
+            "autoplay": true,
+            "timer": 0,
+            "replayq": true,
+            "dyn": false,
+            "new": {
+                "bury": false,
+                "delays": [1.0, 10.0],
+                "initialFactor": 2500,
+                "ints": [1, 4, 0],
+                "order": 1,
+                "perDay": 20,
+            },
+            "lapse": {
+                "delays": [10.0],
+                "leechAction": 1,
+                "leechFails": 8,
+                "minInt": 1,
+                "mult": 0.0,
+            },
+            "rev": {
+                "bury": false,
+                "ease4": 1.3,
+                "maxIvl": 36500,
+                "perDay": 200,
+                "hardFactor": 1.2,
+            },
+        }
+    })
+}
@@ -0,0 +1,147 @@
+use std::io::Read;
+
+use rand::{distr::Alphanumeric, Rng};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteConnection},
+    ConnectOptions, Connection, FromRow,
+};
+use zip::ZipArchive;
+
+use super::FIELD_SEPARATOR;
+use crate::markdown::html_to_text;
+
+/// Largest entry read out of an `.apkg`, decompressed. The upload itself is
+/// limited, but a small zip can still inflate to any size.
+const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// A note read from an `.apkg`, reduced to plain text.
+pub struct ImportedNote {
+    pub front: String,
+    pub back: String,
+    pub tags: Vec<String>,
+}
+
+/// The notes of an `.apkg`, plus the name of the deck most of its cards are
+/// in.
+pub struct ImportedDeck {
+    pub name: Option<String>,
+    pub notes: Vec<ImportedNote>,
+}
+
+#[derive(FromRow)]
+struct NoteRow {
+    flds: String,
+    tags: String,
+}
+
+/// Read the notes of an `.apkg`. Packages exported with "Support older Anki
+/// versions" unchecked (zstd-compressed `collection.anki21b` only) are not
+/// supported.
+pub async fn read_apkg(bytes: &[u8]) -> Result<ImportedDeck, String> {
+    let mut archive = ZipArchive::new(std::io::Cursor::new(bytes)).map_err(|e| e.to_string())?;
+
+    // Newer packages carry a stub `collection.anki2` next to the real
+    // `collection.anki21`.
+    let entry = ["collection.anki21", "collection.anki2"]
+        .into_iter()
+        .find(|name| archive.index_for_name(name).is_some());
+    let Some(entry) = entry else {
+        return Err(if archive.index_for_name("collection.anki21b").is_some() {
+            "collection.anki21b is not supported; export with \"Support older Anki versions\""
+                .to_string()
+        } else {
+            "not an Anki package: collection.anki2 is missing".to_string()
+        });
+    };
+
+    let collection = read_entry(&mut archive, entry)?;
+
+    let suffix: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+    let path = std::env::temp_dir().join(format!("mdmap-import-{suffix}.anki2"));
+
+    std::fs::write(&path, &collection).map_err(|e| e.to_string())?;
+    let result = read_collection(&path).await;
+    let _ = std::fs::remove_file(&path);
+
+    result
+}
+
+/// Read one entry of the archive, refusing to inflate past `MAX_ENTRY_BYTES`.
+fn read_entry(
+    archive: &mut ZipArchive<std::io::Cursor<&[u8]>>,
+    name: &str,
+) -> Result<Vec<u8>, String> {
+    let file = archive.by_name(name).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::new();
+    file.take(MAX_ENTRY_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    if bytes.len() as u64 > MAX_ENTRY_BYTES {
+        return Err(format!(
+            "{name} is larger than {} MB",
+            MAX_ENTRY_BYTES / 1024 / 1024
+        ));
+    }
+    Ok(bytes)
+}
+
+async fn read_collection(path: &std::path::Path) -> Result<ImportedDeck, String> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let rows = sqlx::query_as::<_, NoteRow>("SELECT flds, tags FROM notes ORDER BY id")
+        .fetch_all(&mut conn)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let name = main_deck_name(&mut conn).await;
+    let _ = conn.close().await;
+
+    let notes = rows
+        .into_iter()
+        .map(|row| {
+            let mut fields = row.flds.split(FIELD_SEPARATOR).map(html_to_text);
+            let front = fields.next().unwrap_or_default();
+            let back = fields
+                .filter(|field| !field.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            ImportedNote {
+                front,
+                back,
+                tags: row.tags.split_whitespace().map(str::to_string).collect(),
+            }
+        })
+        .filter(|note| !note.front.is_empty() || !note.back.is_empty())
+        .collect();
+
+    Ok(ImportedDeck { name, notes })
+}
+
+/// Name of the deck holding most cards, from the schema-11 `col.decks` JSON.
+async fn main_deck_name(conn: &mut SqliteConnection) -> Option<String> {
+    let did: i64 =
+        sqlx::query_scalar("SELECT did FROM cards GROUP BY did ORDER BY COUNT(*) DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await
+            .ok()??;
+    let decks: String = sqlx::query_scalar("SELECT decks FROM col LIMIT 1")
+        .fetch_optional(&mut *conn)
+        .await
+        .ok()??;
+
+    let decks: serde_json::Value = serde_json::from_str(&decks).ok()?;
+    decks
+        .get(did.to_string())?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
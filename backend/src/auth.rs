@@ -1,8 +1,4 @@
-use std::{
-    collections::HashMap,
-    env,
-    sync::{Arc, Mutex},
-};
+use std::{env, time::Duration};
 
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
@@ -22,21 +18,118 @@ use rand::{distr::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
 use sqlx::{MySql, Pool};
 
-use crate::models::{ApiResponse, UserRow};
+use crate::db::{
+    delete_expired_sessions, delete_session, delete_user_sessions, fetch_active_user_by_username,
+    fetch_active_user_row, fetch_api_token_candidates, fetch_session, insert_session,
+    set_user_password, touch_api_token, touch_session,
+};
+use crate::models::ApiResponse;
 
 const SESSION_COOKIE: &str = "memoapp_session";
 
-/// Who a session belongs to. Kept for future per-user / per-role features.
+/// Days an unused session lasts unless MEMOAPP_SESSION_TTL_DAYS is set.
+const DEFAULT_SESSION_TTL_DAYS: i64 = 30;
+
+/// A session's expiry is pushed back at most this often, so busy clients do
+/// not write on every request.
+const SESSION_TOUCH_SECS: i64 = 5 * 60;
+
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Leading characters of an API token stored in the clear, to find the
+/// token's row and to tell tokens apart in lists.
+const API_TOKEN_PREFIX_CHARS: usize = 12;
+
+/// Shortest password accepted when one is set or changed.
+const MIN_PASSWORD_CHARS: usize = 8;
+
+/// Roles a user can have.
+pub const ROLES: [&str; 4] = ["admin", "editor", "viewer", "reviewer"];
+
+/// What a request needs from the caller's role. Each role can do what the
+/// one before it can: every role reads (private cards included), reviewers
+/// also post flash-card results, editors also edit, admins also manage users.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Access {
+    Read,
+    Review,
+    Write,
+    Admin,
+}
+
+/// What an API token may be used for, on top of what its user's role allows.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scope {
+    /// Read private cards and flash cards.
+    Read,
+    /// Edit cards. Also covers answering flash cards.
+    Write,
+    /// Read and answer flash cards.
+    Flashcards,
+    /// Manage users.
+    Admin,
+}
+
+/// Names of the scopes, as stored in `api_tokens.scopes`.
+pub const SCOPES: [&str; 4] = ["read", "write", "flashcards", "admin"];
+
+impl Scope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(Scope::Read),
+            "write" => Some(Scope::Write),
+            "flashcards" => Some(Scope::Flashcards),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Who a session belongs to.
 #[derive(Clone)]
-#[allow(dead_code)]
 pub struct SessionUser {
     pub user_id: i64,
     pub role: String,
 }
 
+impl SessionUser {
+    /// Whether the user's role grants `access`. Unknown roles only read.
+    pub fn can(&self, access: Access) -> bool {
+        let granted = match self.role.as_str() {
+            "admin" => Access::Admin,
+            "editor" => Access::Write,
+            "reviewer" => Access::Review,
+            _ => Access::Read,
+        };
+        access <= granted
+    }
+}
+
+/// How a write request was authenticated.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AuthVia {
+    /// Browser session cookie.
+    Session,
+    /// `Authorization: Bearer` API token.
+    ApiToken,
+}
+
+/// The user behind a request that needed authentication (writes and user
+/// management). `require_write_auth` adds it to the request extensions, so handlers can take
+/// `Option<Extension<RequestUser>>` instead of checking credentials again.
+#[derive(Clone)]
+pub struct RequestUser {
+    pub user: SessionUser,
+    pub via: AuthVia,
+}
+
+/// Sessions live in the `sessions` table, so they survive restarts and are
+/// shared by every instance using the same database.
 #[derive(Clone)]
 pub struct AuthState {
-    sessions: Arc<Mutex<HashMap<String, SessionUser>>>,
+    pool: Pool<MySql>,
+    /// Sliding expiry: a session unused this long is gone.
+    session_ttl_days: i64,
     cookie_secure: bool,
     /// Optional server-side secret ("pepper") mixed into every password hash.
     /// Lives only in the environment, never in the database.
@@ -45,30 +138,54 @@ pub struct AuthState {
 
 #[derive(Deserialize)]
 pub struct LoginParams {
+    username: String,
     password: String,
 }
 
+#[derive(Deserialize)]
+pub struct ChangePasswordParams {
+    current_password: String,
+    new_password: String,
+}
+
 #[derive(Serialize)]
 pub struct AuthStatus {
     authenticated: bool,
     auth_enabled: bool,
+    /// Role of the logged-in user, so the UI can hide what it may not do.
+    role: Option<String>,
 }
 
-#[derive(Serialize)]
-pub struct ApiTokenResponse {
-    token: String,
-    prefix: String,
+/// The user behind a valid API token, and what the token may do.
+pub struct ApiTokenUser {
+    pub user: SessionUser,
+    scopes: Vec<Scope>,
 }
 
-#[derive(sqlx::FromRow)]
-struct ApiKeyUserRow {
-    id: i64,
-    role: String,
-    api_key_hash: String,
+impl ApiTokenUser {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Whether the token's scopes cover a route needing `access`. The user's
+    /// role is checked separately. Reads never get here; handlers resolve
+    /// their viewer with `AuthState::card_reader`.
+    pub fn allows(&self, access: Access) -> bool {
+        match access {
+            Access::Admin => self.has_scope(Scope::Admin),
+            Access::Write => self.has_scope(Scope::Write),
+            _ => self.has_scope(Scope::Flashcards) || self.has_scope(Scope::Write),
+        }
+    }
 }
 
 impl AuthState {
-    pub fn from_env() -> Self {
+    pub fn from_env(pool: Pool<MySql>) -> Self {
+        let session_ttl_days = env::var("MEMOAPP_SESSION_TTL_DAYS")
+            .ok()
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|days| *days > 0)
+            .unwrap_or(DEFAULT_SESSION_TTL_DAYS);
         let cookie_secure = env::var("MEMOAPP_COOKIE_SECURE")
             .map(|value| matches!(value.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"))
             .unwrap_or(false);
@@ -79,7 +196,8 @@ impl AuthState {
             .unwrap_or_default();
 
         Self {
-            sessions: Arc::new(Mutex::new(HashMap::new())),
+            pool,
+            session_ttl_days,
             cookie_secure,
             pepper,
         }
@@ -104,6 +222,18 @@ impl AuthState {
             .map_err(|e| e.to_string())
     }
 
+    /// Hash `password` for a user after checking it is long enough.
+    pub fn hash_new_password(&self, password: &str) -> Result<String, (StatusCode, String)> {
+        if password.chars().count() < MIN_PASSWORD_CHARS {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("password must be at least {MIN_PASSWORD_CHARS} characters"),
+            ));
+        }
+        self.hash_password(password)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+    }
+
     /// Verify a plaintext password against a stored argon2 PHC string.
     fn verify_password(&self, password: &str, hash: &str) -> bool {
         match PasswordHash::new(hash) {
@@ -115,74 +245,112 @@ impl AuthState {
         }
     }
 
-    pub fn is_authenticated(&self, headers: &HeaderMap) -> bool {
-        let Some(token) = session_token_from_headers(headers) else {
-            return false;
+    /// The user behind the current session, if any. Using a session pushes
+    /// its expiry back.
+    pub async fn current_user(&self, headers: &HeaderMap) -> Option<SessionUser> {
+        let token = session_token_from_headers(headers)?;
+        let session = match fetch_session(&self.pool, token, SESSION_TOUCH_SECS).await {
+            Ok(session) => session?,
+            Err(e) => {
+                eprintln!("session lookup failed: {e}");
+                return None;
+            }
         };
 
-        self.sessions
-            .lock()
-            .map(|sessions| sessions.contains_key(token))
-            .unwrap_or(false)
-    }
+        if session.needs_touch {
+            if let Err(e) = touch_session(&self.pool, token, self.session_ttl_days).await {
+                eprintln!("session touch failed: {e}");
+            }
+        }
 
-    /// The user behind the current session, if any. Reserved for future
-    /// per-user / role-based authorization.
-    #[allow(dead_code)]
-    pub fn current_user(&self, headers: &HeaderMap) -> Option<SessionUser> {
-        let token = session_token_from_headers(headers)?;
-        self.sessions
-            .lock()
-            .ok()
-            .and_then(|sessions| sessions.get(token).cloned())
+        Some(SessionUser {
+            user_id: session.user_id,
+            role: session.role,
+        })
     }
 
-    fn create_session(&self, user: SessionUser) -> String {
+    async fn create_session(&self, user_id: i64) -> Result<String, sqlx::Error> {
         let token: String = rand::rng()
             .sample_iter(&Alphanumeric)
             .take(64)
             .map(char::from)
             .collect();
 
-        if let Ok(mut sessions) = self.sessions.lock() {
-            sessions.insert(token.clone(), user);
-        }
-
-        token
+        insert_session(&self.pool, &token, user_id, self.session_ttl_days).await?;
+        Ok(token)
     }
 
-    fn remove_session(&self, headers: &HeaderMap) {
+    async fn remove_session(&self, headers: &HeaderMap) -> Result<(), sqlx::Error> {
         let Some(token) = session_token_from_headers(headers) else {
-            return;
+            return Ok(());
         };
 
-        if let Ok(mut sessions) = self.sessions.lock() {
-            sessions.remove(token);
-        }
+        delete_session(&self.pool, token).await
+    }
+
+    /// Delete expired sessions now and then every hour.
+    pub fn start_session_sweeper(&self) {
+        let pool = self.pool.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = delete_expired_sessions(&pool).await {
+                    eprintln!("session sweep failed: {e}");
+                }
+            }
+        });
     }
 
-    pub async fn api_key_user(&self, pool: &Pool<MySql>, headers: &HeaderMap) -> Option<SessionUser> {
+    /// The user behind the request's `Authorization: Bearer` token, if it is
+    /// valid. Using a token records when it was last used.
+    pub async fn api_token_user(&self, headers: &HeaderMap) -> Option<ApiTokenUser> {
         let token = bearer_token_from_headers(headers)?;
-        let users = sqlx::query_as::<_, ApiKeyUserRow>(
-            "SELECT id, role, api_key_hash FROM users WHERE is_active = TRUE AND api_key_hash IS NOT NULL",
-        )
-        .fetch_all(pool)
-        .await
-        .ok()?;
-
-        users.into_iter().find_map(|user| {
-            self.verify_password(token, &user.api_key_hash)
-                .then_some(SessionUser {
-                    user_id: user.id,
-                    role: user.role,
-                })
+        let prefix = token.get(..API_TOKEN_PREFIX_CHARS)?;
+        let candidates =
+            match fetch_api_token_candidates(&self.pool, prefix, SESSION_TOUCH_SECS).await {
+                Ok(candidates) => candidates,
+                Err(e) => {
+                    eprintln!("api token lookup failed: {e}");
+                    return None;
+                }
+            };
+        let row = candidates
+            .into_iter()
+            .find(|row| self.verify_password(token, &row.token_hash))?;
+
+        if row.needs_touch {
+            if let Err(e) = touch_api_token(&self.pool, row.id).await {
+                eprintln!("api token touch failed: {e}");
+            }
+        }
+
+        Some(ApiTokenUser {
+            user: SessionUser {
+                user_id: row.user_id,
+                role: row.role,
+            },
+            scopes: row.scopes.split(',').filter_map(Scope::parse).collect(),
         })
     }
 
+    /// Who is reading cards: the session user, else the user of a token with
+    /// the `read` scope or `scope` (e.g. `Flashcards` on flash-card routes).
+    pub async fn card_reader(&self, headers: &HeaderMap, scope: Scope) -> Option<SessionUser> {
+        if let Some(user) = self.current_user(headers).await {
+            return Some(user);
+        }
+        self.api_token_user(headers)
+            .await
+            .filter(|token| token.has_scope(Scope::Read) || token.has_scope(scope))
+            .map(|token| token.user)
+    }
+
     fn session_cookie(&self, token: &str) -> String {
         let secure = if self.cookie_secure { "; Secure" } else { "" };
+        let max_age = self.session_ttl_days * 24 * 60 * 60;
         format!(
-            "{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age=2592000{secure}"
+            "{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age={max_age}{secure}"
         )
     }
 
@@ -233,18 +401,32 @@ async fn auth_enabled(pool: &Pool<MySql>) -> bool {
         .unwrap_or(false)
 }
 
+/// Also renews the session cookie, so the browser keeps it as long as the
+/// server-side session slides forward.
 pub async fn status(
     State(state): State<AuthState>,
     Extension(pool): Extension<Pool<MySql>>,
     headers: HeaderMap,
-) -> ApiResponse<AuthStatus> {
-    ApiResponse::new_ok(
+) -> Response {
+    let user = state.current_user(&headers).await;
+    let authenticated = user.is_some();
+    let mut response = ApiResponse::new_ok(
         StatusCode::OK,
         AuthStatus {
-            authenticated: state.is_authenticated(&headers),
+            authenticated,
             auth_enabled: auth_enabled(&pool).await,
+            role: user.map(|user| user.role),
         },
     )
+    .into_response();
+
+    if let Some(token) = session_token_from_headers(&headers).filter(|_| authenticated) {
+        if let Ok(cookie) = HeaderValue::from_str(&state.session_cookie(token)) {
+            response.headers_mut().insert(SET_COOKIE, cookie);
+        }
+    }
+
+    response
 }
 
 pub async fn login(
@@ -252,15 +434,8 @@ pub async fn login(
     Extension(pool): Extension<Pool<MySql>>,
     Json(params): Json<LoginParams>,
 ) -> Response {
-    // Password-only login: match the submitted password against each active
-    // user's hash to identify who is logging in.
-    let users = match sqlx::query_as::<_, UserRow>(
-        "SELECT id, username, password_hash, role FROM users WHERE is_active = TRUE",
-    )
-    .fetch_all(&pool)
-    .await
-    {
-        Ok(users) => users,
+    let user = match fetch_active_user_by_username(&pool, params.username.trim()).await {
+        Ok(user) => user,
         Err(e) => {
             return ApiResponse::<AuthStatus>::new_err(
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -270,25 +445,42 @@ pub async fn login(
         }
     };
 
-    let matched = users
-        .into_iter()
-        .find(|user| state.verify_password(&params.password, &user.password_hash));
+    let matched = match user {
+        Some(user) => state
+            .verify_password(&params.password, &user.password_hash)
+            .then_some(user),
+        None => {
+            // Hash anyway, so unknown usernames take as long as wrong passwords.
+            let _ = state.hash_password(&params.password);
+            None
+        }
+    };
 
     let Some(user) = matched else {
-        return ApiResponse::<AuthStatus>::new_err(StatusCode::UNAUTHORIZED, "invalid password")
-            .into_response();
+        return ApiResponse::<AuthStatus>::new_err(
+            StatusCode::UNAUTHORIZED,
+            "invalid username or password",
+        )
+        .into_response();
     };
 
-    let token = state.create_session(SessionUser {
-        user_id: user.id,
-        role: user.role,
-    });
+    let token = match state.create_session(user.id).await {
+        Ok(token) => token,
+        Err(e) => {
+            return ApiResponse::<AuthStatus>::new_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.to_string(),
+            )
+            .into_response();
+        }
+    };
 
     let mut response = ApiResponse::new_ok(
         StatusCode::OK,
         AuthStatus {
             authenticated: true,
             auth_enabled: true,
+            role: Some(user.role),
         },
     )
     .into_response();
@@ -305,12 +497,16 @@ pub async fn logout(
     Extension(pool): Extension<Pool<MySql>>,
     headers: HeaderMap,
 ) -> Response {
-    state.remove_session(&headers);
+    if let Err(e) = state.remove_session(&headers).await {
+        return ApiResponse::<AuthStatus>::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            .into_response();
+    }
     let mut response = ApiResponse::new_ok(
         StatusCode::OK,
         AuthStatus {
             authenticated: false,
             auth_enabled: auth_enabled(&pool).await,
+            role: None,
         },
     )
     .into_response();
@@ -322,104 +518,131 @@ pub async fn logout(
     response
 }
 
-pub async fn generate_api_token(
+/// Change the logged-in user's own password. Their other sessions are
+/// logged out; this one stays.
+pub async fn change_password(
     State(state): State<AuthState>,
     Extension(pool): Extension<Pool<MySql>>,
     headers: HeaderMap,
-) -> Response {
-    let Some(user) = state.current_user(&headers) else {
-        return ApiResponse::<ApiTokenResponse>::new_err(
-            StatusCode::UNAUTHORIZED,
-            "login required",
-        )
-        .into_response();
+    Json(params): Json<ChangePasswordParams>,
+) -> ApiResponse<()> {
+    let Some(user) = state.current_user(&headers).await else {
+        return ApiResponse::new_err(StatusCode::UNAUTHORIZED, "login required");
     };
 
-    let token = generate_api_token_value();
-    let prefix = token.chars().take(12).collect::<String>();
-    let hash = match state.hash_password(&token) {
+    let row = match fetch_active_user_row(&pool, user.user_id).await {
+        Ok(Some(row)) => row,
+        Ok(None) => return ApiResponse::new_err(StatusCode::UNAUTHORIZED, "login required"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if !state.verify_password(&params.current_password, &row.password_hash) {
+        return ApiResponse::new_err(StatusCode::FORBIDDEN, "current password is wrong");
+    }
+    let hash = match state.hash_new_password(&params.new_password) {
         Ok(hash) => hash,
-        Err(e) => {
-            return ApiResponse::<ApiTokenResponse>::new_err(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                e,
-            )
-            .into_response();
-        }
+        Err((status, message)) => return ApiResponse::new_err(status, message),
     };
 
-    let result = sqlx::query(
-        "UPDATE users SET api_key_hash = ?, api_key_prefix = ? WHERE id = ? AND is_active = TRUE",
-    )
-    .bind(hash)
-    .bind(&prefix)
-    .bind(user.user_id)
-    .execute(&pool)
-    .await;
-
-    match result {
-        Ok(result) if result.rows_affected() > 0 => ApiResponse::new_ok(
-            StatusCode::OK,
-            ApiTokenResponse { token, prefix },
-        )
-        .into_response(),
-        Ok(_) => ApiResponse::<ApiTokenResponse>::new_err(
-            StatusCode::UNAUTHORIZED,
-            "login required",
-        )
-        .into_response(),
-        Err(e) => ApiResponse::<ApiTokenResponse>::new_err(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            e.to_string(),
-        )
-        .into_response(),
+    if let Err(e) = set_user_password(&pool, user.user_id, &hash).await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    let token = session_token_from_headers(&headers);
+    if let Err(e) = delete_user_sessions(&pool, user.user_id, token).await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    ApiResponse::new_ok(StatusCode::OK, ())
+}
+
+/// Access a route needs, or `None` when anyone may call it. Reads are
+/// public (handlers hide private cards themselves), except user management
+/// and study sessions. `/auth/*` handlers check the session on their own.
+fn required_access(method: &Method, path: &str) -> Option<Access> {
+    if path.starts_with("/auth/") {
+        return None;
+    }
+    if path == "/users" || path.starts_with("/users/") {
+        return Some(Access::Admin);
+    }
+    // Sessions belong to the user who started them, so even reading one
+    // needs a login.
+    let study_session = path == "/study/sessions" || path.starts_with("/study/sessions/");
+    if study_session && method != Method::OPTIONS {
+        return Some(Access::Review);
     }
+    if matches!(method, &Method::GET | &Method::HEAD | &Method::OPTIONS) {
+        return None;
+    }
+
+    // Every other way of answering a card.
+    let review = path == "/cards/flush_json" || path == "/cards/quiz";
+    Some(if review { Access::Review } else { Access::Write })
 }
 
 pub async fn require_write_auth(
     State(state): State<AuthState>,
-    Extension(pool): Extension<Pool<MySql>>,
-    req: Request,
+    mut req: Request,
     next: Next,
 ) -> Response {
-    if req.uri().path().starts_with("/auth/") {
-        return next.run(req).await;
-    }
-
-    if matches!(req.method(), &Method::GET | &Method::HEAD | &Method::OPTIONS) {
+    let Some(access) = required_access(req.method(), req.uri().path()) else {
         return next.run(req).await;
-    }
+    };
 
-    if req.uri().path() == "/cards/flush_json" {
-        if state.api_key_user(&pool, req.headers()).await.is_some() {
-            return next.run(req).await;
+    // Flash-card results are only posted by integrations, with a token.
+    let flash_cards = req.uri().path() == "/cards/flush_json";
+    let session = if flash_cards {
+        None
+    } else {
+        state.current_user(req.headers()).await
+    };
+    let user = if let Some(user) = session {
+        RequestUser {
+            user,
+            via: AuthVia::Session,
+        }
+    } else if let Some(token) = state.api_token_user(req.headers()).await {
+        if !token.allows(access) {
+            return ApiResponse::<()>::new_err(
+                StatusCode::FORBIDDEN,
+                "api token scopes do not allow this",
+            )
+            .into_response();
         }
+        RequestUser {
+            user: token.user,
+            via: AuthVia::ApiToken,
+        }
+    } else {
+        let message = if flash_cards {
+            "api token required"
+        } else {
+            "authentication required"
+        };
+        return ApiResponse::<()>::new_err(StatusCode::UNAUTHORIZED, message).into_response();
+    };
 
+    if !user.user.can(access) {
         return ApiResponse::<()>::new_err(
-            StatusCode::UNAUTHORIZED,
-            "api token required",
+            StatusCode::FORBIDDEN,
+            format!("role '{}' may not do this", user.user.role),
         )
         .into_response();
     }
 
-    if state.is_authenticated(req.headers()) {
-        return next.run(req).await;
-    }
-
-    if state.api_key_user(&pool, req.headers()).await.is_some() {
-        return next.run(req).await;
-    }
-
-    ApiResponse::<()>::new_err(StatusCode::UNAUTHORIZED, "authentication required").into_response()
+    req.extensions_mut().insert(user);
+    next.run(req).await
 }
 
-fn generate_api_token_value() -> String {
+/// A new random API token and the prefix stored with it.
+pub fn generate_api_token() -> (String, String) {
     let value: String = rand::rng()
         .sample_iter(&Alphanumeric)
         .take(48)
         .map(char::from)
         .collect();
-    format!("memo_{value}")
+    let token = format!("memo_{value}");
+    let prefix = token[..API_TOKEN_PREFIX_CHARS].to_string();
+    (token, prefix)
 }
 
 fn session_token_from_headers(headers: &HeaderMap) -> Option<&str> {
@@ -0,0 +1,755 @@
+//! Structured card queries, e.g.
+//! `tag:rust -tag:done visibility:private updated:>2025-06-01 in:frame(42) type:frame "tokio"`.
+//!
+//! Terms separated by spaces must all match; `OR` between terms matches
+//! either side and binds looser than the implicit AND. `-` negates a term and
+//! parentheses group terms. Supported terms:
+//!
+//! - `tag:NAME`: has the tag (exact name).
+//! - `visibility:public`, `visibility:private`.
+//! - `type:TYPE`: `card_type`, e.g. `normal` or `frame`.
+//! - `created:DATE`, `updated:DATE`: on that day (UTC). Prefix the date with
+//!   `>`, `>=`, `<` or `<=` to compare instead.
+//! - `due:DATE`: a review item of the card is due on that day; compares like
+//!   `created:`.
+//! - `has:tag`, `has:parent`: has any tag, has a parent card.
+//! - `in:frame(ID)` or `in:ID`: a descendant of card ID through `card_card`.
+//! - `title:TEXT`: the title contains TEXT.
+//! - `TEXT`: the title or contents contain TEXT.
+//!
+//! A DATE is `2025-06-01`, `today`, or a number of days or weeks from today
+//! such as `-7d` or `+2w`. Relative dates are resolved when the query runs,
+//! so a saved query keeps following the calendar.
+//!
+//! Values containing spaces or parentheses are double-quoted, e.g.
+//! `tag:"read later"` or `"async fn"`. Text matching is case-insensitive.
+
+use std::fmt;
+
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use sqlx::{MySql, QueryBuilder};
+
+/// Parenthesized groups nested deeper than this are rejected.
+const MAX_DEPTH: usize = 32;
+
+/// Range of MySQL's DATETIME. Dates outside it cannot be bound.
+const EARLIEST_DATE: NaiveDate = NaiveDate::from_ymd_opt(1000, 1, 1).expect("valid date");
+const LATEST_DATE: NaiveDate = NaiveDate::from_ymd_opt(9999, 12, 31).expect("valid date");
+
+/// A parsed query.
+#[derive(Debug, PartialEq)]
+pub enum CardQuery {
+    And(Vec<CardQuery>),
+    Or(Vec<CardQuery>),
+    Not(Box<CardQuery>),
+    Tag(String),
+    Visibility(String),
+    CardType(String),
+    Created(DateComparison),
+    Updated(DateComparison),
+    Due(DateComparison),
+    HasTag,
+    HasParent,
+    /// Descendants of a card.
+    In(i64),
+    Title(String),
+    Text(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateComparison {
+    On(DateValue),
+    After(DateValue),
+    OnOrAfter(DateValue),
+    Before(DateValue),
+    OnOrBefore(DateValue),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateValue {
+    Date(NaiveDate),
+    /// Days after (or before, if negative) the day the query runs.
+    FromToday(i64),
+}
+
+impl DateValue {
+    /// The date, clamped to what MySQL can compare against.
+    fn resolve(&self, today: NaiveDate) -> NaiveDate {
+        let date = match self {
+            DateValue::Date(date) => *date,
+            DateValue::FromToday(days) if *days >= 0 => today
+                .checked_add_days(Days::new(*days as u64))
+                .unwrap_or(LATEST_DATE),
+            DateValue::FromToday(days) => today
+                .checked_sub_days(Days::new(days.unsigned_abs()))
+                .unwrap_or(EARLIEST_DATE),
+        };
+        date.clamp(EARLIEST_DATE, LATEST_DATE)
+    }
+}
+
+/// Where and why a query failed to parse. `column` counts characters from 1.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl CardQuery {
+    /// Parse `input`. An empty query matches every card.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            position: 0,
+            depth: 0,
+        };
+        let query = parser.parse_or()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error_at(parser.position, "unexpected \")\""));
+        }
+        Ok(query)
+    }
+
+    /// Append the query as one SQL condition on `c` (the queried card).
+    pub fn push_condition(&self, query: &mut QueryBuilder<'_, MySql>) {
+        match self {
+            CardQuery::And(terms) => push_joined(query, terms, " AND ", "TRUE"),
+            CardQuery::Or(terms) => push_joined(query, terms, " OR ", "FALSE"),
+            CardQuery::Not(term) => {
+                query.push("NOT (");
+                term.push_condition(query);
+                query.push(")");
+            }
+            CardQuery::Tag(name) => {
+                query
+                    .push(
+                        "c.id IN (SELECT ct.card_id FROM card_tag ct \
+                         INNER JOIN tags t ON t.id = ct.tag_id WHERE t.name = ",
+                    )
+                    .push_bind(name.clone())
+                    .push(")");
+            }
+            CardQuery::Visibility(visibility) => {
+                query.push("c.visibility = ").push_bind(visibility.clone());
+            }
+            CardQuery::CardType(card_type) => {
+                query.push("c.card_type = ").push_bind(card_type.clone());
+            }
+            CardQuery::Created(comparison) => {
+                push_date_condition(query, "c.created_at", comparison)
+            }
+            CardQuery::Updated(comparison) => {
+                push_date_condition(query, "c.updated_at", comparison)
+            }
+            CardQuery::Due(comparison) => {
+                query.push("c.id IN (SELECT cs.card_id FROM card_schedules cs WHERE ");
+                push_date_condition(query, "cs.due_at", comparison);
+                query.push(")");
+            }
+            CardQuery::HasTag => {
+                query.push("c.id IN (SELECT card_id FROM card_tag)");
+            }
+            CardQuery::HasParent => {
+                query.push("c.id IN (SELECT card_child_id FROM card_card)");
+            }
+            CardQuery::In(card_id) => {
+                // UNION (not UNION ALL) stops at cards already visited, so a
+                // cycle in card_card cannot loop forever.
+                query
+                    .push(
+                        "c.id IN (WITH RECURSIVE descendants (id) AS (\
+                         SELECT card_child_id FROM card_card WHERE card_parent_id = ",
+                    )
+                    .push_bind(*card_id)
+                    .push(
+                        " UNION SELECT cc.card_child_id FROM card_card cc \
+                         INNER JOIN descendants d ON cc.card_parent_id = d.id\
+                         ) SELECT id FROM descendants)",
+                    );
+            }
+            CardQuery::Title(text) => {
+                query
+                    .push("COALESCE(c.title, '') LIKE ")
+                    .push_bind(like_pattern(text));
+            }
+            CardQuery::Text(text) => {
+                let pattern = like_pattern(text);
+                query
+                    .push("(COALESCE(c.title, '') LIKE ")
+                    .push_bind(pattern.clone())
+                    .push(" OR COALESCE(c.contents, '') LIKE ")
+                    .push_bind(pattern)
+                    .push(")");
+            }
+        }
+    }
+}
+
+fn push_joined(
+    query: &mut QueryBuilder<'_, MySql>,
+    terms: &[CardQuery],
+    separator: &str,
+    empty: &str,
+) {
+    if terms.is_empty() {
+        query.push(empty);
+        return;
+    }
+    query.push("(");
+    for (i, term) in terms.iter().enumerate() {
+        if i > 0 {
+            query.push(separator);
+        }
+        term.push_condition(query);
+    }
+    query.push(")");
+}
+
+fn push_date_condition(
+    query: &mut QueryBuilder<'_, MySql>,
+    column: &str,
+    comparison: &DateComparison,
+) {
+    let today = Utc::now().date_naive();
+    let start =
+        |date: &DateValue| -> NaiveDateTime { date.resolve(today).and_time(NaiveTime::MIN) };
+    let end = |date: &DateValue| -> NaiveDateTime {
+        date.resolve(today)
+            .succ_opt()
+            .unwrap_or(NaiveDate::MAX)
+            .and_time(NaiveTime::MIN)
+    };
+
+    match comparison {
+        DateComparison::On(date) => {
+            query
+                .push(format!("({column} >= "))
+                .push_bind(start(date))
+                .push(format!(" AND {column} < "))
+                .push_bind(end(date))
+                .push(")");
+        }
+        DateComparison::After(date) => {
+            query.push(format!("{column} >= ")).push_bind(end(date));
+        }
+        DateComparison::OnOrAfter(date) => {
+            query.push(format!("{column} >= ")).push_bind(start(date));
+        }
+        DateComparison::Before(date) => {
+            query.push(format!("{column} < ")).push_bind(start(date));
+        }
+        DateComparison::OnOrBefore(date) => {
+            query.push(format!("{column} < ")).push_bind(end(date));
+        }
+    }
+}
+
+/// `2025-06-01`, `today`, or days or weeks from today like `-7d` or `+2w`.
+fn parse_date(value: &str) -> Option<DateValue> {
+    if value == "today" {
+        return Some(DateValue::FromToday(0));
+    }
+    if let Some(offset) = value.strip_prefix(['+', '-']) {
+        let (count, days_per_unit) = match (offset.strip_suffix('d'), offset.strip_suffix('w')) {
+            (Some(count), _) => (count, 1),
+            (_, Some(count)) => (count, 7),
+            _ => return None,
+        };
+        if count.is_empty() || !count.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let days = count.parse::<i64>().ok()?.checked_mul(days_per_unit)?;
+        return Some(DateValue::FromToday(if value.starts_with('-') {
+            -days
+        } else {
+            days
+        }));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(DateValue::Date)
+}
+
+/// `%text%` with LIKE wildcards in `text` escaped.
+fn like_pattern(text: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<CardQuery, ParseError> {
+        let mut terms = vec![self.parse_and()?];
+        while self.at_or() {
+            let or_position = self.position;
+            self.position += 2;
+            let term = self.parse_and()?;
+            if term == CardQuery::And(Vec::new()) {
+                return Err(self.error_at(or_position, "expected a term after OR"));
+            }
+            terms.push(term);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            CardQuery::Or(terms)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<CardQuery, ParseError> {
+        let mut terms = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None | Some(')') => break,
+                _ if self.at_or() => {
+                    if terms.is_empty() {
+                        return Err(self.error_at(self.position, "expected a term before OR"));
+                    }
+                    break;
+                }
+                _ => terms.push(self.parse_unary()?),
+            }
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            CardQuery::And(terms)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<CardQuery, ParseError> {
+        let start = self.position;
+        let negations = self.chars[start..]
+            .iter()
+            .take_while(|c| **c == '-')
+            .count();
+        self.position += negations;
+        if negations > 0 && self.peek().is_none_or(|c| c.is_whitespace() || c == ')') {
+            return Err(self.error_at(start, "expected a term after -"));
+        }
+
+        let term = self.parse_term()?;
+        Ok(if negations % 2 == 1 {
+            CardQuery::Not(Box::new(term))
+        } else {
+            term
+        })
+    }
+
+    fn parse_term(&mut self) -> Result<CardQuery, ParseError> {
+        let start = self.position;
+        match self.peek() {
+            Some('(') => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error_at(start, "parentheses nested too deeply"));
+                }
+                self.position += 1;
+                self.depth += 1;
+                let query = self.parse_or()?;
+                self.depth -= 1;
+                self.skip_whitespace();
+                if self.peek() != Some(')') {
+                    return Err(self.error_at(start, "unclosed \"(\""));
+                }
+                self.position += 1;
+                if query == CardQuery::And(Vec::new()) {
+                    return Err(self.error_at(start, "empty parentheses"));
+                }
+                return Ok(query);
+            }
+            Some('"') => {
+                let text = self.parse_quoted()?;
+                if text.is_empty() {
+                    return Err(self.error_at(start, "empty quotes"));
+                }
+                return Ok(CardQuery::Text(text));
+            }
+            _ => {}
+        }
+
+        let field_length = self.chars[start..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .count();
+        if field_length == 0 || self.chars.get(start + field_length) != Some(&':') {
+            return Ok(CardQuery::Text(self.parse_bare()));
+        }
+        let field: String = self.chars[start..start + field_length].iter().collect();
+        self.position += field_length + 1;
+
+        match field.as_str() {
+            "tag" => Ok(CardQuery::Tag(self.parse_value(&field)?)),
+            "title" => Ok(CardQuery::Title(self.parse_value(&field)?)),
+            "type" => Ok(CardQuery::CardType(self.parse_value(&field)?)),
+            "visibility" => {
+                let value_start = self.position;
+                let value = self.parse_value(&field)?;
+                if value != "public" && value != "private" {
+                    return Err(self.error_at(value_start, "visibility must be public or private"));
+                }
+                Ok(CardQuery::Visibility(value))
+            }
+            "created" => Ok(CardQuery::Created(self.parse_date_comparison(&field)?)),
+            "updated" => Ok(CardQuery::Updated(self.parse_date_comparison(&field)?)),
+            "due" => Ok(CardQuery::Due(self.parse_date_comparison(&field)?)),
+            "has" => {
+                let value_start = self.position;
+                match self.parse_value(&field)?.as_str() {
+                    "tag" => Ok(CardQuery::HasTag),
+                    "parent" => Ok(CardQuery::HasParent),
+                    _ => Err(self.error_at(value_start, "has: must be tag or parent")),
+                }
+            }
+            "in" => self.parse_in(),
+            _ => Err(self.error_at(start, format!("unknown field \"{field}\""))),
+        }
+    }
+
+    /// A quoted string or a bare word after `field:`.
+    fn parse_value(&mut self, field: &str) -> Result<String, ParseError> {
+        let value = if self.peek() == Some('"') {
+            self.parse_quoted()?
+        } else {
+            self.parse_bare()
+        };
+        if value.is_empty() {
+            return Err(self.error_at(self.position, format!("expected a value after {field}:")));
+        }
+        Ok(value)
+    }
+
+    fn parse_date_comparison(&mut self, field: &str) -> Result<DateComparison, ParseError> {
+        let operator: String = self.chars[self.position..]
+            .iter()
+            .take_while(|c| matches!(c, '<' | '>' | '='))
+            .collect();
+        let operator_start = self.position;
+        self.position += operator.len();
+
+        let date_start = self.position;
+        let value = self.parse_bare();
+        if value.is_empty() {
+            return Err(self.error_at(date_start, format!("expected a date after {field}:")));
+        }
+        let date = parse_date(&value).ok_or_else(|| {
+            self.error_at(date_start, "expected a date like 2025-06-01, today or -7d")
+        })?;
+
+        match operator.as_str() {
+            "" | "=" => Ok(DateComparison::On(date)),
+            ">" => Ok(DateComparison::After(date)),
+            ">=" => Ok(DateComparison::OnOrAfter(date)),
+            "<" => Ok(DateComparison::Before(date)),
+            "<=" => Ok(DateComparison::OnOrBefore(date)),
+            _ => Err(self.error_at(operator_start, format!("unknown comparison \"{operator}\""))),
+        }
+    }
+
+    /// `frame(ID)` or `ID`.
+    fn parse_in(&mut self) -> Result<CardQuery, ParseError> {
+        let start = self.position;
+        let wrapped = self.chars[start..].starts_with(&['f', 'r', 'a', 'm', 'e', '(']);
+        if wrapped {
+            self.position += "frame(".len();
+        }
+
+        let digits_start = self.position;
+        let digits: String = self.chars[digits_start..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        let card_id = digits
+            .parse::<i64>()
+            .map_err(|_| self.error_at(digits_start, "expected a card id like in:frame(42)"))?;
+        self.position += digits.len();
+
+        if wrapped {
+            if self.peek() != Some(')') {
+                return Err(self.error_at(self.position, "expected \")\""));
+            }
+            self.position += 1;
+        }
+        if self.peek().is_some_and(|c| !c.is_whitespace() && c != ')') {
+            return Err(self.error_at(self.position, "expected a card id like in:frame(42)"));
+        }
+        Ok(CardQuery::In(card_id))
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, ParseError> {
+        let start = self.position;
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error_at(start, "unclosed quote")),
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(value);
+                }
+                Some('\\') if self.chars.get(self.position + 1).is_some() => {
+                    value.push(self.chars[self.position + 1]);
+                    self.position += 2;
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    /// Characters up to the next space or parenthesis.
+    fn parse_bare(&mut self) -> String {
+        let value: String = self.chars[self.position..]
+            .iter()
+            .take_while(|c| !c.is_whitespace() && !matches!(c, '(' | ')'))
+            .collect();
+        self.position += value.chars().count();
+        value
+    }
+
+    /// Whether the next word is the `OR` operator.
+    fn at_or(&self) -> bool {
+        self.chars[self.position..].starts_with(&['O', 'R'])
+            && self
+                .chars
+                .get(self.position + 2)
+                .is_none_or(|c| c.is_whitespace() || matches!(c, '(' | ')'))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn error_at(&self, position: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            column: position + 1,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> CardQuery {
+        CardQuery::parse(input).unwrap()
+    }
+
+    fn error(input: &str) -> (usize, String) {
+        let e = CardQuery::parse(input).unwrap_err();
+        (e.column, e.message)
+    }
+
+    fn sql(input: &str) -> String {
+        let mut query = QueryBuilder::<MySql>::new("");
+        parse(input).push_condition(&mut query);
+        query.sql().to_string()
+    }
+
+    fn date(value: &str) -> DateValue {
+        DateValue::Date(NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap())
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(parse(""), CardQuery::And(Vec::new()));
+        assert_eq!(parse("   "), CardQuery::And(Vec::new()));
+        assert_eq!(sql(""), "TRUE");
+    }
+
+    #[test]
+    fn terms_and_fields() {
+        assert_eq!(
+            parse(r#"tag:rust -tag:"read later" in:frame(42) "async fn""#),
+            CardQuery::And(vec![
+                CardQuery::Tag("rust".to_string()),
+                CardQuery::Not(Box::new(CardQuery::Tag("read later".to_string()))),
+                CardQuery::In(42),
+                CardQuery::Text("async fn".to_string()),
+            ])
+        );
+        assert_eq!(parse("in:7"), CardQuery::In(7));
+        assert_eq!(parse("has:parent"), CardQuery::HasParent);
+        assert_eq!(
+            parse("type:frame"),
+            CardQuery::CardType("frame".to_string())
+        );
+        assert_eq!(parse("--tag:a"), CardQuery::Tag("a".to_string()));
+    }
+
+    #[test]
+    fn or_binds_looser_than_and() {
+        assert_eq!(
+            parse("tag:a tag:b OR (tag:c OR tag:d)"),
+            CardQuery::Or(vec![
+                CardQuery::And(vec![
+                    CardQuery::Tag("a".to_string()),
+                    CardQuery::Tag("b".to_string()),
+                ]),
+                CardQuery::Or(vec![
+                    CardQuery::Tag("c".to_string()),
+                    CardQuery::Tag("d".to_string()),
+                ]),
+            ])
+        );
+        // Only an upper-case standalone OR is the operator.
+        assert_eq!(
+            parse("ORACLE or"),
+            CardQuery::And(vec![
+                CardQuery::Text("ORACLE".to_string()),
+                CardQuery::Text("or".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn dates_and_comparisons() {
+        assert_eq!(
+            parse("updated:>2025-06-01"),
+            CardQuery::Updated(DateComparison::After(date("2025-06-01")))
+        );
+        assert_eq!(
+            parse("created:<=today"),
+            CardQuery::Created(DateComparison::OnOrBefore(DateValue::FromToday(0)))
+        );
+        assert_eq!(
+            parse("due:-2w"),
+            CardQuery::Due(DateComparison::On(DateValue::FromToday(-14)))
+        );
+        assert_eq!(
+            parse("due:+3d"),
+            CardQuery::Due(DateComparison::On(DateValue::FromToday(3)))
+        );
+    }
+
+    #[test]
+    fn overflowing_dates_are_rejected_or_clamped() {
+        assert_eq!(parse_date("+99999999999999999999d"), None);
+        assert_eq!(parse_date("+9223372036854775807w"), None);
+        assert_eq!(parse_date("2025-02-30"), None);
+        assert_eq!(parse_date("+d"), None);
+
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        assert_eq!(DateValue::FromToday(i64::MAX).resolve(today), LATEST_DATE);
+        assert_eq!(DateValue::FromToday(i64::MIN).resolve(today), EARLIEST_DATE);
+        assert_eq!(date("0001-01-01").resolve(today), EARLIEST_DATE);
+        assert_eq!(
+            DateValue::FromToday(-7).resolve(today).to_string(),
+            "2025-05-25"
+        );
+        // Dates past either end of the calendar still build a condition.
+        assert_eq!(
+            sql("due:<=+9999999999d"),
+            "c.id IN (SELECT cs.card_id FROM card_schedules cs WHERE cs.due_at < ?)"
+        );
+        assert_eq!(sql("created:>-9999999999d"), "c.created_at >= ?");
+    }
+
+    #[test]
+    fn unicode_text_and_columns_count_characters() {
+        assert_eq!(
+            parse("tag:日本語 勉強"),
+            CardQuery::And(vec![
+                CardQuery::Tag("日本語".to_string()),
+                CardQuery::Text("勉強".to_string()),
+            ])
+        );
+        assert_eq!(
+            error("日本 bogus:x"),
+            (4, "unknown field \"bogus\"".to_string())
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(
+            error("tag:"),
+            (5, "expected a value after tag:".to_string())
+        );
+        assert_eq!(error("(tag:a"), (1, "unclosed \"(\"".to_string()));
+        assert_eq!(error("tag:a)"), (6, "unexpected \")\"".to_string()));
+        assert_eq!(error("()"), (1, "empty parentheses".to_string()));
+        assert_eq!(error("\"open"), (1, "unclosed quote".to_string()));
+        assert_eq!(
+            error("OR tag:a"),
+            (1, "expected a term before OR".to_string())
+        );
+        assert_eq!(
+            error("tag:a OR"),
+            (7, "expected a term after OR".to_string())
+        );
+        assert_eq!(error("- tag:a"), (1, "expected a term after -".to_string()));
+        assert_eq!(
+            error("visibility:secret"),
+            (12, "visibility must be public or private".to_string())
+        );
+        assert_eq!(
+            error("in:frame(x)"),
+            (10, "expected a card id like in:frame(42)".to_string())
+        );
+        assert_eq!(error("updated:=>2025-06-01").1, "unknown comparison \"=>\"");
+        let deep = format!("{}tag:a{}", "(".repeat(40), ")".repeat(40));
+        assert_eq!(error(&deep).1, "parentheses nested too deeply");
+    }
+
+    #[test]
+    fn sql_for_terms() {
+        assert_eq!(
+            sql("tag:rust -has:tag"),
+            "(c.id IN (SELECT ct.card_id FROM card_tag ct INNER JOIN tags t ON t.id = ct.tag_id \
+             WHERE t.name = ?) AND NOT (c.id IN (SELECT card_id FROM card_tag)))"
+        );
+        assert_eq!(
+            sql("visibility:private OR type:frame"),
+            "(c.visibility = ? OR c.card_type = ?)"
+        );
+        assert_eq!(
+            sql("tokio"),
+            "(COALESCE(c.title, '') LIKE ? OR COALESCE(c.contents, '') LIKE ?)"
+        );
+        assert_eq!(
+            sql("updated:2025-06-01"),
+            "(c.updated_at >= ? AND c.updated_at < ?)"
+        );
+        assert_eq!(
+            sql("due:<today"),
+            "c.id IN (SELECT cs.card_id FROM card_schedules cs WHERE cs.due_at < ?)"
+        );
+        assert!(sql("in:frame(42)").starts_with("c.id IN (WITH RECURSIVE descendants"));
+    }
+
+    #[test]
+    fn like_patterns_escape_wildcards() {
+        assert_eq!(like_pattern("50%_off\\"), "%50\\%\\_off\\\\%");
+        assert_eq!(like_pattern(""), "%%");
+        assert_eq!(like_pattern("日本"), "%日本%");
+    }
+}
@@ -1,5 +1,86 @@
 mod card;
-pub use card::{fetch_all_card_rows, fetch_card_row_by_id, fetch_card_rows_in_range};
+pub use card::{
+    create_poly, fetch_all_card_rows, fetch_card_row_by_id, fetch_card_rows_by_ids,
+    fetch_card_rows_by_query, fetch_card_rows_by_tags, fetch_card_rows_in_range,
+    fetch_child_card_rows, insert_card, insert_card_tag, lock_card_version, move_card_row,
+    set_card_tags, update_card_row,
+};
+
+mod card_card;
+pub use card_card::{
+    delete_card_relation, fetch_card_relation, fetch_card_relations, insert_card_relation,
+    lock_card_relation,
+};
 
 mod pool;
 pub use pool::create_pool;
+
+mod tag;
+pub use tag::{fetch_tag_id, fetch_tags_by_ids, find_or_create_tag};
+
+mod schedule;
+pub use schedule::{fetch_card_item_schedules, fetch_card_schedule, upsert_card_schedule};
+
+mod review;
+pub use review::{insert_card_review, NewCardReview};
+
+mod study_mode;
+pub use study_mode::{delete_study_mode, fetch_study_mode, upsert_study_mode};
+
+mod study_session;
+pub use study_session::{
+    complete_study_session_current, fetch_session_answers, fetch_session_totals,
+    fetch_study_session, finish_study_session, insert_study_session, lock_study_session,
+    set_study_session_current,
+};
+
+mod search;
+pub use search::{fetch_search_terms, fetch_search_stats, fetch_unindexed_cards, index_card};
+
+mod saved_query;
+pub use saved_query::{
+    count_smart_frames_using, delete_saved_query, fetch_saved_queries, fetch_saved_query,
+    insert_saved_query, update_saved_query,
+};
+
+mod revision;
+pub use revision::{
+    fetch_card_revision, fetch_card_revisions, record_card_revision, NewCardRevision,
+};
+
+mod trash;
+pub use trash::{
+    delete_trashed_relation, fetch_trashed_cards, fetch_trashed_parent_edges,
+    fetch_trashed_relations, hand_over_trashed_relation, purge_trashed_cards,
+    restore_trashed_relation, trash_card, untrash_card,
+};
+
+mod changes;
+pub use changes::{fetch_changes, stamp_changes};
+
+mod sync_op;
+pub use sync_op::{fetch_applied_sync_op, prune_applied_sync_ops, record_applied_sync_op};
+
+mod session;
+pub use session::{
+    delete_expired_sessions, delete_session, delete_user_sessions, fetch_session, insert_session,
+    touch_session,
+};
+
+mod user;
+pub use user::{
+    count_other_active_admins, fetch_active_user_by_username, fetch_active_user_row, fetch_user,
+    fetch_users, insert_user, set_user_password, update_user,
+};
+
+mod card_acl;
+pub use card_acl::{
+    fetch_card_access_rows, fetch_card_acl, fetch_card_acl_rules, fetch_card_parent_edges,
+    replace_card_acl,
+};
+
+mod api_token;
+pub use api_token::{
+    delete_api_token, delete_user_api_tokens, fetch_api_token, fetch_api_token_candidates,
+    fetch_api_tokens, insert_api_token, touch_api_token, NewApiToken,
+};
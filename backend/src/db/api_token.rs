@@ -0,0 +1,157 @@
+use sqlx::{Executor, FromRow, MySql};
+
+use crate::models::ApiTokenRow;
+
+const SELECT_API_TOKENS: &str = r#"
+SELECT id, name, token_prefix, CAST(scopes AS CHAR) AS scopes, expires_at, last_used_at,
+    created_at
+FROM api_tokens
+"#;
+
+/// A token to add to `api_tokens`.
+pub struct NewApiToken<'a> {
+    pub user_id: i64,
+    pub name: &'a str,
+    pub token_prefix: &'a str,
+    pub token_hash: &'a str,
+    /// Comma-separated scope names.
+    pub scopes: &'a str,
+    pub expires_in_days: Option<i64>,
+}
+
+/// A live token that may match the one presented, and its user.
+#[derive(FromRow)]
+pub struct ApiTokenAuthRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub role: String,
+    pub token_hash: String,
+    pub scopes: String,
+    /// Last used long enough ago that `last_used_at` should be refreshed.
+    pub needs_touch: bool,
+}
+
+// トークンを作成して ID を返す
+pub async fn insert_api_token<'e, E>(
+    executor: E,
+    token: &NewApiToken<'_>,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query(
+        r#"
+        INSERT INTO api_tokens (user_id, name, token_prefix, token_hash, scopes, expires_at)
+        VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP + INTERVAL ? DAY)
+        "#,
+    )
+    .bind(token.user_id)
+    .bind(token.name)
+    .bind(token.token_prefix)
+    .bind(token.token_hash)
+    .bind(token.scopes)
+    .bind(token.expires_in_days)
+    .execute(executor)
+    .await?;
+    Ok(res.last_insert_id() as i64)
+}
+
+// ユーザーのトークンを新しい順に取得
+pub async fn fetch_api_tokens<'e, E>(
+    executor: E,
+    user_id: i64,
+) -> Result<Vec<ApiTokenRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} WHERE user_id = ? ORDER BY id DESC", SELECT_API_TOKENS);
+    sqlx::query_as::<_, ApiTokenRow>(&sql)
+        .bind(user_id)
+        .fetch_all(executor)
+        .await
+}
+
+// ユーザーのトークンを ID 指定で１件取得
+pub async fn fetch_api_token<'e, E>(
+    executor: E,
+    user_id: i64,
+    token_id: i64,
+) -> Result<Option<ApiTokenRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} WHERE user_id = ? AND id = ?", SELECT_API_TOKENS);
+    sqlx::query_as::<_, ApiTokenRow>(&sql)
+        .bind(user_id)
+        .bind(token_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// 先頭文字列が一致し、期限内でユーザーが有効なトークンを取得
+pub async fn fetch_api_token_candidates<'e, E>(
+    executor: E,
+    token_prefix: &str,
+    touch_after_secs: i64,
+) -> Result<Vec<ApiTokenAuthRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, ApiTokenAuthRow>(
+        r#"
+        SELECT t.id, t.user_id, u.role, t.token_hash, CAST(t.scopes AS CHAR) AS scopes,
+            (t.last_used_at IS NULL
+                OR t.last_used_at < CURRENT_TIMESTAMP - INTERVAL ? SECOND) AS needs_touch
+        FROM api_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_prefix = ?
+          AND (t.expires_at IS NULL OR t.expires_at > CURRENT_TIMESTAMP)
+          AND u.is_active = TRUE
+        "#,
+    )
+    .bind(touch_after_secs)
+    .bind(token_prefix)
+    .fetch_all(executor)
+    .await
+}
+
+// 最終利用日時を更新
+pub async fn touch_api_token<'e, E>(executor: E, token_id: i64) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(token_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// ユーザーのトークンを削除（失効）。存在しなければ false
+pub async fn delete_api_token<'e, E>(
+    executor: E,
+    user_id: i64,
+    token_id: i64,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("DELETE FROM api_tokens WHERE user_id = ? AND id = ?")
+        .bind(user_id)
+        .bind(token_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// ユーザーのトークンを全て削除（失効）
+pub async fn delete_user_api_tokens<'e, E>(executor: E, user_id: i64) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("DELETE FROM api_tokens WHERE user_id = ?")
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
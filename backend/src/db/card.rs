@@ -1,31 +1,106 @@
-use crate::models::CardRow;
-use sqlx::{Executor, MySql};
+use crate::{
+    card_query::CardQuery,
+    models::{CardParams, CardRow},
+    schema::{Dimmension, TagFilter},
+};
+use sqlx::{Executor, MySql, QueryBuilder, Transaction};
 
-// SELECT の共通部分
+// SELECT の共通部分（ゴミ箱のカードを除く）
 const SELECT_CARD_ROWS: &str = r#"
 SELECT
     ST_X(ST_PointN(ST_ExteriorRing(shape), 1)) AS pos_x,
     ST_Y(ST_PointN(ST_ExteriorRing(shape), 1)) AS pos_y,
     (ST_X(ST_PointN(ST_ExteriorRing(shape), 3)) - ST_X(ST_PointN(ST_ExteriorRing(shape), 1))) AS size_x,
     (ST_Y(ST_PointN(ST_ExteriorRing(shape), 3)) - ST_Y(ST_PointN(ST_ExteriorRing(shape), 1))) AS size_y,
-    c.id, c.title, c.contents, c.visibility, c.card_type, c.ok_count, c.created_at, c.updated_at, cc.card_parent_id AS parent_id,
+    c.id, c.title, c.contents, c.visibility, c.card_type, c.saved_query_id, c.ok_count, c.version, c.owner_id, c.created_at, c.updated_at, cc.card_parent_id AS parent_id,
     COALESCE(JSON_ARRAYAGG(ct.tag_id), JSON_ARRAY()) AS tag_ids,
     COALESCE(JSON_ARRAYAGG(JSON_OBJECT('id', cc.card_child_id)), JSON_ARRAY()) AS card_ids
 FROM cards c
 LEFT JOIN card_tag AS ct ON ct.card_id = c.id
 LEFT JOIN card_card AS cc ON cc.card_child_id = c.id
+WHERE c.deleted_at IS NULL
 "#;
 
+const GROUP_BY_CARD_ROWS: &str = "GROUP BY c.id, c.title, c.contents, c.visibility, c.card_type, c.saved_query_id, c.ok_count, c.version, c.owner_id, c.created_at, c.updated_at, pos_x, pos_y, size_x, size_y";
+
 // 全件取得
 pub async fn fetch_all_card_rows<'e, E>(executor: E) -> Result<Vec<CardRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} {}", SELECT_CARD_ROWS, GROUP_BY_CARD_ROWS);
+    sqlx::query_as::<_, CardRow>(&sql).fetch_all(executor).await
+}
+
+// タグ条件で絞り込んで取得
+pub async fn fetch_card_rows_by_tags<'e, E>(
+    executor: E,
+    filter: &TagFilter,
+) -> Result<Vec<CardRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let mut query = QueryBuilder::<MySql>::new(SELECT_CARD_ROWS);
+    filter.push_conditions(&mut query);
+    query.push(" ").push(GROUP_BY_CARD_ROWS);
+
+    query.build_query_as::<CardRow>().fetch_all(executor).await
+}
+
+// 検索クエリ（card_query）で絞り込んで取得
+pub async fn fetch_card_rows_by_query<'e, E>(
+    executor: E,
+    card_query: &CardQuery,
+) -> Result<Vec<CardRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let mut query = QueryBuilder::<MySql>::new(SELECT_CARD_ROWS);
+    query.push(" AND (");
+    card_query.push_condition(&mut query);
+    query.push(") ").push(GROUP_BY_CARD_ROWS);
+
+    query.build_query_as::<CardRow>().fetch_all(executor).await
+}
+
+// card_card 上の直接の子カードを取得
+pub async fn fetch_child_card_rows<'e, E>(
+    executor: E,
+    parent_id: i64,
+) -> Result<Vec<CardRow>, sqlx::Error>
 where
     E: Executor<'e, Database = MySql>,
 {
     let sql = format!(
-        "{} GROUP BY c.id, c.title, c.contents, c.visibility, c.card_type, c.ok_count, c.created_at, c.updated_at, pos_x, pos_y, size_x, size_y",
-        SELECT_CARD_ROWS
+        "{} AND c.id IN (SELECT card_child_id FROM card_card WHERE card_parent_id = ?) {}",
+        SELECT_CARD_ROWS, GROUP_BY_CARD_ROWS
     );
-    sqlx::query_as::<_, CardRow>(&sql).fetch_all(executor).await
+    sqlx::query_as::<_, CardRow>(&sql)
+        .bind(parent_id)
+        .fetch_all(executor)
+        .await
+}
+
+// ID を指定して複数件取得（順序は不定）
+pub async fn fetch_card_rows_by_ids<'e, E>(
+    executor: E,
+    card_ids: &[i64],
+) -> Result<Vec<CardRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    if card_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut query = QueryBuilder::<MySql>::new(SELECT_CARD_ROWS);
+    query.push(" AND c.id IN (");
+    let mut ids = query.separated(", ");
+    for card_id in card_ids {
+        ids.push_bind(*card_id);
+    }
+    query.push(") ").push(GROUP_BY_CARD_ROWS);
+
+    query.build_query_as::<CardRow>().fetch_all(executor).await
 }
 
 // 範囲クエリ（MBRIntersects + GROUP BY）
@@ -37,9 +112,8 @@ where
     E: Executor<'e, Database = MySql>,
 {
     let sql = format!(
-        "{} WHERE MBRIntersects(shape, ST_GeomFromText(?)) \
-         GROUP BY c.id, c.title, c.contents, c.visibility, c.card_type, c.ok_count, c.created_at, c.updated_at, pos_x, pos_y, size_x, size_y",
-        SELECT_CARD_ROWS
+        "{} AND MBRIntersects(shape, ST_GeomFromText(?)) {}",
+        SELECT_CARD_ROWS, GROUP_BY_CARD_ROWS
     );
     sqlx::query_as::<_, CardRow>(&sql)
         .bind(wkt_poly)
@@ -52,9 +126,171 @@ pub async fn fetch_card_row_by_id<'e, E>(executor: E, card_id: i64) -> Result<Ca
 where
     E: Executor<'e, Database = MySql>,
 {
-    let sql = format!("{} WHERE c.id = ?", SELECT_CARD_ROWS);
+    let sql = format!("{} AND c.id = ?", SELECT_CARD_ROWS);
     sqlx::query_as::<_, CardRow>(&sql)
         .bind(card_id)
         .fetch_one(executor)
         .await
 }
+
+// カードの行をロックして現在の version を取得（ゴミ箱のカード・存在しなければ None）
+pub async fn lock_card_version<'e, E>(executor: E, card_id: i64) -> Result<Option<i32>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_scalar("SELECT version FROM cards WHERE id = ? AND deleted_at IS NULL FOR UPDATE")
+        .bind(card_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// カードを１件作成して ID を返す（タグは別途 insert_card_tag）
+pub async fn insert_card<'e, E>(
+    executor: E,
+    params: &CardParams,
+    owner_id: Option<i64>,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let poly = create_poly(
+        params.position.x,
+        params.position.y,
+        params.position.x + params.size.x,
+        params.position.y + params.size.y,
+    );
+
+    let res = sqlx::query(
+        r#"
+        INSERT INTO cards (shape, title, contents, visibility, card_type, saved_query_id, owner_id)
+        VALUES (ST_GeomFromText(?), ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&poly)
+    .bind(&params.title)
+    .bind(&params.contents)
+    .bind(&params.visibility)
+    .bind(&params.card_type)
+    .bind(params.smart_frame_query_id())
+    .bind(owner_id)
+    .execute(executor)
+    .await?;
+
+    Ok(res.last_insert_id() as i64)
+}
+
+// カードの位置・サイズ・内容などを更新して version を上げる（タグは set_card_tags）
+pub async fn update_card_row<'e, E>(executor: E, params: &CardParams) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let poly = create_poly(
+        params.position.x,
+        params.position.y,
+        params.position.x + params.size.x,
+        params.position.y + params.size.y,
+    );
+
+    sqlx::query(
+        r#"
+        UPDATE cards
+        SET shape = ST_GeomFromText(?), title = ?, contents = ?, visibility = ?, card_type = ?,
+            saved_query_id = ?, version = version + 1
+        WHERE id = ?
+        "#,
+    )
+    .bind(&poly)
+    .bind(&params.title)
+    .bind(&params.contents)
+    .bind(&params.visibility)
+    .bind(&params.card_type)
+    .bind(params.smart_frame_query_id())
+    .bind(params.id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// カードの位置・サイズだけ更新して version を上げる
+pub async fn move_card_row<'e, E>(
+    executor: E,
+    card_id: i64,
+    position: &Dimmension,
+    size: &Dimmension,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let poly = create_poly(
+        position.x,
+        position.y,
+        position.x + size.x,
+        position.y + size.y,
+    );
+    sqlx::query("UPDATE cards SET shape = ST_GeomFromText(?), version = version + 1 WHERE id = ?")
+        .bind(&poly)
+        .bind(card_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// カードのタグを tag_ids に揃える（付いたままのタグの行は触らない）
+pub async fn set_card_tags(
+    tx: &mut Transaction<'_, MySql>,
+    card_id: i64,
+    tag_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    let current: Vec<i64> = sqlx::query_scalar("SELECT tag_id FROM card_tag WHERE card_id = ?")
+        .bind(card_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+    for tag_id in current.iter().filter(|tag_id| !tag_ids.contains(tag_id)) {
+        sqlx::query("DELETE FROM card_tag WHERE card_id = ? AND tag_id = ?")
+            .bind(card_id)
+            .bind(tag_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    let mut added = Vec::new();
+    for &tag_id in tag_ids {
+        if !current.contains(&tag_id) && !added.contains(&tag_id) {
+            insert_card_tag(&mut **tx, card_id, tag_id).await?;
+            added.push(tag_id);
+        }
+    }
+    Ok(())
+}
+
+// card_tag を１件追加
+pub async fn insert_card_tag<'e, E>(
+    executor: E,
+    card_id: i64,
+    tag_id: i64,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("INSERT INTO card_tag (card_id, tag_id) VALUES (?, ?)")
+        .bind(card_id)
+        .bind(tag_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// WKT ポリゴン
+pub fn create_poly(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> String {
+    format!(
+        "POLYGON((\
+        {min_x} {min_y}, {max_x} {min_y}, \
+        {max_x} {max_y}, {min_x} {max_y}, \
+        {min_x} {min_y} \
+      ))",
+        min_x = min_x,
+        min_y = min_y,
+        max_x = max_x,
+        max_y = max_y,
+    )
+}
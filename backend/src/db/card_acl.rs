@@ -0,0 +1,114 @@
+use sqlx::{Executor, FromRow, MySql, QueryBuilder, Transaction};
+
+use crate::models::CardAclEntry;
+
+/// What access checks need to know about a card.
+#[derive(FromRow)]
+pub struct CardAccessRow {
+    pub id: i64,
+    pub owner_id: Option<i64>,
+    pub visibility: String,
+}
+
+/// A row of `card_acl`.
+#[derive(FromRow)]
+pub struct CardAclRuleRow {
+    pub card_id: i64,
+    pub user_id: Option<i64>,
+    pub role: Option<String>,
+    pub permission: String,
+}
+
+// 全カードの所有者と公開範囲（ゴミ箱のカードも含む）
+pub async fn fetch_card_access_rows<'e, E>(executor: E) -> Result<Vec<CardAccessRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, CardAccessRow>("SELECT id, owner_id, visibility FROM cards")
+        .fetch_all(executor)
+        .await
+}
+
+// card_acl を全件取得
+pub async fn fetch_card_acl_rules<'e, E>(executor: E) -> Result<Vec<CardAclRuleRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, CardAclRuleRow>(
+        "SELECT card_id, user_id, role, permission FROM card_acl ORDER BY id",
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// 親子関係を (親, 子) で全件取得
+pub async fn fetch_card_parent_edges<'e, E>(executor: E) -> Result<Vec<(i64, i64)>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, (i64, i64)>("SELECT card_parent_id, card_child_id FROM card_card")
+        .fetch_all(executor)
+        .await
+}
+
+// フレーム１件の ACL を取得
+pub async fn fetch_card_acl<'e, E>(
+    executor: E,
+    card_id: i64,
+) -> Result<Vec<CardAclEntry>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, CardAclEntry>(
+        "SELECT user_id, role, permission FROM card_acl WHERE card_id = ? ORDER BY id",
+    )
+    .bind(card_id)
+    .fetch_all(executor)
+    .await
+}
+
+// フレームの ACL を丸ごと置き換える（空なら ACL なし）。
+// 見えるカードが変わるので、フレームと配下のカードを変更フィードに載せる
+pub async fn replace_card_acl(
+    tx: &mut Transaction<'_, MySql>,
+    card_id: i64,
+    entries: &[CardAclEntry],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM card_acl WHERE card_id = ?")
+        .bind(card_id)
+        .execute(&mut **tx)
+        .await?;
+    if !entries.is_empty() {
+        let mut insert = QueryBuilder::<MySql>::new(
+            "INSERT INTO card_acl (card_id, user_id, role, permission) ",
+        );
+        insert.push_values(entries, |mut values, entry| {
+            values
+                .push_bind(card_id)
+                .push_bind(entry.user_id)
+                .push_bind(entry.role.as_deref())
+                .push_bind(&entry.permission);
+        });
+        insert.build().execute(&mut **tx).await?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO sync_pending (entity, entity_id)
+        SELECT 'card', id FROM (
+          WITH RECURSIVE governed (id) AS (
+            SELECT ?
+            UNION
+            SELECT cc.card_child_id
+              FROM card_card cc
+              JOIN governed g ON cc.card_parent_id = g.id
+          )
+          SELECT id FROM governed
+        ) governed_cards
+        "#,
+    )
+    .bind(card_id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
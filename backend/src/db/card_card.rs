@@ -0,0 +1,122 @@
+use sqlx::{Executor, MySql, QueryBuilder};
+
+use crate::models::CardRelation;
+
+const SELECT_CARD_RELATION: &str = r#"
+SELECT card_parent_id, card_child_id, connector, version, created_at, updated_at
+FROM card_card
+WHERE card_parent_id = ? AND card_child_id = ?
+"#;
+
+// 親子関係を１件追加
+pub async fn insert_card_relation<'e, E>(
+    executor: E,
+    parent_id: i64,
+    child_id: i64,
+    connector: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO card_card (card_parent_id, card_child_id, connector)
+        VALUES (?, ?, ?)
+        "#,
+    )
+    .bind(parent_id)
+    .bind(child_id)
+    .bind(connector)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// 親子関係を１件削除（無ければ false）
+pub async fn delete_card_relation<'e, E>(
+    executor: E,
+    parent_id: i64,
+    child_id: i64,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("DELETE FROM card_card WHERE card_parent_id = ? AND card_child_id = ?")
+        .bind(parent_id)
+        .bind(child_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// 親子関係を１件取得
+pub async fn fetch_card_relation<'e, E>(
+    executor: E,
+    parent_id: i64,
+    child_id: i64,
+) -> Result<Option<CardRelation>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, CardRelation>(SELECT_CARD_RELATION)
+        .bind(parent_id)
+        .bind(child_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// 親子関係を１件取得し、トランザクションの終わりまで行をロック
+pub async fn lock_card_relation<'e, E>(
+    executor: E,
+    parent_id: i64,
+    child_id: i64,
+) -> Result<Option<CardRelation>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} FOR UPDATE", SELECT_CARD_RELATION);
+    sqlx::query_as::<_, CardRelation>(&sql)
+        .bind(parent_id)
+        .bind(child_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// 指定の親子関係と、指定カードに接続する親子関係をまとめて取得
+pub async fn fetch_card_relations<'e, E>(
+    executor: E,
+    keys: &[(i64, i64)],
+    card_ids: &[i64],
+) -> Result<Vec<CardRelation>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    if keys.is_empty() && card_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT card_parent_id, card_child_id, connector, version, created_at, updated_at \
+         FROM card_card WHERE ",
+    );
+    let mut conditions = query.separated(" OR ");
+    for (parent_id, child_id) in keys {
+        conditions
+            .push("(card_parent_id = ")
+            .push_bind_unseparated(*parent_id)
+            .push_unseparated(" AND card_child_id = ")
+            .push_bind_unseparated(*child_id)
+            .push_unseparated(")");
+    }
+    for card_id in card_ids {
+        conditions
+            .push("card_parent_id = ")
+            .push_bind_unseparated(*card_id)
+            .push("card_child_id = ")
+            .push_bind_unseparated(*card_id);
+    }
+
+    query
+        .build_query_as::<CardRelation>()
+        .fetch_all(executor)
+        .await
+}
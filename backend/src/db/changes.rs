@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+
+use sqlx::{Executor, FromRow, MySql, Pool, QueryBuilder};
+
+/// Pending changes moved into the feed per transaction.
+const STAMP_BATCH: i64 = 1000;
+
+/// A row of `sync_pending` or `sync_changes`.
+#[derive(FromRow)]
+pub struct ChangeRow {
+    /// `sync_pending.id` or `sync_changes.seq`.
+    pub seq: i64,
+    /// `card`, `relation` or `tag`.
+    pub entity: String,
+    pub entity_id: i64,
+    pub child_id: i64,
+}
+
+// コミット済みの sync_pending を sync_changes へ移して seq を振る（書き込み側で呼ぶ）。
+// sync_lock で直列化するので、seq は変更が見えるようになった順に増える
+pub async fn stamp_changes(pool: &Pool<MySql>) -> Result<(), sqlx::Error> {
+    loop {
+        let mut tx = pool.begin().await?;
+        sqlx::query("SELECT id FROM sync_lock WHERE id = 1 FOR UPDATE")
+            .execute(&mut *tx)
+            .await?;
+
+        // Plain read: rows of transactions still in flight are skipped, not
+        // waited for, and get a later seq once they commit.
+        let pending = sqlx::query_as::<_, ChangeRow>(
+            "SELECT id AS seq, entity, entity_id, child_id FROM sync_pending ORDER BY id LIMIT ?",
+        )
+        .bind(STAMP_BATCH)
+        .fetch_all(&mut *tx)
+        .await?;
+        if pending.is_empty() {
+            tx.commit().await?;
+            return Ok(());
+        }
+
+        // Latest change of each entity, in the order they were made.
+        let mut seen = HashSet::new();
+        let mut latest: Vec<&ChangeRow> = pending
+            .iter()
+            .rev()
+            .filter(|row| seen.insert((row.entity.as_str(), row.entity_id, row.child_id)))
+            .collect();
+        latest.reverse();
+
+        // Each entity keeps only its newest row.
+        let mut delete = QueryBuilder::<MySql>::new("DELETE FROM sync_changes WHERE ");
+        let mut keys = delete.separated(" OR ");
+        for row in &latest {
+            keys.push("(entity = ")
+                .push_bind_unseparated(&row.entity)
+                .push_unseparated(" AND entity_id = ")
+                .push_bind_unseparated(row.entity_id)
+                .push_unseparated(" AND child_id = ")
+                .push_bind_unseparated(row.child_id)
+                .push_unseparated(")");
+        }
+        delete.build().execute(&mut *tx).await?;
+
+        let mut insert =
+            QueryBuilder::<MySql>::new("INSERT INTO sync_changes (entity, entity_id, child_id) ");
+        insert.push_values(&latest, |mut values, row| {
+            values
+                .push_bind(&row.entity)
+                .push_bind(row.entity_id)
+                .push_bind(row.child_id);
+        });
+        insert.build().execute(&mut *tx).await?;
+
+        let mut done = QueryBuilder::<MySql>::new("DELETE FROM sync_pending WHERE id IN (");
+        let mut ids = done.separated(", ");
+        for row in &pending {
+            ids.push_bind(row.seq);
+        }
+        done.push(")");
+        done.build().execute(&mut *tx).await?;
+
+        tx.commit().await?;
+        if (pending.len() as i64) < STAMP_BATCH {
+            return Ok(());
+        }
+    }
+}
+
+// seq が since より後の変更を seq 順に取得
+pub async fn fetch_changes<'e, E>(
+    executor: E,
+    since: i64,
+    limit: i64,
+) -> Result<Vec<ChangeRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, ChangeRow>(
+        r#"
+        SELECT seq, entity, entity_id, child_id
+        FROM sync_changes
+        WHERE seq > ?
+        ORDER BY seq
+        LIMIT ?
+        "#,
+    )
+    .bind(since)
+    .bind(limit)
+    .fetch_all(executor)
+    .await
+}
@@ -0,0 +1,44 @@
+use chrono::NaiveDateTime;
+use sqlx::{Executor, MySql};
+
+/// One flash-card answer to append to `card_reviews`.
+pub struct NewCardReview<'a> {
+    pub card_id: i64,
+    pub item_key: &'a str,
+    pub reviewed_at: NaiveDateTime,
+    pub is_ok: bool,
+    pub response_ms: Option<i32>,
+    pub scope_tag: Option<&'a str>,
+    pub scope_parent_id: Option<i64>,
+    /// Study session the answer was given in, if any.
+    pub session_id: Option<i64>,
+}
+
+// レビュー履歴を１件追加
+pub async fn insert_card_review<'e, E>(
+    executor: E,
+    review: &NewCardReview<'_>,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO card_reviews
+          (card_id, item_key, reviewed_at, is_ok, response_ms, scope_tag, scope_parent_id,
+           session_id)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(review.card_id)
+    .bind(review.item_key)
+    .bind(review.reviewed_at)
+    .bind(review.is_ok)
+    .bind(review.response_ms)
+    .bind(review.scope_tag)
+    .bind(review.scope_parent_id)
+    .bind(review.session_id)
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
@@ -0,0 +1,92 @@
+use sqlx::{Executor, MySql, Transaction};
+
+use crate::models::{CardRevision, RevisionSource};
+
+/// A card's title and contents after a change, for `card_revisions`.
+pub struct NewCardRevision<'a> {
+    pub card_id: i64,
+    pub title: &'a str,
+    pub contents: &'a str,
+    pub user_id: Option<i64>,
+    pub source: RevisionSource,
+    pub restored_from: Option<i64>,
+}
+
+const SELECT_CARD_REVISIONS: &str = r#"
+SELECT r.id, r.card_id, COALESCE(r.title, '') AS title, COALESCE(r.contents, '') AS contents,
+       r.user_id, u.username, r.source, r.restored_from, r.created_at
+FROM card_revisions r
+LEFT JOIN users u ON u.id = r.user_id
+"#;
+
+// 最新のリビジョンとタイトル・本文が異なるときだけ１件追加し、その ID を返す
+// （位置やサイズだけの更新では履歴を増やさない）
+pub async fn record_card_revision(
+    tx: &mut Transaction<'_, MySql>,
+    revision: &NewCardRevision<'_>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let latest = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+        "SELECT title, contents FROM card_revisions WHERE card_id = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(revision.card_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    if let Some((title, contents)) = latest {
+        if title.as_deref().unwrap_or_default() == revision.title
+            && contents.as_deref().unwrap_or_default() == revision.contents
+        {
+            return Ok(None);
+        }
+    }
+
+    let res = sqlx::query(
+        r#"
+        INSERT INTO card_revisions (card_id, title, contents, user_id, source, restored_from)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(revision.card_id)
+    .bind(revision.title)
+    .bind(revision.contents)
+    .bind(revision.user_id)
+    .bind(revision.source.as_str())
+    .bind(revision.restored_from)
+    .execute(&mut **tx)
+    .await?;
+    Ok(Some(res.last_insert_id() as i64))
+}
+
+// カードのリビジョンを新しい順に取得
+pub async fn fetch_card_revisions<'e, E>(
+    executor: E,
+    card_id: i64,
+) -> Result<Vec<CardRevision>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!(
+        "{} WHERE r.card_id = ? ORDER BY r.id DESC",
+        SELECT_CARD_REVISIONS
+    );
+    sqlx::query_as::<_, CardRevision>(&sql)
+        .bind(card_id)
+        .fetch_all(executor)
+        .await
+}
+
+// カードのリビジョンを１件取得（別カードの ID なら None）
+pub async fn fetch_card_revision<'e, E>(
+    executor: E,
+    card_id: i64,
+    revision_id: i64,
+) -> Result<Option<CardRevision>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} WHERE r.card_id = ? AND r.id = ?", SELECT_CARD_REVISIONS);
+    sqlx::query_as::<_, CardRevision>(&sql)
+        .bind(card_id)
+        .bind(revision_id)
+        .fetch_optional(executor)
+        .await
+}
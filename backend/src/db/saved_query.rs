@@ -0,0 +1,97 @@
+use sqlx::{Executor, MySql};
+
+use crate::models::SavedQuery;
+
+const SELECT_SAVED_QUERIES: &str =
+    "SELECT id, name, query, created_at, updated_at FROM saved_queries";
+
+// 保存済みクエリを全件取得（名前順）
+pub async fn fetch_saved_queries<'e, E>(executor: E) -> Result<Vec<SavedQuery>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} ORDER BY name", SELECT_SAVED_QUERIES);
+    sqlx::query_as::<_, SavedQuery>(&sql)
+        .fetch_all(executor)
+        .await
+}
+
+// ID 指定で１件取得
+pub async fn fetch_saved_query<'e, E>(
+    executor: E,
+    saved_query_id: i64,
+) -> Result<Option<SavedQuery>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} WHERE id = ?", SELECT_SAVED_QUERIES);
+    sqlx::query_as::<_, SavedQuery>(&sql)
+        .bind(saved_query_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// 保存済みクエリを１件作成して ID を返す
+pub async fn insert_saved_query<'e, E>(
+    executor: E,
+    name: &str,
+    query: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("INSERT INTO saved_queries (name, query) VALUES (?, ?)")
+        .bind(name)
+        .bind(query)
+        .execute(executor)
+        .await?;
+    Ok(res.last_insert_id() as i64)
+}
+
+// 名前とクエリを更新。対象がなければ false
+pub async fn update_saved_query<'e, E>(
+    executor: E,
+    saved_query_id: i64,
+    name: &str,
+    query: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("UPDATE saved_queries SET name = ?, query = ? WHERE id = ?")
+        .bind(name)
+        .bind(query)
+        .bind(saved_query_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// 保存済みクエリを削除。対象がなければ false
+pub async fn delete_saved_query<'e, E>(
+    executor: E,
+    saved_query_id: i64,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("DELETE FROM saved_queries WHERE id = ?")
+        .bind(saved_query_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// このクエリを使っているスマートフレームの数
+pub async fn count_smart_frames_using<'e, E>(
+    executor: E,
+    saved_query_id: i64,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_scalar("SELECT COUNT(*) FROM cards WHERE saved_query_id = ?")
+        .bind(saved_query_id)
+        .fetch_one(executor)
+        .await
+}
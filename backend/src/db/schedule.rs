@@ -0,0 +1,98 @@
+use crate::models::{CardItemSchedule, CardSchedule};
+use sqlx::{Executor, MySql, QueryBuilder};
+
+// スケジュール状態を取得（未学習の項目は None）
+pub async fn fetch_card_schedule<'e, E>(
+    executor: E,
+    card_id: i64,
+    item_key: &str,
+) -> Result<Option<CardSchedule>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, CardSchedule>(
+        r#"
+        SELECT ease_factor, interval_days, stability, difficulty, reps, lapses, leitner_box, due_at, last_reviewed_at
+        FROM card_schedules
+        WHERE card_id = ? AND item_key = ?
+        "#,
+    )
+    .bind(card_id)
+    .bind(item_key)
+    .fetch_optional(executor)
+    .await
+}
+
+// 複数カードの全項目のスケジュール状態を取得
+pub async fn fetch_card_item_schedules<'e, E>(
+    executor: E,
+    card_ids: &[i64],
+) -> Result<Vec<CardItemSchedule>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    if card_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT card_id, item_key, ease_factor, interval_days, stability, difficulty, \
+         reps, lapses, leitner_box, due_at, last_reviewed_at FROM card_schedules WHERE card_id IN (",
+    );
+    let mut ids = query.separated(", ");
+    for id in card_ids {
+        ids.push_bind(id);
+    }
+    query.push(")");
+
+    query
+        .build_query_as::<CardItemSchedule>()
+        .fetch_all(executor)
+        .await
+}
+
+// スケジュール状態を保存（INSERT or UPDATE）
+pub async fn upsert_card_schedule<'e, E>(
+    executor: E,
+    card_id: i64,
+    item_key: &str,
+    algorithm: &str,
+    schedule: &CardSchedule,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO card_schedules
+          (card_id, item_key, algorithm, ease_factor, interval_days, stability, difficulty, reps, lapses, leitner_box, due_at, last_reviewed_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON DUPLICATE KEY UPDATE
+          algorithm = VALUES(algorithm),
+          ease_factor = VALUES(ease_factor),
+          interval_days = VALUES(interval_days),
+          stability = VALUES(stability),
+          difficulty = VALUES(difficulty),
+          reps = VALUES(reps),
+          lapses = VALUES(lapses),
+          leitner_box = VALUES(leitner_box),
+          due_at = VALUES(due_at),
+          last_reviewed_at = VALUES(last_reviewed_at)
+        "#,
+    )
+    .bind(card_id)
+    .bind(item_key)
+    .bind(algorithm)
+    .bind(schedule.ease_factor)
+    .bind(schedule.interval_days)
+    .bind(schedule.stability)
+    .bind(schedule.difficulty)
+    .bind(schedule.reps)
+    .bind(schedule.lapses)
+    .bind(schedule.leitner_box)
+    .bind(schedule.due_at)
+    .bind(schedule.last_reviewed_at)
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
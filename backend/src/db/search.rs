@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use sqlx::{Executor, FromRow, MySql, QueryBuilder, Transaction};
+
+use crate::search::{index_terms, QueryTerm};
+
+/// Rows per multi-row INSERT into `card_search_terms`.
+const INSERT_CHUNK: usize = 500;
+
+/// An indexed term of a card matching a query term.
+#[derive(FromRow)]
+pub struct SearchTermRow {
+    pub card_id: i64,
+    pub term: String,
+    pub title_count: i32,
+    pub contents_count: i32,
+    pub title_length: i32,
+    pub contents_length: i32,
+}
+
+#[derive(FromRow)]
+pub struct UnindexedCardRow {
+    pub id: i64,
+    pub title: String,
+    pub contents: String,
+}
+
+// カードの検索インデックスを作り直す
+pub async fn index_card(
+    tx: &mut Transaction<'_, MySql>,
+    card_id: i64,
+    title: &str,
+    contents: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM card_search_terms WHERE card_id = ?")
+        .bind(card_id)
+        .execute(&mut **tx)
+        .await?;
+
+    let title_terms = index_terms(title);
+    let contents_terms = index_terms(contents);
+    let mut terms: BTreeMap<&str, (i32, i32)> = BTreeMap::new();
+    for (term, count) in &title_terms {
+        terms.entry(term).or_default().0 = *count;
+    }
+    for (term, count) in &contents_terms {
+        terms.entry(term).or_default().1 = *count;
+    }
+
+    let entries: Vec<_> = terms.into_iter().collect();
+    for chunk in entries.chunks(INSERT_CHUNK) {
+        let mut query = QueryBuilder::<MySql>::new(
+            "INSERT INTO card_search_terms (card_id, term, title_count, contents_count) ",
+        );
+        query.push_values(chunk, |mut row, (term, (title_count, contents_count))| {
+            row.push_bind(card_id)
+                .push_bind(*term)
+                .push_bind(*title_count)
+                .push_bind(*contents_count);
+        });
+        query.build().execute(&mut **tx).await?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO card_search_docs (card_id, title_length, contents_length)
+        VALUES (?, ?, ?)
+        ON DUPLICATE KEY UPDATE
+          title_length = VALUES(title_length),
+          contents_length = VALUES(contents_length)
+        "#,
+    )
+    .bind(card_id)
+    .bind(title_terms.values().sum::<i32>())
+    .bind(contents_terms.values().sum::<i32>())
+    .execute(&mut **tx)
+    .await
+    .map(|_| ())
+}
+
+// 検索語に一致する索引語を取得（前方一致の語は LIKE）
+pub async fn fetch_search_terms<'e, E>(
+    executor: E,
+    terms: &[QueryTerm],
+) -> Result<Vec<SearchTermRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT t.card_id, t.term, t.title_count, t.contents_count, \
+         d.title_length, d.contents_length \
+         FROM card_search_terms t \
+         JOIN card_search_docs d ON d.card_id = t.card_id \
+         JOIN cards c ON c.id = t.card_id \
+         WHERE c.deleted_at IS NULL AND (1 = 0",
+    );
+    for term in terms {
+        if term.prefix {
+            // Terms are letters and digits only, so there is nothing to escape.
+            query
+                .push(" OR t.term LIKE ")
+                .push_bind(format!("{}%", term.term));
+        } else {
+            query.push(" OR t.term = ").push_bind(term.term.clone());
+        }
+    }
+    query.push(")");
+
+    query
+        .build_query_as::<SearchTermRow>()
+        .fetch_all(executor)
+        .await
+}
+
+// 索引済みカード数と平均の長さ（タイトルは重み付け）
+pub async fn fetch_search_stats<'e, E>(
+    executor: E,
+    title_weight: f64,
+) -> Result<(i64, f64), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, (i64, f64)>(
+        r#"
+        SELECT COUNT(*),
+               CAST(COALESCE(AVG(title_length * ? + contents_length), 0) AS DOUBLE)
+        FROM card_search_docs
+        "#,
+    )
+    .bind(title_weight)
+    .fetch_one(executor)
+    .await
+}
+
+// 検索インデックスが無いカード（マイグレーション前からあるカード）
+pub async fn fetch_unindexed_cards<'e, E>(executor: E) -> Result<Vec<UnindexedCardRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, UnindexedCardRow>(
+        r#"
+        SELECT c.id, COALESCE(c.title, '') AS title, COALESCE(c.contents, '') AS contents
+        FROM cards c
+        LEFT JOIN card_search_docs d ON d.card_id = c.id
+        WHERE d.card_id IS NULL
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+}
@@ -0,0 +1,124 @@
+use sqlx::{Executor, FromRow, MySql};
+
+/// A live session and its user.
+#[derive(FromRow)]
+pub struct SessionRow {
+    pub user_id: i64,
+    pub role: String,
+    /// Last seen long enough ago that its expiry should be pushed back.
+    pub needs_touch: bool,
+}
+
+// セッションを作成（token はハッシュ化して保存）
+pub async fn insert_session<'e, E>(
+    executor: E,
+    token: &str,
+    user_id: i64,
+    ttl_days: i64,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO sessions (token_hash, user_id, expires_at)
+        VALUES (SHA2(?, 256), ?, CURRENT_TIMESTAMP + INTERVAL ? DAY)
+        "#,
+    )
+    .bind(token)
+    .bind(user_id)
+    .bind(ttl_days)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// 期限内で、ユーザーが有効なセッションを取得
+pub async fn fetch_session<'e, E>(
+    executor: E,
+    token: &str,
+    touch_after_secs: i64,
+) -> Result<Option<SessionRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, SessionRow>(
+        r#"
+        SELECT s.user_id, u.role,
+            s.last_seen_at < CURRENT_TIMESTAMP - INTERVAL ? SECOND AS needs_touch
+        FROM sessions s
+        JOIN users u ON u.id = s.user_id
+        WHERE s.token_hash = SHA2(?, 256)
+          AND s.expires_at > CURRENT_TIMESTAMP
+          AND u.is_active = TRUE
+        "#,
+    )
+    .bind(touch_after_secs)
+    .bind(token)
+    .fetch_optional(executor)
+    .await
+}
+
+// 最終利用日時を更新し、期限を ttl_days 日後へ延長
+pub async fn touch_session<'e, E>(
+    executor: E,
+    token: &str,
+    ttl_days: i64,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        UPDATE sessions
+        SET last_seen_at = CURRENT_TIMESTAMP,
+            expires_at = CURRENT_TIMESTAMP + INTERVAL ? DAY
+        WHERE token_hash = SHA2(?, 256) AND expires_at > CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(ttl_days)
+    .bind(token)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// セッションを削除（ログアウト）
+pub async fn delete_session<'e, E>(executor: E, token: &str) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("DELETE FROM sessions WHERE token_hash = SHA2(?, 256)")
+        .bind(token)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// ユーザーのセッションを削除（keep_token のセッションは残す）
+pub async fn delete_user_sessions<'e, E>(
+    executor: E,
+    user_id: i64,
+    keep_token: Option<&str>,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("DELETE FROM sessions WHERE user_id = ? AND NOT token_hash <=> SHA2(?, 256)")
+        .bind(user_id)
+        .bind(keep_token)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// 期限切れのセッションを削除
+pub async fn delete_expired_sessions<'e, E>(executor: E) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("DELETE FROM sessions WHERE expires_at <= CURRENT_TIMESTAMP")
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected())
+}
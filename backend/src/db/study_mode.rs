@@ -0,0 +1,74 @@
+use sqlx::{Executor, MySql, QueryBuilder};
+
+// フレーム・タグに設定された学習モード。フレームの設定を優先し、
+// タグ同士では先に設定されたものを使う
+pub async fn fetch_study_mode<'e, E>(
+    executor: E,
+    frame_id: Option<i64>,
+    tag_names: &[String],
+) -> Result<Option<String>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT sm.mode FROM study_modes sm \
+         LEFT JOIN tags t ON t.id = sm.tag_id WHERE sm.frame_id = ",
+    );
+    query.push_bind(frame_id);
+    if !tag_names.is_empty() {
+        query.push(" OR t.name IN (");
+        let mut names = query.separated(", ");
+        for name in tag_names {
+            names.push_bind(name.clone());
+        }
+        query.push(")");
+    }
+    query.push(" ORDER BY sm.frame_id IS NULL, sm.id LIMIT 1");
+
+    query
+        .build_query_scalar::<String>()
+        .fetch_optional(executor)
+        .await
+}
+
+// 学習モードを設定（INSERT or UPDATE）
+pub async fn upsert_study_mode<'e, E>(
+    executor: E,
+    tag_id: Option<i64>,
+    frame_id: Option<i64>,
+    mode: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO study_modes (tag_id, frame_id, mode)
+        VALUES (?, ?, ?)
+        ON DUPLICATE KEY UPDATE mode = VALUES(mode)
+        "#,
+    )
+    .bind(tag_id)
+    .bind(frame_id)
+    .bind(mode)
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+// 学習モードの設定を削除（既定のスケジューラに戻す）
+pub async fn delete_study_mode<'e, E>(
+    executor: E,
+    tag_id: Option<i64>,
+    frame_id: Option<i64>,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("DELETE FROM study_modes WHERE tag_id <=> ? AND frame_id <=> ?")
+        .bind(tag_id)
+        .bind(frame_id)
+        .execute(executor)
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,201 @@
+use chrono::NaiveDateTime;
+use sqlx::{Executor, FromRow, MySql};
+
+use crate::models::StudySessionRow;
+
+const SELECT_STUDY_SESSION: &str = r#"
+SELECT id, user_id, scope, new_limit, learning_limit, review_limit,
+       new_count, learning_count, review_count,
+       current_card_id, current_item_key, current_queue, served_at,
+       started_at, finished_at
+FROM study_sessions
+WHERE id = ?
+"#;
+
+/// Answers given in a session, in order.
+#[derive(FromRow)]
+pub struct SessionAnswerRow {
+    pub card_id: i64,
+    pub item_key: String,
+    pub is_ok: bool,
+}
+
+/// Totals over the answers given in a session.
+#[derive(FromRow)]
+pub struct SessionTotalsRow {
+    pub answered: i64,
+    pub correct: i64,
+    pub total_ms: i64,
+}
+
+// 学習セッションを作成して ID を返す
+pub async fn insert_study_session<'e, E>(
+    executor: E,
+    user_id: i64,
+    scope: &str,
+    new_limit: i32,
+    learning_limit: i32,
+    review_limit: i32,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query(
+        r#"
+        INSERT INTO study_sessions (user_id, scope, new_limit, learning_limit, review_limit)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(user_id)
+    .bind(scope)
+    .bind(new_limit)
+    .bind(learning_limit)
+    .bind(review_limit)
+    .execute(executor)
+    .await?;
+    Ok(res.last_insert_id() as i64)
+}
+
+// 学習セッションを取得
+pub async fn fetch_study_session<'e, E>(
+    executor: E,
+    session_id: i64,
+) -> Result<Option<StudySessionRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, StudySessionRow>(SELECT_STUDY_SESSION)
+        .bind(session_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// 学習セッションを行ロック付きで取得（トランザクション内で使う）
+pub async fn lock_study_session<'e, E>(
+    executor: E,
+    session_id: i64,
+) -> Result<Option<StudySessionRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, StudySessionRow>(&format!("{SELECT_STUDY_SESSION} FOR UPDATE"))
+        .bind(session_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// 出題中の項目を設定
+pub async fn set_study_session_current<'e, E>(
+    executor: E,
+    session_id: i64,
+    card_id: i64,
+    item_key: &str,
+    queue: &str,
+    served_at: NaiveDateTime,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        UPDATE study_sessions
+        SET current_card_id = ?, current_item_key = ?, current_queue = ?, served_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(card_id)
+    .bind(item_key)
+    .bind(queue)
+    .bind(served_at)
+    .bind(session_id)
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+// 出題中の項目への回答を反映（キューごとの回答数を加算し、出題中を解除）
+pub async fn complete_study_session_current<'e, E>(
+    executor: E,
+    session_id: i64,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        UPDATE study_sessions
+        SET new_count = new_count + (current_queue = 'new'),
+            learning_count = learning_count + (current_queue = 'learning'),
+            review_count = review_count + (current_queue = 'review'),
+            current_card_id = NULL, current_item_key = NULL,
+            current_queue = NULL, served_at = NULL
+        WHERE id = ?
+        "#,
+    )
+    .bind(session_id)
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+// 学習セッションを終了（終了済みなら何もしない）
+pub async fn finish_study_session<'e, E>(
+    executor: E,
+    session_id: i64,
+    finished_at: NaiveDateTime,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        r#"
+        UPDATE study_sessions
+        SET finished_at = ?, current_card_id = NULL, current_item_key = NULL,
+            current_queue = NULL, served_at = NULL
+        WHERE id = ? AND finished_at IS NULL
+        "#,
+    )
+    .bind(finished_at)
+    .bind(session_id)
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+// セッション内の回答を古い順に取得
+pub async fn fetch_session_answers<'e, E>(
+    executor: E,
+    session_id: i64,
+) -> Result<Vec<SessionAnswerRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, SessionAnswerRow>(
+        "SELECT card_id, item_key, is_ok FROM card_reviews WHERE session_id = ? ORDER BY id",
+    )
+    .bind(session_id)
+    .fetch_all(executor)
+    .await
+}
+
+// セッション内の回答数・正答数・回答時間の合計
+pub async fn fetch_session_totals<'e, E>(
+    executor: E,
+    session_id: i64,
+) -> Result<SessionTotalsRow, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, SessionTotalsRow>(
+        r#"
+        SELECT COUNT(*) AS answered,
+               CAST(COALESCE(SUM(is_ok), 0) AS SIGNED) AS correct,
+               CAST(COALESCE(SUM(response_ms), 0) AS SIGNED) AS total_ms
+        FROM card_reviews
+        WHERE session_id = ?
+        "#,
+    )
+    .bind(session_id)
+    .fetch_one(executor)
+    .await
+}
@@ -0,0 +1,52 @@
+use sqlx::{Executor, MySql};
+
+// ユーザーが適用済みの /sync 操作を取得。未適用なら None、適用済みなら作成したカード
+pub async fn fetch_applied_sync_op<'e, E>(
+    executor: E,
+    user_id: Option<i64>,
+    op_id: &str,
+) -> Result<Option<Option<i64>>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT card_id FROM sync_applied_ops WHERE user_id <=> ? AND op_id = ?",
+    )
+    .bind(user_id)
+    .bind(op_id)
+    .fetch_optional(executor)
+    .await
+}
+
+// ユーザーの /sync 操作を適用済みとして記録
+pub async fn record_applied_sync_op<'e, E>(
+    executor: E,
+    user_id: Option<i64>,
+    op_id: &str,
+    card_id: Option<i64>,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("INSERT INTO sync_applied_ops (user_id, op_id, card_id) VALUES (?, ?, ?)")
+        .bind(user_id)
+        .bind(op_id)
+        .bind(card_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// days 日より前に適用した /sync 操作の記録を削除
+pub async fn prune_applied_sync_ops<'e, E>(executor: E, days: i64) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query(
+        "DELETE FROM sync_applied_ops WHERE created_at < CURRENT_TIMESTAMP - INTERVAL ? DAY",
+    )
+    .bind(days)
+    .execute(executor)
+    .await?;
+    Ok(res.rows_affected())
+}
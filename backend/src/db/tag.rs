@@ -0,0 +1,56 @@
+use sqlx::{Executor, MySql, QueryBuilder, Transaction};
+
+use crate::models::TagRow;
+
+// 名前でタグの ID を取得
+pub async fn fetch_tag_id<'e, E>(executor: E, name: &str) -> Result<Option<i64>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_scalar::<_, i64>("SELECT id FROM tags WHERE name = ? ORDER BY id LIMIT 1")
+        .bind(name)
+        .fetch_optional(executor)
+        .await
+}
+
+// 名前でタグを探し、無ければ作成して ID を返す
+pub async fn find_or_create_tag(
+    tx: &mut Transaction<'_, MySql>,
+    name: &str,
+) -> Result<i64, sqlx::Error> {
+    let existing =
+        sqlx::query_scalar::<_, i64>("SELECT id FROM tags WHERE name = ? ORDER BY id LIMIT 1")
+            .bind(name)
+            .fetch_optional(&mut **tx)
+            .await?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    let res = sqlx::query("INSERT INTO tags (name) VALUES (?)")
+        .bind(name)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.last_insert_id() as i64)
+}
+
+// ID を指定してタグをまとめて取得（存在しないものは含まれない）
+pub async fn fetch_tags_by_ids<'e, E>(
+    executor: E,
+    tag_ids: &[i64],
+) -> Result<Vec<TagRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    if tag_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut query = QueryBuilder::<MySql>::new("SELECT id, name FROM tags WHERE id IN (");
+    let mut ids = query.separated(", ");
+    for tag_id in tag_ids {
+        ids.push_bind(*tag_id);
+    }
+    query.push(")");
+
+    query.build_query_as::<TagRow>().fetch_all(executor).await
+}
@@ -0,0 +1,235 @@
+use sqlx::{Executor, FromRow, MySql, Transaction};
+
+use crate::models::TrashedCard;
+
+/// A `card_card` row moved to the trash with one of its cards.
+#[derive(FromRow)]
+pub struct TrashedRelation {
+    pub card_parent_id: i64,
+    pub card_child_id: i64,
+    /// The card at the other end is in the trash too.
+    pub other_deleted: bool,
+}
+
+// カードをゴミ箱へ移す（card_card / card_tag の行は trashed_* へ退避）。
+// 存在しない・既にゴミ箱にあるときは false
+pub async fn trash_card(
+    tx: &mut Transaction<'_, MySql>,
+    card_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE cards SET deleted_at = CURRENT_TIMESTAMP, version = version + 1 \
+         WHERE id = ? AND deleted_at IS NULL",
+    )
+    .bind(card_id)
+    .execute(&mut **tx)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO trashed_card_card
+            (trashed_card_id, card_parent_id, card_child_id, connector, version, created_at)
+        SELECT ?, card_parent_id, card_child_id, connector, version, created_at
+        FROM card_card
+        WHERE card_parent_id = ? OR card_child_id = ?
+        "#,
+    )
+    .bind(card_id)
+    .bind(card_id)
+    .bind(card_id)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query("DELETE FROM card_card WHERE card_parent_id = ? OR card_child_id = ?")
+        .bind(card_id)
+        .bind(card_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO trashed_card_tag (card_id, tag_id, created_at)
+        SELECT card_id, tag_id, created_at FROM card_tag WHERE card_id = ?
+        "#,
+    )
+    .bind(card_id)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query("DELETE FROM card_tag WHERE card_id = ?")
+        .bind(card_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(true)
+}
+
+// ゴミ箱からカードを戻し、タグも戻す（親子関係は restore_trashed_relation で個別に）。
+// ゴミ箱に無いときは false
+pub async fn untrash_card(
+    tx: &mut Transaction<'_, MySql>,
+    card_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE cards SET deleted_at = NULL, version = version + 1 \
+         WHERE id = ? AND deleted_at IS NOT NULL",
+    )
+    .bind(card_id)
+    .execute(&mut **tx)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO card_tag (card_id, tag_id, created_at)
+        SELECT card_id, tag_id, created_at FROM trashed_card_tag WHERE card_id = ?
+        "#,
+    )
+    .bind(card_id)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query("DELETE FROM trashed_card_tag WHERE card_id = ?")
+        .bind(card_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(true)
+}
+
+// カードと一緒に退避した親子関係
+pub async fn fetch_trashed_relations<'e, E>(
+    executor: E,
+    card_id: i64,
+) -> Result<Vec<TrashedRelation>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, TrashedRelation>(
+        r#"
+        SELECT t.card_parent_id, t.card_child_id,
+               (p.deleted_at IS NOT NULL OR c.deleted_at IS NOT NULL) AS other_deleted
+        FROM trashed_card_card t
+        JOIN cards p ON p.id = t.card_parent_id
+        JOIN cards c ON c.id = t.card_child_id
+        WHERE t.trashed_card_id = ?
+        "#,
+    )
+    .bind(card_id)
+    .fetch_all(executor)
+    .await
+}
+
+// ゴミ箱にあるカードの退避した親を (親, 子) で全件取得
+pub async fn fetch_trashed_parent_edges<'e, E>(executor: E) -> Result<Vec<(i64, i64)>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, (i64, i64)>(
+        r#"
+        SELECT t.card_parent_id, t.card_child_id
+        FROM trashed_card_card t
+        JOIN cards c ON c.id = t.card_child_id
+        WHERE c.deleted_at IS NOT NULL
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// 退避した親子関係を card_card へ戻す
+pub async fn restore_trashed_relation(
+    tx: &mut Transaction<'_, MySql>,
+    relation: &TrashedRelation,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO card_card (card_parent_id, card_child_id, connector, version, created_at)
+        SELECT card_parent_id, card_child_id, connector, version + 1, created_at
+        FROM trashed_card_card
+        WHERE card_parent_id = ? AND card_child_id = ?
+        "#,
+    )
+    .bind(relation.card_parent_id)
+    .bind(relation.card_child_id)
+    .execute(&mut **tx)
+    .await?;
+    delete_trashed_relation(&mut **tx, relation).await
+}
+
+// 退避した親子関係を、相手側のカードの復元時に戻すよう付け替える
+pub async fn hand_over_trashed_relation<'e, E>(
+    executor: E,
+    relation: &TrashedRelation,
+    card_id: i64,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        "UPDATE trashed_card_card SET trashed_card_id = ? \
+         WHERE card_parent_id = ? AND card_child_id = ?",
+    )
+    .bind(card_id)
+    .bind(relation.card_parent_id)
+    .bind(relation.card_child_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// 退避した親子関係を捨てる
+pub async fn delete_trashed_relation<'e, E>(
+    executor: E,
+    relation: &TrashedRelation,
+) -> Result<(), sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query("DELETE FROM trashed_card_card WHERE card_parent_id = ? AND card_child_id = ?")
+        .bind(relation.card_parent_id)
+        .bind(relation.card_child_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// ゴミ箱のカードを新しく削除した順に取得
+pub async fn fetch_trashed_cards<'e, E>(executor: E) -> Result<Vec<TrashedCard>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, TrashedCard>(
+        r#"
+        SELECT c.id, COALESCE(c.title, '') AS title, c.visibility, c.card_type, c.deleted_at,
+               (SELECT COUNT(*) FROM trashed_card_tag t WHERE t.card_id = c.id) AS tag_count,
+               (SELECT COUNT(*) FROM trashed_card_card t WHERE t.trashed_card_id = c.id)
+                   AS relation_count
+        FROM cards c
+        WHERE c.deleted_at IS NOT NULL
+        ORDER BY c.deleted_at DESC, c.id DESC
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// retention_days 日より前にゴミ箱へ入ったカードを完全に削除し、件数を返す
+// （リビジョン・スケジュール・退避した行などは外部キーで一緒に消える）
+pub async fn purge_trashed_cards<'e, E>(
+    executor: E,
+    retention_days: i64,
+) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    // deleted_at is set by the database clock, so compare against it too.
+    let res =
+        sqlx::query("DELETE FROM cards WHERE deleted_at < CURRENT_TIMESTAMP - INTERVAL ? DAY")
+            .bind(retention_days)
+            .execute(executor)
+            .await?;
+    Ok(res.rows_affected())
+}
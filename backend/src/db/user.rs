@@ -0,0 +1,133 @@
+use sqlx::{Executor, MySql, Transaction};
+
+use crate::models::{User, UserRow};
+
+const SELECT_USERS: &str =
+    "SELECT id, username, role, is_active, created_at, updated_at FROM users";
+
+// ユーザーを全件取得（ユーザー名順）
+pub async fn fetch_users<'e, E>(executor: E) -> Result<Vec<User>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} ORDER BY username", SELECT_USERS);
+    sqlx::query_as::<_, User>(&sql).fetch_all(executor).await
+}
+
+// ID 指定で１件取得
+pub async fn fetch_user<'e, E>(executor: E, user_id: i64) -> Result<Option<User>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let sql = format!("{} WHERE id = ?", SELECT_USERS);
+    sqlx::query_as::<_, User>(&sql)
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await
+}
+
+// ログイン用に、有効なユーザーをユーザー名で取得
+pub async fn fetch_active_user_by_username<'e, E>(
+    executor: E,
+    username: &str,
+) -> Result<Option<UserRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, UserRow>(
+        "SELECT id, role, password_hash FROM users WHERE username = ? AND is_active = TRUE",
+    )
+    .bind(username)
+    .fetch_optional(executor)
+    .await
+}
+
+// パスワード確認用に、有効なユーザーを ID で取得
+pub async fn fetch_active_user_row<'e, E>(
+    executor: E,
+    user_id: i64,
+) -> Result<Option<UserRow>, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    sqlx::query_as::<_, UserRow>(
+        "SELECT id, role, password_hash FROM users WHERE id = ? AND is_active = TRUE",
+    )
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+// ユーザーを作成して ID を返す
+pub async fn insert_user<'e, E>(
+    executor: E,
+    username: &str,
+    password_hash: &str,
+    role: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("INSERT INTO users (username, password_hash, role) VALUES (?, ?, ?)")
+        .bind(username)
+        .bind(password_hash)
+        .bind(role)
+        .execute(executor)
+        .await?;
+    Ok(res.last_insert_id() as i64)
+}
+
+// ロールと有効フラグを更新（None は変更しない）。存在しなければ false
+pub async fn update_user<'e, E>(
+    executor: E,
+    user_id: i64,
+    role: Option<&str>,
+    is_active: Option<bool>,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query(
+        r#"
+        UPDATE users
+        SET role = COALESCE(?, role), is_active = COALESCE(?, is_active)
+        WHERE id = ?
+        "#,
+    )
+    .bind(role)
+    .bind(is_active)
+    .bind(user_id)
+    .execute(executor)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// パスワードを変更。存在しなければ false
+pub async fn set_user_password<'e, E>(
+    executor: E,
+    user_id: i64,
+    password_hash: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+        .bind(password_hash)
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// user_id 以外の有効な管理者の数（管理者行はトランザクションの終わりまでロック）
+pub async fn count_other_active_admins(
+    tx: &mut Transaction<'_, MySql>,
+    user_id: i64,
+) -> Result<i64, sqlx::Error> {
+    let admins = sqlx::query_scalar::<_, i64>(
+        "SELECT id FROM users WHERE role = 'admin' AND is_active = TRUE FOR UPDATE",
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(admins.into_iter().filter(|id| *id != user_id).count() as i64)
+}
@@ -0,0 +1,110 @@
+//! Line diff for card revisions.
+
+use serde::Serialize;
+
+/// Above this many cells in the LCS table (lines of the changed middle part
+/// of `old` times those of `new`), the middle is reported as one deletion
+/// plus one insertion instead.
+const MAX_TABLE_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A run of lines that are kept, deleted from the old text or inserted into
+/// the new one.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DiffOp {
+    pub op: DiffKind,
+    pub lines: Vec<String>,
+}
+
+/// Line diff turning `old` into `new`, as runs in order. Within a changed
+/// region deletions come before insertions.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffOp> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops = Vec::new();
+    push_lines(&mut ops, DiffKind::Equal, &old[..prefix]);
+    diff_middle(&mut ops, old_middle, new_middle);
+    push_lines(&mut ops, DiffKind::Equal, &old[old.len() - suffix..]);
+    ops
+}
+
+/// Diff of two texts without a common first or last line, through a
+/// longest common subsequence table.
+fn diff_middle(ops: &mut Vec<DiffOp>, old: &[&str], new: &[&str]) {
+    if old.is_empty() || new.is_empty() || old.len() * new.len() > MAX_TABLE_CELLS {
+        push_lines(ops, DiffKind::Delete, old);
+        push_lines(ops, DiffKind::Insert, new);
+        return;
+    }
+
+    // lcs[i][j]: length of the LCS of old[i..] and new[j..].
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            push_lines(ops, DiffKind::Delete, &deleted);
+            push_lines(ops, DiffKind::Insert, &inserted);
+            deleted.clear();
+            inserted.clear();
+            push_lines(ops, DiffKind::Equal, &old[i..i + 1]);
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            deleted.push(old[i]);
+            i += 1;
+        } else {
+            inserted.push(new[j]);
+            j += 1;
+        }
+    }
+    push_lines(ops, DiffKind::Delete, &deleted);
+    push_lines(ops, DiffKind::Insert, &inserted);
+}
+
+/// Append `lines`, merging them into the last run when it has the same kind.
+fn push_lines(ops: &mut Vec<DiffOp>, op: DiffKind, lines: &[&str]) {
+    if lines.is_empty() {
+        return;
+    }
+    let lines = lines.iter().map(|line| line.to_string());
+    match ops.last_mut() {
+        Some(last) if last.op == op => last.lines.extend(lines),
+        _ => ops.push(DiffOp {
+            op,
+            lines: lines.collect(),
+        }),
+    }
+}
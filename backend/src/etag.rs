@@ -0,0 +1,67 @@
+//! Row versions as ETags, and `If-Match` preconditions on writes.
+//!
+//! A card's or relation's `version` goes out as a strong ETag (`"3"`). A
+//! write carrying `If-Match` only applies while the row is still at one of
+//! the listed versions; without the header it applies unconditionally.
+
+use axum::http::{header::IF_MATCH, HeaderMap};
+
+/// ETag header value for a row version.
+pub fn etag(version: i32) -> String {
+    format!("\"{version}\"")
+}
+
+/// A parsed `If-Match` header.
+pub enum IfMatch {
+    /// `*`: any current version.
+    Any,
+    Versions(Vec<i32>),
+}
+
+impl IfMatch {
+    /// `None` when the request has no `If-Match`. Weak tags never match, as
+    /// `If-Match` uses strong comparison; tags that are not versions of ours
+    /// are an error.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, String> {
+        let mut versions = Vec::new();
+        let mut any = false;
+        let mut present = false;
+        for value in headers.get_all(IF_MATCH) {
+            present = true;
+            let value = value
+                .to_str()
+                .map_err(|_| "If-Match must be ASCII".to_string())?;
+            for tag in value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+            {
+                if tag == "*" {
+                    any = true;
+                } else if tag.starts_with("W/") {
+                    continue;
+                } else {
+                    let version = tag
+                        .strip_prefix('"')
+                        .and_then(|tag| tag.strip_suffix('"'))
+                        .and_then(|tag| tag.parse().ok())
+                        .ok_or_else(|| format!("If-Match: invalid entity tag {tag}"))?;
+                    versions.push(version);
+                }
+            }
+        }
+
+        Ok(match (present, any) {
+            (false, _) => None,
+            (true, true) => Some(IfMatch::Any),
+            (true, false) => Some(IfMatch::Versions(versions)),
+        })
+    }
+
+    pub fn matches(&self, version: i32) -> bool {
+        match self {
+            IfMatch::Any => true,
+            IfMatch::Versions(versions) => versions.contains(&version),
+        }
+    }
+}
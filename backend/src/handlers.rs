@@ -1,4 +1,19 @@
+pub mod anki;
+pub mod api_tokens;
+pub mod batch;
+pub mod card_acl;
 pub mod card_card;
 pub mod cards;
+pub mod changes;
 pub mod flash_card;
+pub mod quiz;
+pub mod revisions;
+pub mod saved_queries;
+pub mod stats;
+pub mod study;
+pub mod study_session;
+pub mod switcher;
+pub mod sync;
 pub mod tags;
+pub mod users;
+pub mod trash;
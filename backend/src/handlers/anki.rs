@@ -0,0 +1,520 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySql, Pool, QueryBuilder};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    anki::{build_apkg, read_apkg, AnkiNote, ImportedNote},
+    auth::{AuthState, RequestUser, Scope},
+    db::{
+        fetch_card_row_by_id, find_or_create_tag, index_card, insert_card, insert_card_relation,
+        insert_card_tag, record_card_revision, NewCardRevision,
+    },
+    handlers::{
+        cards::check_visibility,
+        flash_card::{fetch_scoped_flash_cards, FlashCardQuery, FlashCardScope},
+    },
+    markdown::{html_to_text, render_html, split_first_h1},
+    models::{error_response, ApiResponse, Card, CardParams, RevisionSource, CARD_TYPE_FRAME},
+    schema::Dimmension,
+};
+
+/// Grid used for imported cards, inside the frame. Card size matches a card
+/// created by dropping on the canvas.
+const IMPORT_CARD_SIZE: f64 = 200.0;
+const IMPORT_CARD_GAP: f64 = 40.0;
+const IMPORT_FRAME_PADDING: f64 = 40.0;
+/// Room for the frame's own title above the grid.
+const IMPORT_FRAME_HEADER: f64 = 80.0;
+
+/// `cards.title` is VARCHAR(100).
+const MAX_TITLE_CHARS: usize = 100;
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// `apkg` or `csv`; guessed from the body when omitted.
+    format: Option<String>,
+    /// Title of the frame card. Defaults to the Anki deck name.
+    title: Option<String>,
+    /// Position of the frame card.
+    x: Option<f64>,
+    y: Option<f64>,
+    /// Visibility of the frame and every imported card.
+    visibility: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DeckImport {
+    frame: Card,
+    card_ids: Vec<i64>,
+    tag_ids: Vec<i64>,
+}
+
+#[derive(FromRow)]
+struct CardTagName {
+    card_id: i64,
+    name: String,
+}
+
+/// Export the cards of a tags / parent_id scope (same as `/cards/flush_json`)
+/// as an Anki `.apkg`.
+pub async fn export_apkg(
+    State(auth): State<AuthState>,
+    Query(params): Query<FlashCardQuery>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> Response {
+    let scope = match params.scope() {
+        Ok(scope) => scope,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+    };
+    let viewer = auth.card_reader(&headers, Scope::Flashcards).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let cards = match fetch_scoped_flash_cards(&pool, &scope, &access).await {
+        Ok(cards) => cards,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    // One note per card, not per review item.
+    let mut seen = HashSet::new();
+    let cards: Vec<_> = cards
+        .into_iter()
+        .filter(|card| seen.insert(card.id))
+        .collect();
+
+    let card_ids: Vec<i64> = cards.iter().map(|card| card.id).collect();
+    let mut tags = match fetch_tag_names(&pool, &card_ids).await {
+        Ok(tags) => tags,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let deck_name = match deck_name(&pool, &scope, &access).await {
+        Ok(name) => name,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let notes: Vec<AnkiNote> = cards
+        .into_iter()
+        .map(|card| {
+            let (heading, body) = split_first_h1(&card.contents);
+            AnkiNote {
+                card_id: card.id,
+                front: heading.unwrap_or(card.title),
+                back_html: render_html(&body),
+                tags: tags.remove(&card.id).unwrap_or_default(),
+                modified_at: card.updated_at,
+            }
+        })
+        .collect();
+
+    match build_apkg(&deck_name, &notes).await {
+        Ok(bytes) => (
+            StatusCode::OK,
+            [
+                (CONTENT_TYPE, "application/octet-stream".to_string()),
+                (
+                    CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.apkg\"", file_stem(&deck_name)),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// Import an `.apkg` or a CSV (`front,back,tags`) as a frame card with one
+/// child card per note, laid out in a grid. Tags are matched by name and
+/// created when missing. Everything is written in one transaction.
+pub async fn import_deck(
+    Query(params): Query<ImportQuery>,
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+    body: Bytes,
+) -> ApiResponse<DeckImport> {
+    let format = match params.format.as_deref() {
+        Some(format @ ("apkg" | "csv")) => format,
+        Some(_) => {
+            return ApiResponse::new_err(StatusCode::BAD_REQUEST, "format must be apkg or csv")
+        }
+        // .apkg is a zip archive
+        None if body.starts_with(b"PK\x03\x04") => "apkg",
+        None => "csv",
+    };
+
+    let (deck_name, notes) = if format == "apkg" {
+        match read_apkg(&body).await {
+            Ok(deck) => (deck.name, deck.notes),
+            Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+        }
+    } else {
+        match read_csv(&body) {
+            Ok(notes) => (None, notes),
+            Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+        }
+    };
+
+    if notes.is_empty() {
+        return ApiResponse::new_err(StatusCode::BAD_REQUEST, "no notes to import");
+    }
+
+    let title = params
+        .title
+        .clone()
+        .filter(|title| !title.trim().is_empty())
+        .or(deck_name)
+        .unwrap_or_else(|| "Imported deck".to_string());
+    let visibility = params
+        .visibility
+        .clone()
+        .unwrap_or_else(|| "public".to_string());
+    if let Err((status, message)) = check_visibility(&visibility) {
+        return ApiResponse::new_err(status, message);
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let user_id = user.map(|user| user.user.user_id);
+    let result = import_notes(&mut tx, &params, &title, &visibility, &notes, user_id).await;
+    let (frame_id, card_ids, tag_ids) = match result {
+        Ok(ids) => ids,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        }
+    };
+
+    if let Err(e) = tx.commit().await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    match fetch_card_row_by_id(&pool, frame_id).await {
+        Ok(row) => ApiResponse::new_ok(
+            StatusCode::CREATED,
+            DeckImport {
+                frame: Card::from(row),
+                card_ids,
+                tag_ids,
+            },
+        ),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Returns the frame id, the child card ids and the ids of every tag used.
+async fn import_notes(
+    tx: &mut sqlx::Transaction<'_, MySql>,
+    params: &ImportQuery,
+    title: &str,
+    visibility: &str,
+    notes: &[ImportedNote],
+    user_id: Option<i64>,
+) -> Result<(i64, Vec<i64>, Vec<i64>), sqlx::Error> {
+    let mut tag_ids: BTreeMap<&str, i64> = BTreeMap::new();
+    for name in notes.iter().flat_map(|note| &note.tags) {
+        if !tag_ids.contains_key(name.as_str()) {
+            let id = find_or_create_tag(tx, name).await?;
+            tag_ids.insert(name, id);
+        }
+    }
+
+    let columns = (notes.len() as f64).sqrt().ceil() as usize;
+    let rows = notes.len().div_ceil(columns);
+    let span = |count: usize| {
+        count as f64 * IMPORT_CARD_SIZE + (count.saturating_sub(1)) as f64 * IMPORT_CARD_GAP
+    };
+
+    let frame = CardParams {
+        id: 0,
+        position: Dimmension {
+            x: params.x.unwrap_or(0.0),
+            y: params.y.unwrap_or(0.0),
+        },
+        size: Dimmension {
+            x: span(columns) + IMPORT_FRAME_PADDING * 2.0,
+            y: span(rows) + IMPORT_FRAME_HEADER + IMPORT_FRAME_PADDING,
+        },
+        title: truncate_title(title),
+        contents: format!("# {title}"),
+        parent_id: None,
+        tag_ids: Vec::new(),
+        visibility: visibility.to_string(),
+        card_type: CARD_TYPE_FRAME.to_string(),
+        saved_query_id: None,
+    };
+    let frame_id = insert_card(&mut **tx, &frame, user_id).await?;
+    index_card(tx, frame_id, &frame.title, &frame.contents).await?;
+    record_import_revision(tx, frame_id, &frame, user_id).await?;
+
+    let mut card_ids = Vec::with_capacity(notes.len());
+    for (index, note) in notes.iter().enumerate() {
+        let (title, contents) = note_contents(note);
+        // Child positions are relative to the parent card.
+        let card = CardParams {
+            id: 0,
+            position: Dimmension {
+                x: IMPORT_FRAME_PADDING
+                    + (index % columns) as f64 * (IMPORT_CARD_SIZE + IMPORT_CARD_GAP),
+                y: IMPORT_FRAME_HEADER
+                    + (index / columns) as f64 * (IMPORT_CARD_SIZE + IMPORT_CARD_GAP),
+            },
+            size: Dimmension {
+                x: IMPORT_CARD_SIZE,
+                y: IMPORT_CARD_SIZE,
+            },
+            title,
+            contents,
+            parent_id: Some(frame_id),
+            tag_ids: Vec::new(),
+            visibility: visibility.to_string(),
+            card_type: "normal".to_string(),
+            saved_query_id: None,
+        };
+        let card_id = insert_card(&mut **tx, &card, user_id).await?;
+        index_card(tx, card_id, &card.title, &card.contents).await?;
+        record_import_revision(tx, card_id, &card, user_id).await?;
+
+        // Same connector the frontend stores when a card is dropped into a frame.
+        insert_card_relation(&mut **tx, frame_id, card_id, "null").await?;
+
+        let mut seen = Vec::new();
+        for name in &note.tags {
+            let tag_id = tag_ids[name.as_str()];
+            if !seen.contains(&tag_id) {
+                insert_card_tag(&mut **tx, card_id, tag_id).await?;
+                seen.push(tag_id);
+            }
+        }
+
+        card_ids.push(card_id);
+    }
+
+    let mut tag_ids: Vec<i64> = tag_ids.into_values().collect();
+    tag_ids.sort_unstable();
+    tag_ids.dedup();
+
+    Ok((frame_id, card_ids, tag_ids))
+}
+
+async fn record_import_revision(
+    tx: &mut sqlx::Transaction<'_, MySql>,
+    card_id: i64,
+    card: &CardParams,
+    user_id: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    let revision = NewCardRevision {
+        card_id,
+        title: &card.title,
+        contents: &card.contents,
+        user_id,
+        source: RevisionSource::Import,
+        restored_from: None,
+    };
+    record_card_revision(tx, &revision).await.map(|_| ())
+}
+
+/// Card markdown for a note: the front as the H1, the back as the body.
+fn note_contents(note: &ImportedNote) -> (String, String) {
+    let heading = note.front.split_whitespace().collect::<Vec<_>>().join(" ");
+    let contents = if heading.is_empty() {
+        note.back.clone()
+    } else {
+        format!("# {heading}\n\n{}", note.back)
+            .trim_end()
+            .to_string()
+    };
+    (truncate_title(&heading), contents)
+}
+
+fn truncate_title(title: &str) -> String {
+    title.chars().take(MAX_TITLE_CHARS).collect()
+}
+
+/// Parse `front,back,tags` rows. Tags are separated by spaces or commas.
+/// Tab-separated files and the `#separator:` / `#html:` header lines of
+/// Anki's "Notes in Plain Text" export are understood too; a leading
+/// `front,back` header row is skipped.
+fn read_csv(bytes: &[u8]) -> Result<Vec<ImportedNote>, String> {
+    let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+    let mut text = text.trim_start_matches('\u{feff}');
+
+    let mut delimiter = None;
+    let mut html = false;
+    while let Some((key, value)) = text
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix('#'))
+        .and_then(|line| line.split_once(':'))
+    {
+        match key.trim() {
+            "separator" => {
+                delimiter = match value.trim() {
+                    "tab" | "Tab" => Some(b'\t'),
+                    "comma" | "Comma" => Some(b','),
+                    "semicolon" | "Semicolon" => Some(b';'),
+                    "pipe" | "Pipe" => Some(b'|'),
+                    "space" | "Space" => Some(b' '),
+                    other if other.len() == 1 => Some(other.as_bytes()[0]),
+                    _ => None,
+                }
+            }
+            "html" => html = value.trim() == "true",
+            "tags column" | "notetype" | "deck" | "guid column" | "notetype column"
+            | "deck column" | "columns" | "tags" => {}
+            _ => break,
+        }
+        text = text.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+
+    let delimiter = delimiter.unwrap_or_else(|| {
+        if text.lines().next().is_some_and(|line| line.contains('\t')) {
+            b'\t'
+        } else {
+            b','
+        }
+    });
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+
+    let field = |value: Option<&str>| {
+        let value = value.unwrap_or_default();
+        if html {
+            html_to_text(value)
+        } else {
+            value.trim().to_string()
+        }
+    };
+
+    let mut notes = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        let front = field(record.get(0));
+        let back = field(record.get(1));
+
+        if index == 0 && front.eq_ignore_ascii_case("front") && back.eq_ignore_ascii_case("back") {
+            continue;
+        }
+        if front.is_empty() && back.is_empty() {
+            continue;
+        }
+
+        let tags = record
+            .get(2)
+            .unwrap_or_default()
+            .split(|ch: char| ch.is_whitespace() || ch == ',')
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+        notes.push(ImportedNote { front, back, tags });
+    }
+
+    Ok(notes)
+}
+
+/// Tag names per card, with whitespace replaced since Anki tags are
+/// space-separated.
+async fn fetch_tag_names(
+    pool: &Pool<MySql>,
+    card_ids: &[i64],
+) -> Result<HashMap<i64, Vec<String>>, sqlx::Error> {
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    if card_ids.is_empty() {
+        return Ok(tags);
+    }
+
+    let mut query = QueryBuilder::<MySql>::new(
+        "SELECT ct.card_id, t.name FROM card_tag ct \
+         INNER JOIN tags t ON t.id = ct.tag_id WHERE ct.card_id IN (",
+    );
+    let mut ids = query.separated(", ");
+    for id in card_ids {
+        ids.push_bind(id);
+    }
+    query.push(") ORDER BY t.name");
+
+    for row in query
+        .build_query_as::<CardTagName>()
+        .fetch_all(pool)
+        .await?
+    {
+        let name = row.name.split_whitespace().collect::<Vec<_>>().join("_");
+        if !name.is_empty() {
+            tags.entry(row.card_id).or_default().push(name);
+        }
+    }
+
+    Ok(tags)
+}
+
+/// Deck named after the export scope: the tags, the parent card's title, or
+/// both. A parent the viewer may not read is named by its id only.
+async fn deck_name(
+    pool: &Pool<MySql>,
+    scope: &FlashCardScope,
+    access: &CardAccess,
+) -> Result<String, sqlx::Error> {
+    let parent_title = match scope.parent_id {
+        Some(parent_id) if !access.can_read(parent_id) => Some(format!("card {parent_id}")),
+        Some(parent_id) => {
+            sqlx::query_scalar::<_, Option<String>>("SELECT title FROM cards WHERE id = ?")
+                .bind(parent_id)
+                .fetch_optional(pool)
+                .await?
+                .flatten()
+                .filter(|title| !title.trim().is_empty())
+                .or_else(|| Some(format!("card {parent_id}")))
+        }
+        None => None,
+    };
+
+    let tags = Some(scope.tags.include.join(" + ")).filter(|tags| !tags.is_empty());
+
+    Ok(match (tags, parent_title) {
+        (Some(tags), Some(title)) => format!("{title}::{tags}"),
+        (Some(tags), None) => tags,
+        (None, Some(title)) => title,
+        (None, None) => "mdmap".to_string(),
+    })
+}
+
+fn file_stem(deck_name: &str) -> String {
+    let stem: String = deck_name
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.trim_matches('_').is_empty() {
+        "deck".to_string()
+    } else {
+        stem
+    }
+}
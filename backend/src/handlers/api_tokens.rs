@@ -0,0 +1,112 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    auth::{generate_api_token, AuthState, SCOPES},
+    db::{delete_api_token, fetch_api_token, fetch_api_tokens, insert_api_token, NewApiToken},
+    models::{ApiResponse, ApiToken, ApiTokenParams, CreatedApiToken},
+};
+
+/// Longest name the `api_tokens.name` column holds.
+const MAX_TOKEN_NAME_CHARS: usize = 100;
+
+/// The logged-in user's API tokens, newest first. Tokens cannot be used to
+/// manage tokens; these handlers need a session.
+pub async fn get_api_tokens(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+) -> ApiResponse<Vec<ApiToken>> {
+    let Some(user) = auth.current_user(&headers).await else {
+        return ApiResponse::new_err(StatusCode::UNAUTHORIZED, "login required");
+    };
+
+    match fetch_api_tokens(&pool, user.user_id).await {
+        Ok(rows) => ApiResponse::new_ok(StatusCode::OK, rows.into_iter().map(Into::into).collect()),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Issue a token for the logged-in user. The token is in the response only;
+/// just its prefix and a hash are stored. What it may do is limited by both
+/// its scopes and the user's role.
+pub async fn create_api_token(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Json(params): Json<ApiTokenParams>,
+) -> ApiResponse<CreatedApiToken> {
+    let Some(user) = auth.current_user(&headers).await else {
+        return ApiResponse::new_err(StatusCode::UNAUTHORIZED, "login required");
+    };
+
+    let name = params.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_CHARS {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("name must be 1 to {MAX_TOKEN_NAME_CHARS} characters"),
+        );
+    }
+    if params.scopes.is_empty() || params.scopes.iter().any(|s| !SCOPES.contains(&s.as_str())) {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("scopes must be one or more of {}", SCOPES.join(", ")),
+        );
+    }
+    if params.expires_in_days.is_some_and(|days| days <= 0) {
+        return ApiResponse::new_err(StatusCode::BAD_REQUEST, "expires_in_days must be positive");
+    }
+
+    let (token, prefix) = generate_api_token();
+    let hash = match auth.hash_password(&token) {
+        Ok(hash) => hash,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    let new_token = NewApiToken {
+        user_id: user.user_id,
+        name,
+        token_prefix: &prefix,
+        token_hash: &hash,
+        scopes: &params.scopes.join(","),
+        expires_in_days: params.expires_in_days,
+    };
+
+    let result = async {
+        let token_id = insert_api_token(&pool, &new_token).await?;
+        fetch_api_token(&pool, user.user_id, token_id).await
+    }
+    .await;
+    match result {
+        Ok(Some(row)) => ApiResponse::new_ok(
+            StatusCode::CREATED,
+            CreatedApiToken {
+                token,
+                api_token: row.into(),
+            },
+        ),
+        Ok(None) => ApiResponse::new_err(StatusCode::NOT_FOUND, "api token not found"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Revoke one of the logged-in user's tokens. It stops working at once.
+pub async fn revoke_api_token(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Path(token_id): Path<i64>,
+) -> ApiResponse<()> {
+    let Some(user) = auth.current_user(&headers).await else {
+        return ApiResponse::new_err(StatusCode::UNAUTHORIZED, "login required");
+    };
+
+    match delete_api_token(&pool, user.user_id, token_id).await {
+        Ok(true) => ApiResponse::new_ok(StatusCode::OK, ()),
+        Ok(false) => ApiResponse::new_err(StatusCode::NOT_FOUND, "api token not found"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
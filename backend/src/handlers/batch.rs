@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool, Transaction};
+
+use crate::{
+    acl::CardAccess,
+    auth::RequestUser,
+    db::{
+        fetch_card_relation, fetch_card_row_by_id, index_card, insert_card, insert_card_relation,
+        lock_card_version, move_card_row, record_card_revision, set_card_tags, trash_card,
+        update_card_row, NewCardRevision,
+    },
+    handlers::{
+        card_card::has_cycle,
+        cards::{check_smart_frame, check_visibility},
+        revisions::revision_author,
+    },
+    models::{ApiResponse, Card, CardParams, CardRelation, RevisionSource, CARD_TYPE_SMART_FRAME},
+    schema::Dimmension,
+};
+
+/// Most operations accepted in one batch.
+const MAX_BATCH_OPS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    ops: Vec<BatchOp>,
+}
+
+/// A card given by its id, or by the `temp_id` of a `create` earlier in the
+/// same batch.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum CardRef {
+    Id(i64),
+    Temp(String),
+}
+
+/// One operation of a batch. `version`, when given, must be the card's
+/// current version, as with `If-Match` on `/card`.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOp {
+    Create {
+        temp_id: Option<String>,
+        card: CardParams,
+    },
+    /// Same as `PATCH /card`. `card.id` is ignored; `id` names the card.
+    Update {
+        id: CardRef,
+        version: Option<i32>,
+        card: CardParams,
+    },
+    /// New position, and size when given. Title, contents and tags are left
+    /// alone.
+    Move {
+        id: CardRef,
+        version: Option<i32>,
+        position: Dimmension,
+        size: Option<Dimmension>,
+    },
+    /// Moves the card to the trash, as `DELETE /card`.
+    Delete { id: CardRef, version: Option<i32> },
+    Connect {
+        parent_id: CardRef,
+        child_id: CardRef,
+        connector: String,
+    },
+}
+
+/// Result of one operation, in the order they were sent.
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOpResult {
+    Create { temp_id: Option<String>, card: Card },
+    Update { card: Card },
+    Move { card: Card },
+    Delete { id: i64 },
+    Connect { relation: CardRelation },
+}
+
+#[derive(Serialize)]
+pub struct BatchResult {
+    results: Vec<BatchOpResult>,
+    /// Ids of the cards created for each `temp_id`.
+    temp_ids: HashMap<String, i64>,
+}
+
+/// Sent when the batch was rolled back: the operation that failed, and the
+/// server's copy of its card when a `version` was stale.
+#[derive(Serialize)]
+pub struct BatchFailure {
+    index: usize,
+    current: Option<Card>,
+}
+
+struct OpError {
+    status: StatusCode,
+    message: String,
+    current: Option<Box<Card>>,
+}
+
+impl OpError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            current: None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for OpError {
+    fn from(e: sqlx::Error) -> Self {
+        OpError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+/// State shared by the operations of one batch.
+struct Batch<'c> {
+    tx: Transaction<'c, MySql>,
+    access: CardAccess,
+    temp_ids: HashMap<String, i64>,
+    user_id: Option<i64>,
+    source: RevisionSource,
+}
+
+/// Apply create / update / move / delete / connect operations in one
+/// transaction. Either every operation applies or none does; on failure the
+/// response names the operation (`data.index`) and carries its status.
+pub async fn apply_batch(
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+    Json(request): Json<BatchRequest>,
+) -> Response {
+    if request.ops.is_empty() || request.ops.len() > MAX_BATCH_OPS {
+        return ApiResponse::<()>::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("a batch takes 1 to {MAX_BATCH_OPS} operations"),
+        )
+        .into_response();
+    }
+
+    let access = match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => access,
+        Err(e) => {
+            return ApiResponse::<()>::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                .into_response()
+        }
+    };
+    let tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            return ApiResponse::<()>::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                .into_response()
+        }
+    };
+    let (user_id, source) = revision_author(user.as_deref());
+    let mut batch = Batch {
+        tx,
+        access,
+        temp_ids: HashMap::new(),
+        user_id,
+        source,
+    };
+
+    let mut results = Vec::with_capacity(request.ops.len());
+    for (index, op) in request.ops.into_iter().enumerate() {
+        match batch.apply(op).await {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                let _ = batch.tx.rollback().await;
+                return ApiResponse::new_err_with(
+                    e.status,
+                    format!("operation {index}: {}", e.message),
+                    BatchFailure {
+                        index,
+                        current: e.current.map(|card| *card),
+                    },
+                )
+                .into_response();
+            }
+        }
+    }
+
+    if let Err(e) = batch.tx.commit().await {
+        return ApiResponse::<()>::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            .into_response();
+    }
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        BatchResult {
+            results,
+            temp_ids: batch.temp_ids,
+        },
+    )
+    .into_response()
+}
+
+impl Batch<'_> {
+    async fn apply(&mut self, op: BatchOp) -> Result<BatchOpResult, OpError> {
+        match op {
+            BatchOp::Create { temp_id, card } => self.create(temp_id, card).await,
+            BatchOp::Update { id, version, card } => {
+                let id = self.resolve(&id)?;
+                self.update(id, version, card).await
+            }
+            BatchOp::Move {
+                id,
+                version,
+                position,
+                size,
+            } => {
+                let id = self.resolve(&id)?;
+                self.move_card(id, version, position, size).await
+            }
+            BatchOp::Delete { id, version } => {
+                let id = self.resolve(&id)?;
+                self.lock(id, version).await?;
+                trash_card(&mut self.tx, id).await?;
+                Ok(BatchOpResult::Delete { id })
+            }
+            BatchOp::Connect {
+                parent_id,
+                child_id,
+                connector,
+            } => {
+                let parent_id = self.resolve(&parent_id)?;
+                let child_id = self.resolve(&child_id)?;
+                self.connect(parent_id, child_id, &connector).await
+            }
+        }
+    }
+
+    fn resolve(&self, card: &CardRef) -> Result<i64, OpError> {
+        match card {
+            CardRef::Id(id) => Ok(*id),
+            CardRef::Temp(temp_id) => self.temp_ids.get(temp_id).copied().ok_or_else(|| {
+                OpError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("unknown temp_id {temp_id:?}"),
+                )
+            }),
+        }
+    }
+
+    async fn create(
+        &mut self,
+        temp_id: Option<String>,
+        card: CardParams,
+    ) -> Result<BatchOpResult, OpError> {
+        if let Some(temp_id) = &temp_id {
+            if self.temp_ids.contains_key(temp_id) {
+                return Err(OpError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("temp_id {temp_id:?} is used twice"),
+                ));
+            }
+        }
+        check_visibility(&card.visibility)
+            .map_err(|(status, message)| OpError::new(status, message))?;
+        self.check_smart_frame(&card).await?;
+
+        let card_id = insert_card(&mut *self.tx, &card, self.user_id).await?;
+        self.access.add_created(card_id);
+        set_card_tags(&mut self.tx, card_id, &card.tag_ids).await?;
+        index_card(&mut self.tx, card_id, &card.title, &card.contents).await?;
+        self.record_revision(card_id, &card).await?;
+
+        if let Some(temp_id) = &temp_id {
+            self.temp_ids.insert(temp_id.clone(), card_id);
+        }
+        let card = self.fetch_card(card_id).await?;
+        Ok(BatchOpResult::Create { temp_id, card })
+    }
+
+    async fn update(
+        &mut self,
+        card_id: i64,
+        version: Option<i32>,
+        mut card: CardParams,
+    ) -> Result<BatchOpResult, OpError> {
+        card.id = card_id;
+        check_visibility(&card.visibility)
+            .map_err(|(status, message)| OpError::new(status, message))?;
+        self.check_smart_frame(&card).await?;
+        self.lock(card_id, version).await?;
+
+        update_card_row(&mut *self.tx, &card).await?;
+        index_card(&mut self.tx, card_id, &card.title, &card.contents).await?;
+        self.record_revision(card_id, &card).await?;
+        set_card_tags(&mut self.tx, card_id, &card.tag_ids).await?;
+
+        let card = self.fetch_card(card_id).await?;
+        Ok(BatchOpResult::Update { card })
+    }
+
+    async fn move_card(
+        &mut self,
+        card_id: i64,
+        version: Option<i32>,
+        position: Dimmension,
+        size: Option<Dimmension>,
+    ) -> Result<BatchOpResult, OpError> {
+        self.lock(card_id, version).await?;
+        let size = match size {
+            Some(size) => size,
+            None => self.fetch_card(card_id).await?.size,
+        };
+
+        move_card_row(&mut *self.tx, card_id, &position, &size).await?;
+
+        let card = self.fetch_card(card_id).await?;
+        Ok(BatchOpResult::Move { card })
+    }
+
+    async fn connect(
+        &mut self,
+        parent_id: i64,
+        child_id: i64,
+        connector: &str,
+    ) -> Result<BatchOpResult, OpError> {
+        if parent_id == child_id {
+            return Err(OpError::new(
+                StatusCode::BAD_REQUEST,
+                "a card cannot be its own parent",
+            ));
+        }
+        self.lock(parent_id, None).await?;
+        self.lock(child_id, None).await?;
+        // Children of a smart frame come from its saved query.
+        if self.fetch_card(parent_id).await?.card_type == CARD_TYPE_SMART_FRAME {
+            return Err(OpError::new(
+                StatusCode::BAD_REQUEST,
+                "smart frame children come from its saved query",
+            ));
+        }
+        if has_cycle(&mut *self.tx, parent_id, child_id).await? {
+            return Err(OpError::new(
+                StatusCode::BAD_REQUEST,
+                "connecting would create a cycle",
+            ));
+        }
+
+        match insert_card_relation(&mut *self.tx, parent_id, child_id, connector).await {
+            Ok(()) => {}
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                return Err(OpError::new(
+                    StatusCode::CONFLICT,
+                    format!("card {parent_id} is already connected to card {child_id}"),
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let relation = fetch_card_relation(&mut *self.tx, parent_id, child_id)
+            .await?
+            .ok_or_else(|| OpError::new(StatusCode::NOT_FOUND, "relation not found"))?;
+        Ok(BatchOpResult::Connect { relation })
+    }
+
+    /// Check the user may write a live card, lock it for the rest of the
+    /// batch and check `version`.
+    async fn lock(&mut self, card_id: i64, version: Option<i32>) -> Result<(), OpError> {
+        self.access
+            .check_write(&[card_id])
+            .map_err(|(status, message)| OpError::new(status, message))?;
+        let current = lock_card_version(&mut *self.tx, card_id)
+            .await?
+            .ok_or_else(|| {
+                OpError::new(StatusCode::NOT_FOUND, format!("card {card_id} not found"))
+            })?;
+        if version.is_none_or(|version| version == current) {
+            return Ok(());
+        }
+
+        let card = self.fetch_card(card_id).await?;
+        Err(OpError {
+            status: StatusCode::PRECONDITION_FAILED,
+            message: format!("card {card_id} was changed by another client"),
+            current: Some(Box::new(card)),
+        })
+    }
+
+    async fn check_smart_frame(&mut self, card: &CardParams) -> Result<(), OpError> {
+        check_smart_frame(&mut *self.tx, card)
+            .await
+            .map_err(|(status, message)| OpError::new(status, message))
+    }
+
+    async fn record_revision(&mut self, card_id: i64, card: &CardParams) -> Result<(), OpError> {
+        let revision = NewCardRevision {
+            card_id,
+            title: &card.title,
+            contents: &card.contents,
+            user_id: self.user_id,
+            source: self.source,
+            restored_from: None,
+        };
+        record_card_revision(&mut self.tx, &revision).await?;
+        Ok(())
+    }
+
+    async fn fetch_card(&mut self, card_id: i64) -> Result<Card, OpError> {
+        Ok(Card::from(
+            fetch_card_row_by_id(&mut *self.tx, card_id).await?,
+        ))
+    }
+}
@@ -0,0 +1,121 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    acl::{AccessCache, CardAccess, Permission},
+    auth::{AuthState, RequestUser, ROLES},
+    db::{fetch_card_acl, fetch_card_row_by_id, replace_card_acl},
+    models::{ApiResponse, CardAcl, CardAclEntry, CardAclParams, CARD_TYPE_FRAME},
+};
+
+fn validate_entry(entry: &CardAclEntry) -> Result<(), String> {
+    match (&entry.user_id, &entry.role) {
+        (Some(_), None) => {}
+        (None, Some(role)) if ROLES.contains(&role.as_str()) => {}
+        (None, Some(_)) => return Err(format!("role must be one of {}", ROLES.join(", "))),
+        _ => return Err("each entry needs exactly one of user_id and role".to_string()),
+    }
+    match Permission::parse(&entry.permission) {
+        Some(_) => Ok(()),
+        None => Err("permission must be read or write".to_string()),
+    }
+}
+
+/// A card's owner and ACL, for logged-in users who may read the card.
+pub async fn get_card_acl(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+    Path(card_id): Path<i64>,
+) -> ApiResponse<CardAcl> {
+    let Some(user) = auth.current_user(&headers).await else {
+        return ApiResponse::new_err(StatusCode::UNAUTHORIZED, "login required");
+    };
+    match CardAccess::load(&access_cache, Some(user)).await {
+        Ok(access) => {
+            if let Err((status, message)) = access.check_read(card_id) {
+                return ApiResponse::new_err(status, message);
+            }
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    let card = match fetch_card_row_by_id(&pool, card_id).await {
+        Ok(card) => card,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::new_err(StatusCode::NOT_FOUND, "card not found")
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    match fetch_card_acl(&pool, card_id).await {
+        Ok(entries) => ApiResponse::new_ok(
+            StatusCode::OK,
+            CardAcl {
+                card_id,
+                owner_id: card.owner_id,
+                entries,
+            },
+        ),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Replace a frame's ACL. Only the frame's owner and admins may. An empty
+/// list removes the ACL.
+pub async fn set_card_acl(
+    Extension(pool): Extension<Pool<MySql>>,
+    Path(card_id): Path<i64>,
+    user: Option<Extension<RequestUser>>,
+    Json(params): Json<CardAclParams>,
+) -> ApiResponse<CardAcl> {
+    if let Some(message) = params.entries.iter().find_map(|e| validate_entry(e).err()) {
+        return ApiResponse::new_err(StatusCode::BAD_REQUEST, message);
+    }
+
+    let access = match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let card = match fetch_card_row_by_id(&pool, card_id).await {
+        Ok(card) if access.can_read(card.id) => card,
+        Ok(_) | Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::new_err(StatusCode::NOT_FOUND, "card not found")
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if card.card_type != CARD_TYPE_FRAME {
+        return ApiResponse::new_err(StatusCode::BAD_REQUEST, "only frames have an ACL");
+    }
+    if !access.owns(card_id) {
+        return ApiResponse::new_err(
+            StatusCode::FORBIDDEN,
+            "only the frame's owner may change its ACL",
+        );
+    }
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        replace_card_acl(&mut tx, card_id, &params.entries).await?;
+        tx.commit().await
+    }
+    .await;
+    match result {
+        Ok(()) => ApiResponse::new_ok(
+            StatusCode::OK,
+            CardAcl {
+                card_id,
+                owner_id: card.owner_id,
+                entries: params.entries,
+            },
+        ),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+            ApiResponse::new_err(StatusCode::BAD_REQUEST, "unknown user_id in ACL")
+        }
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
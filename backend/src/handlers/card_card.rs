@@ -1,5 +1,8 @@
-use crate::auth::AuthState;
-use crate::models::{ApiResponse, CardCardParams, CardRelation};
+use crate::acl::{AccessCache, CardAccess};
+use crate::auth::{AuthState, RequestUser, Scope};
+use crate::db::{fetch_card_relation, lock_card_relation};
+use crate::etag::IfMatch;
+use crate::models::{ApiResponse, CardCardParams, CardRelation, CARD_TYPE_SMART_FRAME};
 use axum::{
     extract::State,
     http::{HeaderMap, StatusCode},
@@ -7,19 +10,24 @@ use axum::{
     Extension, Json,
 };
 use serde_json::json;
-use sqlx::{MySql, Pool};
-use std::collections::HashSet;
+use sqlx::{Executor, MySql, Pool};
 
 pub async fn get_connectors(
     State(auth): State<AuthState>,
     headers: HeaderMap,
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
 ) -> ApiResponse<Vec<CardRelation>> {
-    let authed = auth.is_authenticated(&headers);
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
 
     let rows = sqlx::query_as::<_, CardRelation>(
         r#"
-      select c.card_parent_id, c.card_child_id, c.connector, c.created_at, c.updated_at
+      select c.card_parent_id, c.card_child_id, c.connector, c.version, c.created_at,
+          c.updated_at
       from card_card c
     "#,
     )
@@ -34,31 +42,16 @@ pub async fn get_connectors(
         }
     };
 
-    // For unauthenticated viewers, drop any connector that references a private
-    // card so private cards can't be inferred from the relation graph.
-    let private_ids: HashSet<i64> = if authed {
-        HashSet::new()
-    } else {
-        match sqlx::query_scalar::<_, i64>("SELECT id FROM cards WHERE visibility = 'private'")
-            .fetch_all(&pool)
-            .await
-        {
-            Ok(ids) => ids.into_iter().collect(),
-            Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-        }
-    };
-
+    // Drop any connector that references a card the viewer may not read, so
+    // hidden cards can't be inferred from the relation graph.
     let connectors: Vec<CardRelation> = rows
         .into_iter()
-        .filter(|r| {
-            authed
-                || (!private_ids.contains(&r.card_parent_id)
-                    && !private_ids.contains(&r.card_child_id))
-        })
+        .filter(|r| access.can_read(r.card_parent_id) && access.can_read(r.card_child_id))
         .map(|r| CardRelation {
             card_parent_id: r.card_parent_id,
             card_child_id: r.card_child_id,
             connector: r.connector,
+            version: r.version,
             created_at: r.created_at,
             updated_at: r.updated_at,
         })
@@ -69,10 +62,22 @@ pub async fn get_connectors(
     ApiResponse::new_ok(StatusCode::OK, connectors)
 }
 
+/// With `If-Match`, only applies while the relation is still at that
+/// version.
 pub async fn update_connector(
+    headers: HeaderMap,
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    user: Option<Extension<RequestUser>>,
     Json(params): Json<CardCardParams>,
 ) -> ApiResponse<CardRelation> {
+    let if_match = match IfMatch::from_headers(&headers) {
+        Ok(if_match) => if_match,
+        Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+    };
+    if let Err((status, message)) = check_relation_write(&pool, user.as_deref(), &params).await {
+        return ApiResponse::new_err(status, message);
+    }
+
     if let Ok(true) = has_cycle(&pool, params.card_parent_id, params.card_child_id).await {
         return ApiResponse::new_err(
             StatusCode::BAD_REQUEST,
@@ -80,44 +85,102 @@ pub async fn update_connector(
         );
     }
 
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    // Locked until commit, so the version checked is the one updated.
+    let current = lock_card_relation(&mut *tx, params.card_parent_id, params.card_child_id).await;
+    let current = match current {
+        Ok(Some(current)) => current,
+        Ok(None) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "relation not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if let Some(if_match) = &if_match {
+        if !if_match.matches(current.version) {
+            let _ = tx.rollback().await;
+            let version = current.version;
+            return ApiResponse::new_err_with(
+                StatusCode::PRECONDITION_FAILED,
+                "relation was changed by another client",
+                current,
+            )
+            .with_etag(version);
+        }
+    }
+
     let result = sqlx::query(
         r#"
             UPDATE card_card
-            SET connector = ?
+            SET connector = ?, version = version + 1
             WHERE card_parent_id = ? AND card_child_id = ?
         "#,
     )
     .bind(&params.connector)
     .bind(&params.card_parent_id)
     .bind(&params.card_child_id)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await;
 
-    match result {
-        Ok(result) => result,
-        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-    };
-
-    let record = sqlx::query_as::<_, CardRelation>(
-        "SELECT card_parent_id, card_child_id, connector FROM card_card WHERE card_parent_id = ? AND card_child_id = ?",
-    )
-    .bind(&params.card_parent_id)
-    .bind(&params.card_child_id)
-    .fetch_one(&pool)
-    .await;
+    if let Err(e) = result {
+        let _ = tx.rollback().await;
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
 
+    let record = fetch_card_relation(&mut *tx, params.card_parent_id, params.card_child_id).await;
     let record = match record {
-        Ok(record) => record,
+        Ok(Some(record)) => record,
+        Ok(None) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "relation not found"),
         Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     };
+    if let Err(e) = tx.commit().await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
 
-    ApiResponse::new_ok(StatusCode::CREATED, record)
+    let version = record.version;
+    ApiResponse::new_ok(StatusCode::CREATED, record).with_etag(version)
 }
 
 pub async fn connect_card_to_card(
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    user: Option<Extension<RequestUser>>,
     Json(params): Json<CardCardParams>,
 ) -> ApiResponse<CardRelation> {
+    if let Err((status, message)) = check_relation_write(&pool, user.as_deref(), &params).await {
+        return ApiResponse::new_err(status, message);
+    }
+
+    // Cards in the trash stay out of the graph until they are restored.
+    let live_cards = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM cards WHERE id IN (?, ?) AND deleted_at IS NULL",
+    )
+    .bind(params.card_parent_id)
+    .bind(params.card_child_id)
+    .fetch_one(&pool)
+    .await;
+    match live_cards {
+        Ok(2) => {}
+        Ok(_) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "card not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    // Children of a smart frame come from its saved query.
+    let parent_type = sqlx::query_scalar::<_, String>("SELECT card_type FROM cards WHERE id = ?")
+        .bind(params.card_parent_id)
+        .fetch_optional(&pool)
+        .await;
+    match parent_type {
+        Ok(Some(card_type)) if card_type == CARD_TYPE_SMART_FRAME => {
+            return ApiResponse::new_err(
+                StatusCode::BAD_REQUEST,
+                "Cannot connect: smart frame children come from its saved query",
+            );
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        _ => {}
+    }
+
     match has_cycle(&pool, params.card_parent_id, params.card_child_id).await {
         Ok(true) => {
             return ApiResponse::new_err(
@@ -157,16 +220,11 @@ pub async fn connect_card_to_card(
 
     println!("{:?}", last_id);
 
-    let record = sqlx::query_as::<_, CardRelation>(
-        "SELECT card_parent_id, card_child_id, connector, created_at, updated_at FROM card_card WHERE card_parent_id = ? AND card_child_id = ?",
-    )
-    .bind(&params.card_parent_id)
-    .bind(&params.card_child_id)
-    .fetch_one(&pool)
-    .await;
+    let record = fetch_card_relation(&pool, params.card_parent_id, params.card_child_id).await;
 
     let record = match record {
-        Ok(record) => record,
+        Ok(Some(record)) => record,
+        Ok(None) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "relation not found"),
         Err(e) => {
             println!("{:?}", e);
             return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
@@ -178,8 +236,16 @@ pub async fn connect_card_to_card(
 
 pub async fn disconnect_card_to_card(
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    user: Option<Extension<RequestUser>>,
     Json(params): Json<CardCardParams>,
 ) -> impl IntoResponse {
+    if let Err((status, message)) = check_relation_write(&pool, user.as_deref(), &params).await {
+        return (
+            status,
+            Json(json!({"code": status.to_string(), "message": message})),
+        );
+    }
+
     let result = sqlx::query(
         r#"
             DELETE FROM card_card
@@ -205,11 +271,26 @@ pub async fn disconnect_card_to_card(
     }
 }
 
-pub async fn has_cycle(
+/// Relations are changed by those who may write both of their cards.
+async fn check_relation_write(
     pool: &Pool<MySql>,
+    user: Option<&RequestUser>,
+    params: &CardCardParams,
+) -> Result<(), (StatusCode, String)> {
+    CardAccess::for_request(pool, user)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .check_write(&[params.card_parent_id, params.card_child_id])
+}
+
+pub async fn has_cycle<'e, E>(
+    executor: E,
     parent_id: i64,
     child_id: i64,
-) -> Result<bool, sqlx::Error> {
+) -> Result<bool, sqlx::Error>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let sql = r#"
         WITH RECURSIVE descendants AS (
           -- stage1: child_id の直下の子
@@ -232,7 +313,7 @@ pub async fn has_cycle(
     let found: Option<i64> = sqlx::query_scalar(sql)
         .bind(child_id)
         .bind(parent_id)
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
     Ok(found.is_some())
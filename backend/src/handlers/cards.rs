@@ -1,39 +1,83 @@
 use crate::{
-    auth::AuthState,
-    db::{fetch_all_card_rows, fetch_card_row_by_id, fetch_card_rows_in_range},
-    models::{ApiResponse, Card, CardParams},
-    schema::RangeParams,
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, RequestUser, Scope},
+    card_query::CardQuery,
+    db::{
+        create_poly, fetch_all_card_rows, fetch_card_row_by_id, fetch_card_rows_by_ids,
+        fetch_card_rows_by_query, fetch_card_rows_by_tags, fetch_child_card_rows,
+        fetch_saved_query, fetch_card_rows_in_range, fetch_search_stats,
+        fetch_search_terms, fetch_unindexed_cards, index_card, insert_card, insert_card_tag,
+        lock_card_version, record_card_revision, set_card_tags, trash_card, update_card_row,
+        NewCardRevision,
+    },
+    etag::IfMatch,
+    handlers::{revisions::revision_author, saved_queries::run_saved_query},
+    models::{ApiResponse, Card, CardParams, CardSearchHit, CARD_TYPE_SMART_FRAME},
+    schema::{CardQueryParams, RangeParams, SearchParams, TagFilterParams},
+    search::{highlight, query_terms, score, snippet, TermHit, TITLE_WEIGHT},
 };
+use std::collections::HashMap;
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Extension, Json,
 };
 use serde_json::json;
-use sqlx::{MySql, Pool};
+use sqlx::{Executor, MySql, Pool, Transaction};
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 100;
 
 /// Visibility value that must never be exposed to unauthenticated viewers.
 const VISIBILITY_PRIVATE: &str = "private";
 
-/// Whether a viewer with the given auth state may see a card of `visibility`.
-fn can_view(authed: bool, visibility: &str) -> bool {
-    authed || visibility != VISIBILITY_PRIVATE
+/// Visibility values a card may have.
+const VISIBILITIES: [&str; 2] = ["public", VISIBILITY_PRIVATE];
+
+/// `Err` with a `400` unless `visibility` is one a card may have.
+pub(crate) fn check_visibility(visibility: &str) -> Result<(), (StatusCode, String)> {
+    if VISIBILITIES.contains(&visibility) {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::BAD_REQUEST,
+            "visibility must be public or private".to_string(),
+        ))
+    }
 }
 
 pub async fn get_cards(
     State(auth): State<AuthState>,
     headers: HeaderMap,
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+    Query(params): Query<TagFilterParams>,
 ) -> ApiResponse<Vec<Card>> {
-    let authed = auth.is_authenticated(&headers);
-    match fetch_all_card_rows(&pool).await {
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let filter = match params.to_filter() {
+        Ok(filter) => filter,
+        Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+    };
+
+    let rows = if filter.is_empty() {
+        fetch_all_card_rows(&pool).await
+    } else {
+        fetch_card_rows_by_tags(&pool, &filter).await
+    };
+    match rows {
         Ok(rows) => {
-            // Private cards are never sent to unauthenticated viewers.
+            // Only cards the viewer may read: never private cards for
+            // unauthenticated viewers, nor cards under frames whose ACL
+            // leaves the user out.
             let cards = rows
                 .into_iter()
                 .map(Card::from)
-                .filter(|c| can_view(authed, &c.visibility))
+                .filter(|c| access.can_read(c.id))
                 .collect();
             ApiResponse::new_ok(StatusCode::OK, cards)
         }
@@ -45,9 +89,14 @@ pub async fn get_cards_in_range(
     State(auth): State<AuthState>,
     headers: HeaderMap,
     Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
     Query(params): Query<RangeParams>,
 ) -> ApiResponse<Vec<Card>> {
-    let authed = auth.is_authenticated(&headers);
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
     // WKT ポリゴンを作成
     let poly = create_poly(params.min_x, params.min_y, params.max_x, params.max_y);
 
@@ -56,7 +105,7 @@ pub async fn get_cards_in_range(
             let cards = rows
                 .into_iter()
                 .map(Card::from)
-                .filter(|c| can_view(authed, &c.visibility))
+                .filter(|c| access.can_read(c.id))
                 .collect();
             ApiResponse::new_ok(StatusCode::OK, cards)
         }
@@ -64,63 +113,310 @@ pub async fn get_cards_in_range(
     }
 }
 
+/// Cards matching a structured query such as
+/// `tag:rust -tag:done updated:>2025-06-01 in:frame(42) "tokio"`. See
+/// `card_query` for the syntax.
+pub async fn query_cards(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+    Query(params): Query<CardQueryParams>,
+) -> ApiResponse<Vec<Card>> {
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let card_query = match CardQuery::parse(&params.q) {
+        Ok(card_query) => card_query,
+        Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    match fetch_card_rows_by_query(&pool, &card_query).await {
+        Ok(rows) => {
+            let cards = rows
+                .into_iter()
+                .map(Card::from)
+                .filter(|c| access.can_read(c.id))
+                .collect();
+            ApiResponse::new_ok(StatusCode::OK, cards)
+        }
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Children of a card. For a smart frame these are the current results of
+/// its saved query (the frame itself excluded); for other cards, the cards
+/// connected below it in `card_card`.
+pub async fn get_card_children(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Path(card_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> ApiResponse<Vec<Card>> {
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let card = match fetch_card_row_by_id(&pool, card_id).await {
+        Ok(card) if access.can_read(card.id) => card,
+        Ok(_) | Err(sqlx::Error::RowNotFound) => {
+            return ApiResponse::new_err(StatusCode::NOT_FOUND, "card not found")
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let rows = match (card.card_type.as_str(), card.saved_query_id) {
+        (CARD_TYPE_SMART_FRAME, Some(saved_query_id)) => {
+            match run_saved_query(&pool, saved_query_id).await {
+                Ok(rows) => rows,
+                Err((status, message)) => return ApiResponse::new_err(status, message),
+            }
+        }
+        (CARD_TYPE_SMART_FRAME, None) => Vec::new(),
+        _ => match fetch_child_card_rows(&pool, card_id).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        },
+    };
+
+    let cards = rows
+        .into_iter()
+        .filter(|row| row.id != card_id)
+        .map(Card::from)
+        .filter(|c| access.can_read(c.id))
+        .collect();
+    ApiResponse::new_ok(StatusCode::OK, cards)
+}
+
+/// Smart frames need an existing saved query.
+pub(crate) async fn check_smart_frame<'e, E>(
+    executor: E,
+    params: &CardParams,
+) -> Result<(), (StatusCode, String)>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    if params.card_type != CARD_TYPE_SMART_FRAME {
+        return Ok(());
+    }
+    let Some(saved_query_id) = params.saved_query_id else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "smart_frame cards need a saved_query_id".to_string(),
+        ));
+    };
+    match fetch_saved_query(executor, saved_query_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err((StatusCode::BAD_REQUEST, "saved query not found".to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Lock the card for a write and check the `If-Match` precondition. `Err` is
+/// the response to send: `404` for a missing or trashed card, `412` with the
+/// server's copy when the card has moved past the given version.
+async fn check_card_version(
+    tx: &mut Transaction<'_, MySql>,
+    card_id: i64,
+    if_match: Option<&IfMatch>,
+) -> Result<(), ApiResponse<Card>> {
+    let version = match lock_card_version(&mut **tx, card_id).await {
+        Ok(Some(version)) => version,
+        Ok(None) => return Err(ApiResponse::new_err(StatusCode::NOT_FOUND, "card not found")),
+        Err(e) => {
+            return Err(ApiResponse::new_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.to_string(),
+            ))
+        }
+    };
+    if if_match.is_none_or(|if_match| if_match.matches(version)) {
+        return Ok(());
+    }
+
+    match fetch_card_row_by_id(&mut **tx, card_id).await {
+        Ok(row) => Err(ApiResponse::new_err_with(
+            StatusCode::PRECONDITION_FAILED,
+            "card was changed by another client",
+            Card::from(row),
+        )
+        .with_etag(version)),
+        Err(e) => Err(ApiResponse::new_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
+    }
+}
+
+/// Full-text search over titles and contents, best match first. See
+/// `search` for how text is tokenized.
+pub async fn search_cards(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+    Query(params): Query<SearchParams>,
+) -> ApiResponse<Vec<CardSearchHit>> {
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let terms = query_terms(&params.q);
+    if terms.is_empty() {
+        return ApiResponse::new_err(StatusCode::BAD_REQUEST, "q must contain a word");
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    let (rows, (document_count, average_length)) = match tokio::try_join!(
+        fetch_search_terms(&pool, &terms),
+        fetch_search_stats(&pool, TITLE_WEIGHT),
+    ) {
+        Ok(result) => result,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    // Document frequencies count every card, so ranking does not depend on
+    // who is searching.
+    let mut hits_by_card: HashMap<i64, Vec<TermHit>> = HashMap::new();
+    for row in rows {
+        hits_by_card
+            .entry(row.card_id)
+            .or_default()
+            .push(TermHit {
+                term: row.term,
+                title_count: row.title_count,
+                contents_count: row.contents_count,
+                title_length: row.title_length,
+                contents_length: row.contents_length,
+            });
+    }
+    let document_frequency: Vec<usize> = terms
+        .iter()
+        .map(|term| {
+            hits_by_card
+                .values()
+                .filter(|hits| hits.iter().any(|hit| term.matches(&hit.term)))
+                .count()
+        })
+        .collect();
+
+    let mut ranked: Vec<(i64, f64)> = hits_by_card
+        .iter()
+        .filter(|(card_id, _)| access.can_read(**card_id))
+        .filter_map(|(card_id, hits)| {
+            score(
+                &terms,
+                hits,
+                &document_frequency,
+                document_count as usize,
+                average_length,
+            )
+            .map(|score| (*card_id, score))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let ranked: Vec<(i64, f64)> = ranked
+        .into_iter()
+        .skip(params.offset.unwrap_or(0))
+        .take(limit)
+        .collect();
+
+    let card_ids: Vec<i64> = ranked.iter().map(|(card_id, _)| *card_id).collect();
+    let mut cards: HashMap<i64, Card> = match fetch_card_rows_by_ids(&pool, &card_ids).await {
+        Ok(rows) => rows
+            .into_iter()
+            .map(|row| (row.id, Card::from(row)))
+            .collect(),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let hits = ranked
+        .into_iter()
+        .filter_map(|(card_id, score)| {
+            let card = cards.remove(&card_id)?;
+            Some(CardSearchHit {
+                title_html: highlight(&card.title, &terms),
+                snippet: snippet(&card.contents, &terms),
+                card,
+                score,
+            })
+        })
+        .collect();
+
+    ApiResponse::new_ok(StatusCode::OK, hits)
+}
+
+/// Add cards that have no search index yet (created before the index
+/// existed) to it.
+pub async fn index_unindexed_cards(pool: &Pool<MySql>) -> Result<(), sqlx::Error> {
+    for card in fetch_unindexed_cards(pool).await? {
+        let mut tx = pool.begin().await?;
+        index_card(&mut tx, card.id, &card.title, &card.contents).await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
 pub async fn create_card(
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    user: Option<Extension<RequestUser>>,
     Json(params): Json<CardParams>,
 ) -> ApiResponse<Card> {
+    if let Err((status, message)) = check_visibility(&params.visibility) {
+        return ApiResponse::new_err(status, message);
+    }
+    if let Err((status, message)) = check_smart_frame(&pool, &params).await {
+        return ApiResponse::new_err(status, message);
+    }
+
     let mut tx = pool.begin().await.expect("transaction error.");
 
-    let poly = create_poly(
-        params.position.x,
-        params.position.y,
-        params.position.x + params.size.x,
-        params.position.y + params.size.y,
-    );
-
-    let res = sqlx::query(
-        r#"
-        INSERT INTO cards (shape, title, contents, visibility, card_type)
-        VALUES (ST_GeomFromText(?), ?, ?, ?, ?)
-    "#,
-    )
-    .bind(&poly)
-    .bind(&params.title)
-    .bind(&params.contents)
-    .bind(&params.visibility)
-    .bind(&params.card_type)
-    .execute(&mut *tx)
-    .await;
-
-    let res = match res {
-        Ok(result) => result,
+    let owner_id = user.as_ref().map(|user| user.user.user_id);
+    let card_id = match insert_card(&mut *tx, &params, owner_id).await {
+        Ok(card_id) => card_id,
         Err(e) => {
             let _ = tx.rollback().await;
             return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
         }
     };
 
-    let card_id = res.last_insert_id() as i64;
-
     // persist card_tag relations
-    if !params.tag_ids.is_empty() {
-        for tag_id in &params.tag_ids {
-            if let Err(e) = sqlx::query(
-                r#"
-                INSERT INTO card_tag (card_id, tag_id)
-                VALUES (?, ?)
-            "#,
-            )
-            .bind(card_id)
-            .bind(tag_id)
-            .execute(&mut *tx)
-            .await
-            {
-                let _ = tx.rollback().await;
-                return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
-            }
+    for tag_id in &params.tag_ids {
+        if let Err(e) = insert_card_tag(&mut *tx, card_id, *tag_id).await {
+            let _ = tx.rollback().await;
+            return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
         }
     }
 
+    if let Err(e) = index_card(&mut tx, card_id, &params.title, &params.contents).await {
+        let _ = tx.rollback().await;
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    let (user_id, source) = revision_author(user.as_deref());
+    let revision = NewCardRevision {
+        card_id,
+        title: &params.title,
+        contents: &params.contents,
+        user_id,
+        source,
+        restored_from: None,
+    };
+    if let Err(e) = record_card_revision(&mut tx, &revision).await {
+        let _ = tx.rollback().await;
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
     if let Err(e) = tx.commit().await {
         return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
     }
@@ -128,73 +424,77 @@ pub async fn create_card(
     match fetch_card_row_by_id(&pool, card_id).await {
         Ok(row) => {
             let card = Card::from(row);
-            ApiResponse::new_ok(StatusCode::OK, card)
+            let version = card.version;
+            ApiResponse::new_ok(StatusCode::OK, card).with_etag(version)
         }
         Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     }
 }
 
+/// With `If-Match`, only applies while the card is still at that version.
 pub async fn update_card(
+    headers: HeaderMap,
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    user: Option<Extension<RequestUser>>,
     Json(params): Json<CardParams>,
 ) -> ApiResponse<Card> {
-    let poly = create_poly(
-        params.position.x,
-        params.position.y,
-        params.position.x + params.size.x,
-        params.position.y + params.size.y,
-    );
+    let if_match = match IfMatch::from_headers(&headers) {
+        Ok(if_match) => if_match,
+        Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+    };
+    if let Err((status, message)) = check_visibility(&params.visibility) {
+        return ApiResponse::new_err(status, message);
+    }
+    if let Err((status, message)) = check_smart_frame(&pool, &params).await {
+        return ApiResponse::new_err(status, message);
+    }
+    match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => {
+            if let Err((status, message)) = access.check_write(&[params.id]) {
+                return ApiResponse::new_err(status, message);
+            }
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
     let mut tx = match pool.begin().await {
         Ok(tx) => tx,
         Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     };
 
-    let result = sqlx::query(
-        r#"
-        UPDATE cards
-        SET shape = ST_GeomFromText(?), title = ?, contents = ?, visibility = ?, card_type = ?
-        WHERE id = ?
-    "#,
-    )
-    .bind(&poly)
-    .bind(&params.title)
-    .bind(&params.contents)
-    .bind(&params.visibility)
-    .bind(&params.card_type)
-    .bind(&params.id)
-    .execute(&mut *tx)
-    .await;
-
-    if let Err(e) = result {
+    if let Err(response) = check_card_version(&mut tx, params.id, if_match.as_ref()).await {
+        let _ = tx.rollback().await;
+        return response;
+    }
+
+    if let Err(e) = update_card_row(&mut *tx, &params).await {
         let _ = tx.rollback().await;
         return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
     }
 
-    // replace card_tag rows
-    let del_res = sqlx::query(
-        r#"DELETE FROM card_tag WHERE card_id = ?"#,
-    )
-    .bind(&params.id)
-    .execute(&mut *tx)
-    .await;
+    if let Err(e) = index_card(&mut tx, params.id, &params.title, &params.contents).await {
+        let _ = tx.rollback().await;
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
 
-    if let Err(e) = del_res {
+    // Moves and resizes leave title and contents alone and add no revision.
+    let (user_id, source) = revision_author(user.as_deref());
+    let revision = NewCardRevision {
+        card_id: params.id,
+        title: &params.title,
+        contents: &params.contents,
+        user_id,
+        source,
+        restored_from: None,
+    };
+    if let Err(e) = record_card_revision(&mut tx, &revision).await {
         let _ = tx.rollback().await;
         return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
     }
 
-    for tag_id in &params.tag_ids {
-        if let Err(e) = sqlx::query(
-            r#"INSERT INTO card_tag (card_id, tag_id) VALUES (?, ?)"#,
-        )
-        .bind(&params.id)
-        .bind(tag_id)
-        .execute(&mut *tx)
-        .await
-        {
-            let _ = tx.rollback().await;
-            return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
-        }
+    if let Err(e) = set_card_tags(&mut tx, params.id, &params.tag_ids).await {
+        let _ = tx.rollback().await;
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
     }
 
     if let Err(e) = tx.commit().await {
@@ -204,16 +504,43 @@ pub async fn update_card(
     match fetch_card_row_by_id(&pool, params.id).await {
         Ok(row) => {
             let card = Card::from(row);
-            ApiResponse::new_ok(StatusCode::OK, card)
+            let version = card.version;
+            ApiResponse::new_ok(StatusCode::OK, card).with_etag(version)
         }
         Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     }
 }
 
+/// Move a card to the trash. Its tags and relations go with it and come back
+/// on restore (`/trash/:id/restore`).
 pub async fn delete_card(
+    headers: HeaderMap,
     Extension(pool): Extension<Pool<sqlx::MySql>>,
+    user: Option<Extension<RequestUser>>,
     Json(params): Json<Card>,
-) -> impl IntoResponse {
+) -> Response {
+    let if_match = match IfMatch::from_headers(&headers) {
+        Ok(if_match) => if_match,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"code": StatusCode::BAD_REQUEST.to_string(), "message": e})),
+            )
+                .into_response();
+        }
+    };
+    match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => {
+            if let Err((status, message)) = access.check_write(&[params.id]) {
+                return ApiResponse::<()>::new_err(status, message).into_response();
+            }
+        }
+        Err(e) => {
+            return ApiResponse::<()>::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                .into_response()
+        }
+    }
+
     let mut tx = match pool.begin().await {
         Ok(tx) => tx,
         Err(e) => {
@@ -222,73 +549,35 @@ pub async fn delete_card(
                 Json(
                     json!({"code": StatusCode::INTERNAL_SERVER_ERROR.to_string(), "message": e.to_string()}),
                 ),
-            );
+            )
+                .into_response();
         }
     };
 
-    let delete_relations = sqlx::query(
-        r#"
-        DELETE FROM card_card
-        WHERE card_parent_id = ? OR card_child_id = ?
-    "#,
-    )
-    .bind(&params.id)
-    .bind(&params.id)
-    .execute(&mut *tx)
-    .await;
-
-    if let Err(e) = delete_relations {
-        let _ = tx.rollback().await;
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(
-                json!({"code": StatusCode::INTERNAL_SERVER_ERROR.to_string(), "message": e.to_string()}),
-            ),
-        );
-    }
-
-    let delete_tags = sqlx::query(
-        r#"
-        DELETE FROM card_tag
-        WHERE card_id = ?
-    "#,
-    )
-    .bind(&params.id)
-    .execute(&mut *tx)
-    .await;
-
-    if let Err(e) = delete_tags {
-        let _ = tx.rollback().await;
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(
-                json!({"code": StatusCode::INTERNAL_SERVER_ERROR.to_string(), "message": e.to_string()}),
-            ),
-        );
+    if let Some(if_match) = &if_match {
+        if let Err(response) = check_card_version(&mut tx, params.id, Some(if_match)).await {
+            let _ = tx.rollback().await;
+            return response.into_response();
+        }
     }
 
-    let res = sqlx::query(
-        r#"
-        DELETE FROM cards 
-        WHERE id = ?
-    "#,
-    )
-    .bind(&params.id)
-    .execute(&mut *tx)
-    .await;
+    // Without If-Match, deleting an already trashed card is not an error.
+    let res = trash_card(&mut tx, params.id).await;
 
     match res {
         Ok(_) => match tx.commit().await {
             Ok(_) => (
                 StatusCode::ACCEPTED,
                 Json(json!({"code": StatusCode::ACCEPTED.to_string(), "message":"Success"})),
-            ),
+            )
+                .into_response(),
             Err(e) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(
                     json!({"code": StatusCode::INTERNAL_SERVER_ERROR.to_string(), "message": e.to_string()}),
                 ),
-            ),
+            )
+                .into_response(),
         },
         Err(e) => {
             let _ = tx.rollback().await;
@@ -298,20 +587,7 @@ pub async fn delete_card(
                     json!({"code": StatusCode::INTERNAL_SERVER_ERROR.to_string(), "message": e.to_string()}),
                 ),
             )
+                .into_response()
         }
     }
 }
-
-fn create_poly(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> String {
-    format!(
-        "POLYGON((\
-        {min_x} {min_y}, {max_x} {min_y}, \
-        {max_x} {max_y}, {min_x} {max_y}, \
-        {min_x} {min_y} \
-      ))",
-        min_x = min_x,
-        min_y = min_y,
-        max_x = max_x,
-        max_y = max_y,
-    )
-}
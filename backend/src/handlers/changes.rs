@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, Scope},
+    db::{
+        fetch_card_relations, fetch_card_rows_by_ids, fetch_changes, fetch_tags_by_ids,
+        stamp_changes,
+    },
+    models::{ApiResponse, Card, CardRelation, Tag},
+};
+
+/// Changes returned per request. `has_more` asks the client to come back
+/// with the new cursor right away.
+const CHANGES_PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct ChangesParams {
+    /// `cursor` of the previous response. 0 or missing returns everything.
+    #[serde(default)]
+    since: i64,
+}
+
+#[derive(Serialize)]
+pub struct RelationKey {
+    card_parent_id: i64,
+    card_child_id: i64,
+}
+
+/// Cards, relations and tags that were deleted, or that the viewer can no
+/// longer see.
+#[derive(Serialize, Default)]
+pub struct Tombstones {
+    cards: Vec<i64>,
+    relations: Vec<RelationKey>,
+    tags: Vec<i64>,
+}
+
+/// Cards, relations and tags as they are now, and tombstones for the ones
+/// that are gone.
+#[derive(Serialize)]
+pub struct EntityStates {
+    cards: Vec<Card>,
+    relations: Vec<CardRelation>,
+    tags: Vec<Tag>,
+    deleted: Tombstones,
+}
+
+/// Current state of everything changed after the cursor.
+#[derive(Serialize)]
+pub struct ChangeFeed {
+    cursor: i64,
+    has_more: bool,
+    #[serde(flatten)]
+    state: EntityStates,
+}
+
+/// Cards, relations and tags created, updated or deleted after `since`, with
+/// the cursor to pass next time. Deletions, including cards moved to the
+/// trash, come back as tombstones. An entity changed several times is sent
+/// once, as it is now.
+pub async fn get_changes(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Query(params): Query<ChangesParams>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> ApiResponse<ChangeFeed> {
+    let result = async {
+        let viewer = auth.card_reader(&headers, Scope::Read).await;
+        let access = CardAccess::load(&access_cache, viewer).await?;
+        changes_since(&pool, params.since, &access).await
+    }
+    .await;
+    match result {
+        Ok(feed) => ApiResponse::new_ok(StatusCode::OK, feed),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub(crate) async fn changes_since(
+    pool: &Pool<MySql>,
+    since: i64,
+    access: &CardAccess,
+) -> Result<ChangeFeed, sqlx::Error> {
+    let mut changes = fetch_changes(pool, since, CHANGES_PAGE_SIZE + 1).await?;
+    let has_more = changes.len() as i64 > CHANGES_PAGE_SIZE;
+    changes.truncate(CHANGES_PAGE_SIZE as usize);
+    let cursor = changes.last().map_or(since, |change| change.seq);
+
+    let mut card_ids = Vec::new();
+    let mut relation_keys = Vec::new();
+    let mut tag_ids = Vec::new();
+    for change in &changes {
+        match change.entity.as_str() {
+            "card" => card_ids.push(change.entity_id),
+            "relation" => relation_keys.push((change.entity_id, change.child_id)),
+            "tag" => tag_ids.push(change.entity_id),
+            _ => {}
+        }
+    }
+
+    Ok(ChangeFeed {
+        cursor,
+        has_more,
+        state: current_state(pool, card_ids, relation_keys, tag_ids, access).await?,
+    })
+}
+
+/// Stamp the changes of every successful write request into the feed before
+/// answering it, so `/changes` itself stays read-only and a client sees its
+/// own writes on its next poll.
+pub async fn stamp_after_writes(
+    Extension(pool): Extension<Pool<MySql>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_write = !matches!(
+        req.method(),
+        &Method::GET | &Method::HEAD | &Method::OPTIONS
+    );
+    let response = next.run(req).await;
+    if is_write && response.status().is_success() {
+        if let Err(e) = stamp_changes(&pool).await {
+            eprintln!("stamping changes failed: {e}");
+        }
+    }
+    response
+}
+
+/// Look up the given cards, relations and tags. Ones that no longer exist,
+/// or that the viewer may not read, become tombstones.
+pub(crate) async fn current_state(
+    pool: &Pool<MySql>,
+    card_ids: Vec<i64>,
+    relation_keys: Vec<(i64, i64)>,
+    tag_ids: Vec<i64>,
+    access: &CardAccess,
+) -> Result<EntityStates, sqlx::Error> {
+    let mut deleted = Tombstones::default();
+
+    // Cards the viewer may not read are never sent; their tags go with them.
+    let cards: Vec<Card> = fetch_card_rows_by_ids(pool, &card_ids)
+        .await?
+        .into_iter()
+        .map(Card::from)
+        .filter(|card| access.can_read(card.id))
+        .collect();
+    let sent: HashSet<i64> = cards.iter().map(|card| card.id).collect();
+    deleted.cards = card_ids
+        .iter()
+        .copied()
+        .filter(|card_id| !sent.contains(card_id))
+        .collect();
+
+    // Nor are relations to them. Relations of the changed cards come along,
+    // so a card turning readable or unreadable takes its relations with it.
+    let (relations, hidden): (Vec<CardRelation>, Vec<CardRelation>) =
+        fetch_card_relations(pool, &relation_keys, &card_ids)
+            .await?
+            .into_iter()
+            .partition(|relation| {
+                access.can_read(relation.card_parent_id) && access.can_read(relation.card_child_id)
+            });
+    let sent: HashSet<(i64, i64)> = relations
+        .iter()
+        .map(|relation| (relation.card_parent_id, relation.card_child_id))
+        .collect();
+    let mut gone = HashSet::new();
+    deleted.relations = relation_keys
+        .into_iter()
+        .chain(
+            hidden
+                .iter()
+                .map(|relation| (relation.card_parent_id, relation.card_child_id)),
+        )
+        .filter(|key| !sent.contains(key) && gone.insert(*key))
+        .map(|(card_parent_id, card_child_id)| RelationKey {
+            card_parent_id,
+            card_child_id,
+        })
+        .collect();
+
+    let tags: Vec<Tag> = fetch_tags_by_ids(pool, &tag_ids)
+        .await?
+        .into_iter()
+        .map(|row| Tag {
+            id: row.id,
+            name: row.name,
+        })
+        .collect();
+    let sent: HashSet<i64> = tags.iter().map(|tag| i64::from(tag.id)).collect();
+    deleted.tags = tag_ids
+        .into_iter()
+        .filter(|tag_id| !sent.contains(tag_id))
+        .collect();
+
+    Ok(EntityStates {
+        cards,
+        relations,
+        tags,
+        deleted,
+    })
+}
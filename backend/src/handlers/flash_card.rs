@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum::{
     body::Bytes,
     extract::{Query, State},
@@ -7,35 +9,288 @@ use axum::{
 };
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize, Serializer};
-use serde_json::json;
-use sqlx::{FromRow, MySql, Pool};
+use sqlx::{Executor, FromRow, MySql, Pool, QueryBuilder, Transaction};
 
-use crate::auth::AuthState;
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, RequestUser, Scope},
+    db::{
+        fetch_card_item_schedules, fetch_card_schedule, fetch_study_mode, index_card,
+        insert_card_review, record_card_revision, upsert_card_schedule, NewCardReview,
+        NewCardRevision,
+    },
+    models::{error_response, CardSchedule, RevisionSource},
+    review_items::{review_items, ReviewItem},
+    scheduler::{LeitnerBoxes, Scheduler},
+    schema::TagFilter,
+};
 
-#[derive(Deserialize)]
+/// Depth used for `recursive=true` without an explicit `depth`. `card_card`
+/// is kept acyclic by `has_cycle`, so this only bounds very deep trees.
+const MAX_SCOPE_DEPTH: u32 = 64;
+
+// FlashCard の SELECT 共通部分（スケジュールは項目ごとに別途取得）
+const SELECT_FLASH_CARDS: &str = r#"
+SELECT DISTINCT c.id, c.title, c.contents, c.updated_at, c.ok_count
+FROM cards c
+"#;
+
+#[derive(Deserialize, Serialize)]
 pub struct FlashCardQuery {
+    /// Single tag name. Kept for existing clients; same as `tags` with one
+    /// entry.
     tag: Option<String>,
+    /// Comma-separated tag names, combined according to `mode`.
+    tags: Option<String>,
+    /// `any` (default) or `all`.
+    mode: Option<String>,
+    /// Comma-separated tag names a card must not have.
+    exclude: Option<String>,
     parent_id: Option<i64>,
+    /// With `parent_id`: how many levels of descendants to include. `1` (the
+    /// default) means direct children only.
+    depth: Option<u32>,
+    /// With `parent_id`: include every descendant, not just direct children.
+    #[serde(default)]
+    recursive: bool,
+    /// Only return items that are due (or new), ordered by due date.
+    #[serde(default)]
+    due: bool,
 }
 
-#[derive(FromRow, Serialize)]
-pub struct FlashCard {
+/// Cards selected by a `FlashCardQuery`: descendants of `parent_id` and/or
+/// cards matching `tags`.
+pub(crate) struct FlashCardScope {
+    pub(crate) parent_id: Option<i64>,
+    max_depth: u32,
+    pub(crate) tags: TagFilter,
+    pub(crate) due: bool,
+}
+
+impl FlashCardQuery {
+    /// Validate the query. A scope needs `parent_id` or at least one tag to
+    /// include; `exclude` alone would select nearly every card.
+    pub(crate) fn scope(&self) -> Result<FlashCardScope, String> {
+        let tags = TagFilter::from_params(
+            self.tag.as_deref(),
+            self.tags.as_deref(),
+            self.mode.as_deref(),
+            self.exclude.as_deref(),
+        )?;
+        if self.parent_id.is_none() && tags.include.is_empty() {
+            return Err("tag, tags or parent_id is required".to_string());
+        }
+
+        let max_depth = match (self.depth, self.recursive) {
+            (Some(depth), _) => depth.clamp(1, MAX_SCOPE_DEPTH),
+            (None, true) => MAX_SCOPE_DEPTH,
+            (None, false) => 1,
+        };
+
+        Ok(FlashCardScope {
+            parent_id: self.parent_id,
+            max_depth,
+            tags,
+            due: self.due,
+        })
+    }
+}
+
+impl FlashCardScope {
+    /// The scope a single card is answered in outside of a deck (quizzes):
+    /// its first parent frame and its own tags.
+    pub(crate) async fn of_card(
+        tx: &mut Transaction<'_, MySql>,
+        card_id: i64,
+    ) -> Result<Self, sqlx::Error> {
+        let parent_id = sqlx::query_scalar::<_, i64>(
+            "SELECT card_parent_id FROM card_card WHERE card_child_id = ? \
+             ORDER BY card_parent_id LIMIT 1",
+        )
+        .bind(card_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+        let tags = sqlx::query_scalar::<_, String>(
+            "SELECT t.name FROM card_tag ct JOIN tags t ON t.id = ct.tag_id WHERE ct.card_id = ?",
+        )
+        .bind(card_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(FlashCardScope {
+            parent_id,
+            max_depth: 1,
+            tags: TagFilter {
+                include: tags,
+                match_all: false,
+                exclude: Vec::new(),
+            },
+            due: false,
+        })
+    }
+
+    /// Cards in scope, optionally narrowed to a single card. Visibility is
+    /// checked on every level of the `parent_id` tree: a private frame hides
+    /// everything below it.
+    fn query(&self, include_private: bool, card_id: Option<i64>) -> QueryBuilder<'_, MySql> {
+        let mut query = QueryBuilder::<MySql>::new("");
+
+        // parent_id 配下のカード（scope_cards）。has_cycle と同じ再帰 CTE
+        if let Some(parent_id) = self.parent_id {
+            query
+                .push(
+                    "WITH RECURSIVE scope_cards (id, depth) AS (\
+                     SELECT cc.card_child_id, 1 FROM card_card cc \
+                     JOIN cards child ON child.id = cc.card_child_id \
+                     WHERE cc.card_parent_id = ",
+                )
+                .push_bind(parent_id)
+                .push(" AND (")
+                .push_bind(include_private)
+                .push(
+                    " OR child.visibility <> 'private') \
+                     UNION \
+                     SELECT cc.card_child_id, s.depth + 1 FROM card_card cc \
+                     JOIN scope_cards s ON cc.card_parent_id = s.id \
+                     JOIN cards child ON child.id = cc.card_child_id \
+                     WHERE s.depth < ",
+                )
+                .push_bind(self.max_depth)
+                .push(" AND (")
+                .push_bind(include_private)
+                .push(" OR child.visibility <> 'private')) ");
+        }
+
+        query.push(SELECT_FLASH_CARDS);
+        if self.parent_id.is_some() {
+            query.push(" INNER JOIN scope_cards sc ON sc.id = c.id");
+        }
+        query
+            .push(" WHERE (")
+            .push_bind(include_private)
+            .push(" OR c.visibility <> 'private') AND c.deleted_at IS NULL");
+        self.tags.push_conditions(&mut query);
+        if let Some(card_id) = card_id {
+            query.push(" AND c.id = ").push_bind(card_id);
+        }
+        query.push(" ORDER BY c.id ASC");
+
+        query
+    }
+
+    /// Scheduler for answers given in this scope: the study mode of the
+    /// `parent_id` frame or of one of the tags, else `default`.
+    pub(crate) async fn scheduler<'e, E>(
+        &self,
+        executor: E,
+        default: Scheduler,
+    ) -> Result<Scheduler, sqlx::Error>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
+        let mode = fetch_study_mode(executor, self.parent_id, &self.tags.include).await?;
+        Ok(mode
+            .as_deref()
+            .and_then(Scheduler::parse)
+            .unwrap_or(default))
+    }
+
+    /// Value stored in `card_reviews.scope_tag` (VARCHAR(100)).
+    pub(crate) fn review_tag(&self) -> Option<String> {
+        if self.tags.include.is_empty() {
+            return None;
+        }
+        Some(self.tags.include.join(",").chars().take(100).collect())
+    }
+}
+
+#[derive(FromRow)]
+pub(crate) struct FlashCardRow {
     id: i64,
     title: String,
     contents: String,
-    #[serde(rename = "date", serialize_with = "serialize_naive_datetime_as_utc")]
     updated_at: NaiveDateTime,
     ok_count: i32,
 }
 
+/// One review item of a card. Cards with cloze deletions appear once per
+/// cloze number.
+#[derive(Clone, Serialize)]
+pub struct FlashCard {
+    pub(crate) id: i64,
+    pub(crate) item: String,
+    pub(crate) title: String,
+    pub(crate) contents: String,
+    question: String,
+    answer: String,
+    #[serde(rename = "date", serialize_with = "serialize_naive_datetime_as_utc")]
+    pub(crate) updated_at: NaiveDateTime,
+    ok_count: i32,
+    #[serde(
+        rename = "due",
+        serialize_with = "serialize_optional_naive_datetime_as_utc"
+    )]
+    pub(crate) due_at: Option<NaiveDateTime>,
+    interval_days: f64,
+    ease_factor: f64,
+    lapses: i32,
+    /// Successful reviews in a row; 0 for new items and after a failure.
+    #[serde(skip)]
+    pub(crate) reps: i32,
+    #[serde(rename = "box")]
+    pub(crate) leitner_box: i32,
+}
+
+impl FlashCard {
+    fn new(row: &FlashCardRow, item: ReviewItem, schedule: Option<&CardSchedule>) -> Self {
+        let default = CardSchedule::default();
+        let schedule = schedule.unwrap_or(&default);
+        Self {
+            id: row.id,
+            item: item.key,
+            title: row.title.clone(),
+            contents: row.contents.clone(),
+            question: item.question,
+            answer: item.answer,
+            updated_at: row.updated_at,
+            ok_count: row.ok_count,
+            due_at: schedule.due_at,
+            interval_days: schedule.interval_days,
+            ease_factor: schedule.ease_factor,
+            lapses: schedule.lapses,
+            reps: schedule.reps,
+            leitner_box: schedule.leitner_box,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct FlashCardResult {
     id: i64,
+    /// Review item that was answered. Defaults to the card's first item.
+    item: Option<String>,
     #[serde(rename = "is_OK")]
     is_ok: bool,
     date: Option<String>,
     contents: Option<String>,
     title: Option<String>,
+    /// How long the user took to answer, in milliseconds.
+    response_ms: Option<i32>,
+}
+
+impl FlashCardResult {
+    /// A bare answer to one review item, without edits.
+    pub(crate) fn answer(id: i64, item: &str, is_ok: bool, response_ms: Option<i32>) -> Self {
+        FlashCardResult {
+            id,
+            item: Some(item.to_string()),
+            is_ok,
+            date: None,
+            contents: None,
+            title: None,
+            response_ms,
+        }
+    }
 }
 
 pub async fn get_flash_cards_by_tag(
@@ -43,397 +298,238 @@ pub async fn get_flash_cards_by_tag(
     Query(params): Query<FlashCardQuery>,
     headers: HeaderMap,
     Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
 ) -> Response {
-    let include_private = auth.api_key_user(&pool, &headers).await.is_some();
-
-    let result = match (&params.tag, params.parent_id) {
-        (Some(tag), Some(parent_id)) => {
-            fetch_child_cards_by_parent_and_tag(&pool, parent_id, tag, include_private).await
-        }
-        (Some(tag), None) => fetch_cards_by_tag(&pool, tag, include_private).await,
-        (None, Some(parent_id)) => {
-            fetch_child_cards_by_parent(&pool, parent_id, include_private).await
-        }
-        (None, None) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "code": StatusCode::BAD_REQUEST.as_u16(),
-                    "message": "tag or parent_id is required",
-                })),
-            )
-                .into_response();
-        }
+    let scope = match params.scope() {
+        Ok(scope) => scope,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+    };
+    let viewer = auth.card_reader(&headers, Scope::Flashcards).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     };
 
-    match result {
+    match fetch_scoped_flash_cards(&pool, &scope, &access).await {
         Ok(cards) => Json(cards).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "code": StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     }
 }
 
 pub async fn post_flash_card_result(
     Query(params): Query<FlashCardQuery>,
     Extension(pool): Extension<Pool<MySql>>,
+    Extension(scheduler): Extension<Scheduler>,
+    Extension(boxes): Extension<LeitnerBoxes>,
+    user: Option<Extension<RequestUser>>,
     body: Bytes,
 ) -> Response {
     let result = match serde_json::from_slice::<FlashCardResult>(&body) {
         Ok(result) => result,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "code": StatusCode::BAD_REQUEST.as_u16(),
-                    "message": e.to_string(),
-                })),
-            )
-                .into_response();
-        }
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let scope = match params.scope() {
+        Ok(scope) => scope,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+    };
+    // Posting a result needs read access to the card, editing it along with
+    // the result write access.
+    let access = match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => access,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     };
+    let checked = if result.title.is_some() || result.contents.is_some() {
+        access.check_write(&[result.id])
+    } else {
+        access.check_read(result.id)
+    };
+    if let Err((status, message)) = checked {
+        return error_response(status, message);
+    }
 
-    let updated_at = result
-        .date
-        .as_deref()
-        .and_then(parse_flash_card_date);
+    let updated_at = result.date.as_deref().and_then(parse_flash_card_date);
 
-    let update_result = match (&params.tag, params.parent_id) {
-        (Some(tag), Some(parent_id)) => {
-            update_flash_card_result_by_parent_and_tag(&pool, parent_id, tag, result, updated_at)
-                .await
-        }
-        (Some(tag), None) => update_flash_card_result_by_tag(&pool, tag, result, updated_at).await,
-        (None, Some(parent_id)) => {
-            update_flash_card_result_by_parent(&pool, parent_id, result, updated_at).await
-        }
-        (None, None) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "code": StatusCode::BAD_REQUEST.as_u16(),
-                    "message": "tag or parent_id is required",
-                })),
-            )
-                .into_response();
-        }
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
     };
 
-    match update_result {
-        Ok(Some(card)) => Json(card).into_response(),
-        Ok(None) => (
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "code": StatusCode::NOT_FOUND.as_u16(),
-                    "message": "card not found for query",
-                })),
-            )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "code": StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+    let user_id = user.map(|user| user.user.user_id);
+    let row = match update_flash_card_result(&mut tx, &scope, &result, updated_at, user_id).await {
+        Ok(Some(row)) => row,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "card not found for query"),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    // The review item is resolved against the contents just written, so an
+    // answer can be posted together with an edit.
+    let items = review_items(&row.title, &row.contents);
+    let item = match &result.item {
+        Some(key) => items.into_iter().find(|item| item.key == *key),
+        None => items.into_iter().next(),
+    };
+    let Some(item) = item else {
+        return error_response(StatusCode::BAD_REQUEST, "unknown review item for card");
+    };
+
+    let scheduler = match scope.scheduler(&mut *tx, scheduler).await {
+        Ok(scheduler) => scheduler,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let scope_tag = scope.review_tag();
+    let review = NewCardReview {
+        card_id: row.id,
+        item_key: &item.key,
+        reviewed_at: updated_at.unwrap_or_else(|| Utc::now().naive_utc()),
+        is_ok: result.is_ok,
+        response_ms: result.response_ms,
+        scope_tag: scope_tag.as_deref(),
+        scope_parent_id: scope.parent_id,
+        session_id: None,
+    };
+
+    let schedule = match record_review(&mut tx, scheduler, &boxes, &review).await {
+        Ok(schedule) => schedule,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    if let Err(e) = tx.commit().await {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
     }
-}
 
-async fn fetch_cards_by_tag(
-    pool: &Pool<MySql>,
-    tag: &str,
-    include_private: bool,
-) -> Result<Vec<FlashCard>, sqlx::Error> {
-    sqlx::query_as::<_, FlashCard>(
-        r#"
-        SELECT DISTINCT c.id, c.title, c.contents, c.updated_at, c.ok_count
-        FROM cards c
-        INNER JOIN card_tag ct ON ct.card_id = c.id
-        INNER JOIN tags t ON t.id = ct.tag_id
-        WHERE t.name = ? AND (? OR c.visibility <> 'private')
-        ORDER BY c.id ASC
-        "#,
-    )
-    .bind(tag)
-    .bind(include_private)
-    .fetch_all(pool)
-    .await
+    Json(FlashCard::new(&row, item, Some(&schedule))).into_response()
 }
 
-async fn fetch_child_cards_by_parent(
+/// Review items of the cards in `scope` that `access` lets the user read.
+/// Shared by the flash-card listing, the deck exports and study sessions.
+pub(crate) async fn fetch_scoped_flash_cards(
     pool: &Pool<MySql>,
-    parent_id: i64,
-    include_private: bool,
+    scope: &FlashCardScope,
+    access: &CardAccess,
 ) -> Result<Vec<FlashCard>, sqlx::Error> {
-    sqlx::query_as::<_, FlashCard>(
-        r#"
-        SELECT c.id, c.title, c.contents, c.updated_at, c.ok_count
-        FROM cards c
-        INNER JOIN card_card cc ON cc.card_child_id = c.id
-        WHERE cc.card_parent_id = ? AND (? OR c.visibility <> 'private')
-        ORDER BY c.id ASC
-        "#,
-    )
-    .bind(parent_id)
-    .bind(include_private)
-    .fetch_all(pool)
-    .await
-}
+    let mut rows = scope
+        .query(!access.is_anonymous(), None)
+        .build_query_as::<FlashCardRow>()
+        .fetch_all(pool)
+        .await?;
+    rows.retain(|row| access.can_read(row.id));
 
-async fn fetch_child_cards_by_parent_and_tag(
-    pool: &Pool<MySql>,
-    parent_id: i64,
-    tag: &str,
-    include_private: bool,
-) -> Result<Vec<FlashCard>, sqlx::Error> {
-    sqlx::query_as::<_, FlashCard>(
-        r#"
-        SELECT DISTINCT c.id, c.title, c.contents, c.updated_at, c.ok_count
-        FROM cards c
-        INNER JOIN card_card cc ON cc.card_child_id = c.id
-        INNER JOIN card_tag ct ON ct.card_id = c.id
-        INNER JOIN tags t ON t.id = ct.tag_id
-        WHERE cc.card_parent_id = ? AND t.name = ? AND (? OR c.visibility <> 'private')
-        ORDER BY c.id ASC
-        "#,
-    )
-    .bind(parent_id)
-    .bind(tag)
-    .bind(include_private)
-    .fetch_all(pool)
-    .await
-}
+    let card_ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let mut schedules: HashMap<(i64, String), CardSchedule> =
+        fetch_card_item_schedules(pool, &card_ids)
+            .await?
+            .into_iter()
+            .map(|row| ((row.card_id, row.item_key), row.schedule))
+            .collect();
 
-async fn update_flash_card_result_by_tag(
-    pool: &Pool<MySql>,
-    tag: &str,
-    result: FlashCardResult,
-    updated_at: Option<NaiveDateTime>,
-) -> Result<Option<FlashCard>, sqlx::Error> {
-    let affected = if let Some(updated_at) = updated_at {
-        sqlx::query(
-            r#"
-            UPDATE cards c
-            INNER JOIN card_tag ct ON ct.card_id = c.id
-            INNER JOIN tags t ON t.id = ct.tag_id
-            SET
-              c.ok_count = c.ok_count + ?,
-              c.contents = COALESCE(?, c.contents),
-              c.title = COALESCE(?, c.title),
-              c.updated_at = ?
-            WHERE c.id = ? AND t.name = ?
-            "#,
-        )
-        .bind(if result.is_ok { 1 } else { 0 })
-        .bind(result.contents.as_deref())
-        .bind(result.title.as_deref())
-        .bind(updated_at)
-        .bind(result.id)
-        .bind(tag)
-        .execute(pool)
-        .await?
-        .rows_affected()
-    } else {
-        sqlx::query(
-            r#"
-            UPDATE cards c
-            INNER JOIN card_tag ct ON ct.card_id = c.id
-            INNER JOIN tags t ON t.id = ct.tag_id
-            SET
-              c.ok_count = c.ok_count + ?,
-              c.contents = COALESCE(?, c.contents),
-              c.title = COALESCE(?, c.title)
-            WHERE c.id = ? AND t.name = ?
-            "#,
-        )
-        .bind(if result.is_ok { 1 } else { 0 })
-        .bind(result.contents.as_deref())
-        .bind(result.title.as_deref())
-        .bind(result.id)
-        .bind(tag)
-        .execute(pool)
-        .await?
-        .rows_affected()
-    };
+    let mut cards: Vec<FlashCard> = rows
+        .iter()
+        .flat_map(|row| {
+            review_items(&row.title, &row.contents)
+                .into_iter()
+                .map(|item| {
+                    let schedule = schedules.remove(&(row.id, item.key.clone()));
+                    FlashCard::new(row, item, schedule.as_ref())
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
 
-    if affected == 0 {
-        return Ok(None);
+    if scope.due {
+        let now = Utc::now().naive_utc();
+        cards.retain(|card| card.due_at.is_none_or(|due_at| due_at <= now));
+        // Overdue reviews first, new items last. The sort is stable, so items
+        // keep card order within the same due date.
+        cards.sort_by_key(|card| (card.due_at.is_none(), card.due_at));
     }
 
-    let card = sqlx::query_as::<_, FlashCard>(
-        r#"
-        SELECT c.id, c.title, c.contents, c.updated_at, c.ok_count
-        FROM cards c
-        INNER JOIN card_tag ct ON ct.card_id = c.id
-        INNER JOIN tags t ON t.id = ct.tag_id
-        WHERE c.id = ? AND t.name = ?
-        LIMIT 1
-        "#,
-    )
-    .bind(result.id)
-    .bind(tag)
-    .fetch_one(pool)
-    .await?;
-
-    Ok(Some(card))
+    Ok(cards)
 }
 
-async fn update_flash_card_result_by_parent(
-    pool: &Pool<MySql>,
-    parent_id: i64,
-    result: FlashCardResult,
+/// Apply a posted result to a card in `scope`: bump `ok_count` and replace
+/// contents / title when given. `None` when the card is not in scope.
+/// Posting results needs an API token, so private cards are in scope.
+pub(crate) async fn update_flash_card_result(
+    tx: &mut Transaction<'_, MySql>,
+    scope: &FlashCardScope,
+    result: &FlashCardResult,
     updated_at: Option<NaiveDateTime>,
-) -> Result<Option<FlashCard>, sqlx::Error> {
-    let affected = if let Some(updated_at) = updated_at {
-        sqlx::query(
-            r#"
-            UPDATE cards c
-            INNER JOIN card_card cc ON cc.card_child_id = c.id
-            SET
-              c.ok_count = c.ok_count + ?,
-              c.contents = COALESCE(?, c.contents),
-              c.title = COALESCE(?, c.title),
-              c.updated_at = ?
-            WHERE c.id = ? AND cc.card_parent_id = ?
-            "#,
-        )
-        .bind(if result.is_ok { 1 } else { 0 })
-        .bind(result.contents.as_deref())
-        .bind(result.title.as_deref())
-        .bind(updated_at)
-        .bind(result.id)
-        .bind(parent_id)
-        .execute(pool)
-        .await?
-        .rows_affected()
-    } else {
-        sqlx::query(
-            r#"
-            UPDATE cards c
-            INNER JOIN card_card cc ON cc.card_child_id = c.id
-            SET
-              c.ok_count = c.ok_count + ?,
-              c.contents = COALESCE(?, c.contents),
-              c.title = COALESCE(?, c.title)
-            WHERE c.id = ? AND cc.card_parent_id = ?
-            "#,
-        )
-        .bind(if result.is_ok { 1 } else { 0 })
-        .bind(result.contents.as_deref())
-        .bind(result.title.as_deref())
-        .bind(result.id)
-        .bind(parent_id)
-        .execute(pool)
-        .await?
-        .rows_affected()
-    };
-
-    if affected == 0 {
+    user_id: Option<i64>,
+) -> Result<Option<FlashCardRow>, sqlx::Error> {
+    // Checked with its own SELECT so the UPDATE stays a plain update of
+    // `cards` rather than reading `cards` through the scope CTE.
+    let in_scope = scope
+        .query(true, Some(result.id))
+        .build_query_as::<FlashCardRow>()
+        .fetch_optional(&mut **tx)
+        .await?;
+    if in_scope.is_none() {
         return Ok(None);
     }
 
-    let card = sqlx::query_as::<_, FlashCard>(
-        r#"
-        SELECT c.id, c.title, c.contents, c.updated_at, c.ok_count
-        FROM cards c
-        INNER JOIN card_card cc ON cc.card_child_id = c.id
-        WHERE c.id = ? AND cc.card_parent_id = ?
-        LIMIT 1
-        "#,
-    )
-    .bind(result.id)
-    .bind(parent_id)
-    .fetch_one(pool)
-    .await?;
-
-    Ok(Some(card))
-}
+    let mut query = QueryBuilder::<MySql>::new(
+        "UPDATE cards c SET c.version = c.version + 1, c.ok_count = c.ok_count + ",
+    );
+    query
+        .push_bind(if result.is_ok { 1 } else { 0 })
+        .push(", c.contents = COALESCE(")
+        .push_bind(result.contents.as_deref())
+        .push(", c.contents), c.title = COALESCE(")
+        .push_bind(result.title.as_deref())
+        .push(", c.title)");
+    // Without a date, updated_at is left to ON UPDATE CURRENT_TIMESTAMP.
+    if let Some(updated_at) = updated_at {
+        query.push(", c.updated_at = ").push_bind(updated_at);
+    }
+    query.push(" WHERE c.id = ").push_bind(result.id);
+    query.build().execute(&mut **tx).await?;
 
-async fn update_flash_card_result_by_parent_and_tag(
-    pool: &Pool<MySql>,
-    parent_id: i64,
-    tag: &str,
-    result: FlashCardResult,
-    updated_at: Option<NaiveDateTime>,
-) -> Result<Option<FlashCard>, sqlx::Error> {
-    let affected = if let Some(updated_at) = updated_at {
-        sqlx::query(
-            r#"
-            UPDATE cards c
-            INNER JOIN card_card cc ON cc.card_child_id = c.id
-            INNER JOIN card_tag ct ON ct.card_id = c.id
-            INNER JOIN tags t ON t.id = ct.tag_id
-            SET
-              c.ok_count = c.ok_count + ?,
-              c.contents = COALESCE(?, c.contents),
-              c.title = COALESCE(?, c.title),
-              c.updated_at = ?
-            WHERE c.id = ? AND cc.card_parent_id = ? AND t.name = ?
-            "#,
-        )
-        .bind(if result.is_ok { 1 } else { 0 })
-        .bind(result.contents.as_deref())
-        .bind(result.title.as_deref())
-        .bind(updated_at)
-        .bind(result.id)
-        .bind(parent_id)
-        .bind(tag)
-        .execute(pool)
-        .await?
-        .rows_affected()
-    } else {
-        sqlx::query(
-            r#"
-            UPDATE cards c
-            INNER JOIN card_card cc ON cc.card_child_id = c.id
-            INNER JOIN card_tag ct ON ct.card_id = c.id
-            INNER JOIN tags t ON t.id = ct.tag_id
-            SET
-              c.ok_count = c.ok_count + ?,
-              c.contents = COALESCE(?, c.contents),
-              c.title = COALESCE(?, c.title)
-            WHERE c.id = ? AND cc.card_parent_id = ? AND t.name = ?
-            "#,
-        )
-        .bind(if result.is_ok { 1 } else { 0 })
-        .bind(result.contents.as_deref())
-        .bind(result.title.as_deref())
+    let sql = format!("{} WHERE c.id = ?", SELECT_FLASH_CARDS);
+    let row = sqlx::query_as::<_, FlashCardRow>(&sql)
         .bind(result.id)
-        .bind(parent_id)
-        .bind(tag)
-        .execute(pool)
-        .await?
-        .rows_affected()
-    };
-
-    if affected == 0 {
-        return Ok(None);
+        .fetch_one(&mut **tx)
+        .await?;
+    if result.title.is_some() || result.contents.is_some() {
+        index_card(tx, row.id, &row.title, &row.contents).await?;
+        let revision = NewCardRevision {
+            card_id: row.id,
+            title: &row.title,
+            contents: &row.contents,
+            user_id,
+            source: RevisionSource::FlashCard,
+            restored_from: None,
+        };
+        record_card_revision(tx, &revision).await?;
     }
 
-    let card = sqlx::query_as::<_, FlashCard>(
-        r#"
-        SELECT c.id, c.title, c.contents, c.updated_at, c.ok_count
-        FROM cards c
-        INNER JOIN card_card cc ON cc.card_child_id = c.id
-        INNER JOIN card_tag ct ON ct.card_id = c.id
-        INNER JOIN tags t ON t.id = ct.tag_id
-        WHERE c.id = ? AND cc.card_parent_id = ? AND t.name = ?
-        LIMIT 1
-        "#,
+    Ok(Some(row))
+}
+
+/// Log one answer in `card_reviews` and advance the review item's
+/// spaced-repetition state. Returns the new state. Every way of answering a
+/// card goes through here.
+pub(crate) async fn record_review(
+    tx: &mut Transaction<'_, MySql>,
+    scheduler: Scheduler,
+    boxes: &LeitnerBoxes,
+    review: &NewCardReview<'_>,
+) -> Result<CardSchedule, sqlx::Error> {
+    insert_card_review(&mut **tx, review).await?;
+
+    let state = fetch_card_schedule(&mut **tx, review.card_id, review.item_key)
+        .await?
+        .unwrap_or_default();
+    let next = scheduler.review(&state, review.is_ok, review.reviewed_at, boxes);
+    upsert_card_schedule(
+        &mut **tx,
+        review.card_id,
+        review.item_key,
+        scheduler.as_str(),
+        &next,
     )
-    .bind(result.id)
-    .bind(parent_id)
-    .bind(tag)
-    .fetch_one(pool)
     .await?;
 
-    Ok(Some(card))
+    Ok(next)
 }
 
 fn parse_flash_card_date(value: &str) -> Option<NaiveDateTime> {
@@ -442,7 +538,7 @@ fn parse_flash_card_date(value: &str) -> Option<NaiveDateTime> {
         .ok()
 }
 
-fn serialize_naive_datetime_as_utc<S>(
+pub(crate) fn serialize_naive_datetime_as_utc<S>(
     value: &NaiveDateTime,
     serializer: S,
 ) -> Result<S::Ok, S::Error>
@@ -451,3 +547,16 @@ where
 {
     serializer.serialize_str(&format!("{}Z", value.format("%Y-%m-%dT%H:%M:%S")))
 }
+
+pub(crate) fn serialize_optional_naive_datetime_as_utc<S>(
+    value: &Option<NaiveDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(value) => serialize_naive_datetime_as_utc(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,326 @@
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{NaiveDateTime, Utc};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySql, Pool};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, RequestUser, Scope},
+    db::NewCardReview,
+    handlers::flash_card::{
+        record_review, serialize_optional_naive_datetime_as_utc, update_flash_card_result,
+        FlashCardResult, FlashCardScope,
+    },
+    models::error_response,
+    review_items::{review_items, ReviewItem},
+    scheduler::{LeitnerBoxes, Scheduler},
+};
+
+const DEFAULT_CHOICES: usize = 4;
+const MAX_CHOICES: usize = 8;
+
+// 兄弟カード：同じ親（card_card）を持つカードを先に、次に同じタグを持つカード
+const SELECT_SIBLING_CARDS: &str = r#"
+SELECT c.id, c.title, c.contents, MIN(s.sibling_rank) AS sibling_rank
+FROM (
+    SELECT cc2.card_child_id AS id, 0 AS sibling_rank
+    FROM card_card cc1
+    JOIN card_card cc2 ON cc2.card_parent_id = cc1.card_parent_id
+    WHERE cc1.card_child_id = ?
+    UNION ALL
+    SELECT ct2.card_id AS id, 1 AS sibling_rank
+    FROM card_tag ct1
+    JOIN card_tag ct2 ON ct2.tag_id = ct1.tag_id
+    WHERE ct1.card_id = ?
+) s
+JOIN cards c ON c.id = s.id
+WHERE c.id <> ? AND c.card_type <> 'frame'
+GROUP BY c.id, c.title, c.contents
+ORDER BY sibling_rank, c.id
+"#;
+
+#[derive(Deserialize)]
+pub struct QuizQuery {
+    id: i64,
+    /// Review item to ask. Defaults to the card's first item.
+    item: Option<String>,
+    /// Same seed, same choices in the same order. Random when omitted.
+    seed: Option<u64>,
+    /// Number of choices including the correct one (2 to 8, default 4).
+    choices: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct QuizAnswer {
+    id: i64,
+    item: Option<String>,
+    /// `seed` and `choices` of the quiz that was shown.
+    seed: u64,
+    choices: Option<usize>,
+    /// Index of the picked choice.
+    choice: usize,
+    response_ms: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct Quiz {
+    id: i64,
+    item: String,
+    seed: u64,
+    question: String,
+    choices: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct QuizResult {
+    id: i64,
+    item: String,
+    #[serde(rename = "is_OK")]
+    is_ok: bool,
+    /// Index of the correct choice.
+    correct_choice: usize,
+    answer: String,
+    #[serde(
+        rename = "due",
+        serialize_with = "serialize_optional_naive_datetime_as_utc"
+    )]
+    due_at: Option<NaiveDateTime>,
+    interval_days: f64,
+    #[serde(rename = "box")]
+    leitner_box: i32,
+}
+
+#[derive(FromRow)]
+struct QuizCardRow {
+    id: i64,
+    title: String,
+    contents: String,
+}
+
+#[derive(FromRow)]
+struct SiblingCardRow {
+    id: i64,
+    title: String,
+    contents: String,
+    sibling_rank: i64,
+}
+
+/// Multiple-choice question for one review item of a card. The correct
+/// choice is the item's answer; the others are answers of sibling cards.
+pub async fn get_quiz(
+    State(auth): State<AuthState>,
+    Query(params): Query<QuizQuery>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> Response {
+    let viewer = auth.card_reader(&headers, Scope::Flashcards).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let seed = params
+        .seed
+        .unwrap_or_else(|| rand::rng().random::<u32>() as u64);
+
+    match build_quiz(
+        &pool,
+        params.id,
+        params.item.as_deref(),
+        seed,
+        params.choices,
+        &access,
+    )
+    .await
+    {
+        Ok((item, choices, _)) => Json(Quiz {
+            id: params.id,
+            item: item.key,
+            seed,
+            question: item.question,
+            choices,
+        })
+        .into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Check a picked choice and record it like a `/cards/flush_json` result.
+pub async fn post_quiz_answer(
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(scheduler): Extension<Scheduler>,
+    Extension(boxes): Extension<LeitnerBoxes>,
+    user: Option<Extension<RequestUser>>,
+    body: Bytes,
+) -> Response {
+    let answer = match serde_json::from_slice::<QuizAnswer>(&body) {
+        Ok(answer) => answer,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let access = match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => access,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let (item, choices, correct_choice) = match build_quiz(
+        &pool,
+        answer.id,
+        answer.item.as_deref(),
+        answer.seed,
+        answer.choices,
+        &access,
+    )
+    .await
+    {
+        Ok(quiz) => quiz,
+        Err(response) => return response,
+    };
+    if answer.choice >= choices.len() {
+        return error_response(StatusCode::BAD_REQUEST, "choice out of range");
+    }
+    let is_ok = answer.choice == correct_choice;
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let scope = match FlashCardScope::of_card(&mut tx, answer.id).await {
+        Ok(scope) => scope,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let result = FlashCardResult::answer(answer.id, &item.key, is_ok, answer.response_ms);
+    let user_id = user.map(|user| user.user.user_id);
+    match update_flash_card_result(&mut tx, &scope, &result, None, user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "card not found"),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    let scheduler = match scope.scheduler(&mut *tx, scheduler).await {
+        Ok(scheduler) => scheduler,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let review = NewCardReview {
+        card_id: answer.id,
+        item_key: &item.key,
+        reviewed_at: Utc::now().naive_utc(),
+        is_ok,
+        response_ms: answer.response_ms,
+        scope_tag: None,
+        scope_parent_id: None,
+        session_id: None,
+    };
+    let schedule = match record_review(&mut tx, scheduler, &boxes, &review).await {
+        Ok(schedule) => schedule,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    if let Err(e) = tx.commit().await {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    Json(QuizResult {
+        id: answer.id,
+        item: item.key,
+        is_ok,
+        correct_choice,
+        answer: item.answer,
+        due_at: schedule.due_at,
+        interval_days: schedule.interval_days,
+        leitner_box: schedule.leitner_box,
+    })
+    .into_response()
+}
+
+/// The review item, the shuffled choices and the index of the correct one.
+async fn build_quiz(
+    pool: &Pool<MySql>,
+    card_id: i64,
+    item_key: Option<&str>,
+    seed: u64,
+    choice_count: Option<usize>,
+    access: &CardAccess,
+) -> Result<(ReviewItem, Vec<String>, usize), Response> {
+    let card = sqlx::query_as::<_, QuizCardRow>(
+        "SELECT id, title, contents FROM cards WHERE id = ? AND deleted_at IS NULL",
+    )
+    .bind(card_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .filter(|card| access.can_read(card.id))
+    .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "card not found"))?;
+
+    let items = review_items(&card.title, &card.contents);
+    let item = match item_key {
+        Some(key) => items.into_iter().find(|item| item.key == key),
+        None => items.into_iter().next(),
+    }
+    .ok_or_else(|| error_response(StatusCode::BAD_REQUEST, "unknown review item for card"))?;
+    let correct = item.answer.trim().to_string();
+    if correct.is_empty() {
+        return Err(error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "review item has no answer",
+        ));
+    }
+
+    let siblings = sqlx::query_as::<_, SiblingCardRow>(SELECT_SIBLING_CARDS)
+        .bind(card.id)
+        .bind(card.id)
+        .bind(card.id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // Parent siblings are preferred over tag siblings; each group is
+    // shuffled on its own so the preference survives the shuffle.
+    let mut distractors: Vec<String> = Vec::new();
+    for rank in [0, 1] {
+        let mut group: Vec<String> = Vec::new();
+        for sibling in siblings
+            .iter()
+            .filter(|s| s.sibling_rank == rank && access.can_read(s.id))
+        {
+            for sibling_item in review_items(&sibling.title, &sibling.contents) {
+                let answer = sibling_item.answer.trim().to_string();
+                if !answer.is_empty()
+                    && answer != correct
+                    && !distractors.contains(&answer)
+                    && !group.contains(&answer)
+                {
+                    group.push(answer);
+                }
+            }
+        }
+        group.shuffle(&mut rng);
+        distractors.extend(group);
+    }
+
+    let choice_count = choice_count
+        .unwrap_or(DEFAULT_CHOICES)
+        .clamp(2, MAX_CHOICES);
+    distractors.truncate(choice_count - 1);
+    if distractors.is_empty() {
+        return Err(error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "no sibling cards to draw choices from",
+        ));
+    }
+
+    let correct_choice = rng.random_range(0..=distractors.len());
+    let mut choices = distractors;
+    choices.insert(correct_choice, correct);
+
+    Ok((item, choices, correct_choice))
+}
@@ -0,0 +1,217 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, AuthVia, RequestUser, Scope},
+    db::{
+        fetch_card_revision, fetch_card_revisions, fetch_card_row_by_id, index_card,
+        record_card_revision, NewCardRevision,
+    },
+    diff::{diff_lines, DiffOp},
+    models::{ApiResponse, Card, CardRevision, RevisionSource},
+};
+
+#[derive(Deserialize)]
+pub struct RevisionDiffParams {
+    from: i64,
+    /// Defaults to the latest revision.
+    to: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct RevisionDiff {
+    from: i64,
+    to: i64,
+    title: Vec<DiffOp>,
+    contents: Vec<DiffOp>,
+}
+
+/// Who is making a change and through which path, for `card_revisions`.
+/// `None` only when the route is not behind `require_write_auth`.
+pub(crate) fn revision_author(user: Option<&RequestUser>) -> (Option<i64>, RevisionSource) {
+    match user {
+        Some(user) => (
+            Some(user.user.user_id),
+            match user.via {
+                AuthVia::Session => RevisionSource::Ui,
+                AuthVia::ApiToken => RevisionSource::ApiToken,
+            },
+        ),
+        None => (None, RevisionSource::Ui),
+    }
+}
+
+/// Revisions of a card, newest first.
+pub async fn get_card_revisions(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Path(card_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> ApiResponse<Vec<CardRevision>> {
+    if let Err(response) = check_card_visible(&pool, &access_cache, card_id, &auth, &headers).await
+    {
+        return response;
+    }
+
+    match fetch_card_revisions(&pool, card_id).await {
+        Ok(revisions) => ApiResponse::new_ok(StatusCode::OK, revisions),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Line diff of the title and contents between two revisions of a card.
+pub async fn diff_card_revisions(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Path(card_id): Path<i64>,
+    Query(params): Query<RevisionDiffParams>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> ApiResponse<RevisionDiff> {
+    if let Err(response) = check_card_visible(&pool, &access_cache, card_id, &auth, &headers).await
+    {
+        return response;
+    }
+
+    let from = match fetch_card_revision(&pool, card_id, params.from).await {
+        Ok(Some(revision)) => revision,
+        Ok(None) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "revision not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let to = match params.to {
+        Some(to) => fetch_card_revision(&pool, card_id, to).await,
+        None => fetch_card_revisions(&pool, card_id)
+            .await
+            .map(|revisions| revisions.into_iter().next()),
+    };
+    let to = match to {
+        Ok(Some(revision)) => revision,
+        Ok(None) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "revision not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        RevisionDiff {
+            from: from.id,
+            to: to.id,
+            title: diff_lines(&from.title, &to.title),
+            contents: diff_lines(&from.contents, &to.contents),
+        },
+    )
+}
+
+/// Put a revision's title and contents back on the card. This is itself a
+/// change, so it adds a revision pointing at the restored one.
+pub async fn restore_card_revision(
+    Path((card_id, revision_id)): Path<(i64, i64)>,
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+) -> ApiResponse<Card> {
+    match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => {
+            if let Err((status, message)) = access.check_write(&[card_id]) {
+                return ApiResponse::new_err(status, message);
+            }
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let revision = match fetch_card_revision(&mut *tx, card_id, revision_id).await {
+        Ok(Some(revision)) => revision,
+        Ok(None) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "revision not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let (user_id, source) = revision_author(user.as_deref());
+    let result = async {
+        // Trashed cards have to be restored from the trash first.
+        let res = sqlx::query(
+            "UPDATE cards SET title = ?, contents = ?, version = version + 1 \
+             WHERE id = ? AND deleted_at IS NULL",
+        )
+        .bind(&revision.title)
+        .bind(&revision.contents)
+        .bind(card_id)
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok(false);
+        }
+        index_card(&mut tx, card_id, &revision.title, &revision.contents).await?;
+        record_card_revision(
+            &mut tx,
+            &NewCardRevision {
+                card_id,
+                title: &revision.title,
+                contents: &revision.contents,
+                user_id,
+                source,
+                restored_from: Some(revision.id),
+            },
+        )
+        .await?;
+        Ok::<_, sqlx::Error>(true)
+    }
+    .await;
+    match result {
+        Ok(true) => {}
+        Ok(false) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "card not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+    if let Err(e) = tx.commit().await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    match fetch_card_row_by_id(&pool, card_id).await {
+        Ok(row) => {
+            let version = row.version;
+            ApiResponse::new_ok(StatusCode::OK, Card::from(row)).with_etag(version)
+        }
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// The history of cards the viewer may not read is hidden, as a missing
+/// card.
+async fn check_card_visible<T>(
+    pool: &Pool<MySql>,
+    access_cache: &AccessCache,
+    card_id: i64,
+    auth: &AuthState,
+    headers: &HeaderMap,
+) -> Result<(), ApiResponse<T>> {
+    let viewer = auth.card_reader(headers, Scope::Read).await;
+    let access = match CardAccess::load(access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => {
+            return Err(ApiResponse::new_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.to_string(),
+            ))
+        }
+    };
+    match fetch_card_row_by_id(pool, card_id).await {
+        Ok(card) if access.can_read(card.id) => Ok(()),
+        Ok(_) | Err(sqlx::Error::RowNotFound) => Err(ApiResponse::new_err(
+            StatusCode::NOT_FOUND,
+            "card not found",
+        )),
+        Err(e) => Err(ApiResponse::new_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
+    }
+}
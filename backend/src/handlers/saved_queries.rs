@@ -0,0 +1,164 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, Scope},
+    card_query::CardQuery,
+    db::{
+        count_smart_frames_using, delete_saved_query, fetch_card_rows_by_query,
+        fetch_saved_queries, fetch_saved_query, insert_saved_query, update_saved_query,
+    },
+    models::{ApiResponse, Card, CardRow, SavedQuery, SavedQueryParams},
+};
+
+const MAX_NAME_CHARS: usize = 100;
+
+pub async fn get_saved_queries(
+    Extension(pool): Extension<Pool<MySql>>,
+) -> ApiResponse<Vec<SavedQuery>> {
+    match fetch_saved_queries(&pool).await {
+        Ok(queries) => ApiResponse::new_ok(StatusCode::OK, queries),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn create_saved_query(
+    Extension(pool): Extension<Pool<MySql>>,
+    Json(params): Json<SavedQueryParams>,
+) -> ApiResponse<SavedQuery> {
+    let name = match validate(&params) {
+        Ok(name) => name,
+        Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+    };
+
+    let saved_query_id = match insert_saved_query(&pool, name, &params.query).await {
+        Ok(saved_query_id) => saved_query_id,
+        Err(e) => return write_error(e),
+    };
+    match fetch_saved_query(&pool, saved_query_id).await {
+        Ok(Some(saved_query)) => ApiResponse::new_ok(StatusCode::CREATED, saved_query),
+        Ok(None) => ApiResponse::new_err(StatusCode::NOT_FOUND, "saved query not found"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn update_saved_query_by_id(
+    Path(saved_query_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Json(params): Json<SavedQueryParams>,
+) -> ApiResponse<SavedQuery> {
+    let name = match validate(&params) {
+        Ok(name) => name,
+        Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+    };
+
+    match update_saved_query(&pool, saved_query_id, name, &params.query).await {
+        Ok(true) => {}
+        Ok(false) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "saved query not found"),
+        Err(e) => return write_error(e),
+    }
+    match fetch_saved_query(&pool, saved_query_id).await {
+        Ok(Some(saved_query)) => ApiResponse::new_ok(StatusCode::OK, saved_query),
+        Ok(None) => ApiResponse::new_err(StatusCode::NOT_FOUND, "saved query not found"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Refused while a smart frame still uses the query.
+pub async fn delete_saved_query_by_id(
+    Path(saved_query_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+) -> ApiResponse<()> {
+    match count_smart_frames_using(&pool, saved_query_id).await {
+        Ok(0) => {}
+        Ok(count) => {
+            return ApiResponse::new_err(
+                StatusCode::CONFLICT,
+                format!("saved query is used by {count} smart frame(s)"),
+            )
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    match delete_saved_query(&pool, saved_query_id).await {
+        Ok(true) => ApiResponse::new_ok(StatusCode::OK, ()),
+        Ok(false) => ApiResponse::new_err(StatusCode::NOT_FOUND, "saved query not found"),
+        Err(e) => write_error(e),
+    }
+}
+
+/// Current results of a saved query.
+pub async fn get_saved_query_cards(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Path(saved_query_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> ApiResponse<Vec<Card>> {
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    match run_saved_query(&pool, saved_query_id).await {
+        Ok(rows) => {
+            let cards = rows
+                .into_iter()
+                .map(Card::from)
+                .filter(|c| access.can_read(c.id))
+                .collect();
+            ApiResponse::new_ok(StatusCode::OK, cards)
+        }
+        Err((status, message)) => ApiResponse::new_err(status, message),
+    }
+}
+
+/// Cards matching the saved query, unfiltered by access.
+pub(crate) async fn run_saved_query(
+    pool: &Pool<MySql>,
+    saved_query_id: i64,
+) -> Result<Vec<CardRow>, (StatusCode, String)> {
+    let saved_query = fetch_saved_query(pool, saved_query_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "saved query not found".to_string()))?;
+    // Queries are checked when saved, so this only fails if the syntax has
+    // changed since.
+    let card_query = CardQuery::parse(&saved_query.query).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("saved query {}: {e}", saved_query.id),
+        )
+    })?;
+
+    fetch_card_rows_by_query(pool, &card_query)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// The trimmed name, once the name and query are valid.
+fn validate(params: &SavedQueryParams) -> Result<&str, String> {
+    let name = params.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!("name must be 1 to {MAX_NAME_CHARS} characters"));
+    }
+    CardQuery::parse(&params.query).map_err(|e| format!("query: {e}"))?;
+    Ok(name)
+}
+
+fn write_error<T>(e: sqlx::Error) -> ApiResponse<T> {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            ApiResponse::new_err(StatusCode::CONFLICT, "a saved query with this name exists")
+        }
+        sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+            ApiResponse::new_err(StatusCode::CONFLICT, "saved query is used by a smart frame")
+        }
+        e => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
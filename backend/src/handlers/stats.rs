@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension,
+};
+use chrono::{Days, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, MySql, Pool, QueryBuilder};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, Scope},
+    models::ApiResponse,
+};
+
+const DEFAULT_STATS_DAYS: i64 = 30;
+
+/// Widest window `days` may ask for (about ten years).
+const MAX_STATS_DAYS: i64 = 3650;
+
+#[derive(Deserialize)]
+pub struct ReviewStatsQuery {
+    tag: Option<String>,
+    parent_id: Option<i64>,
+    /// `tag` or `parent`: one stats entry per tag / parent card.
+    group_by: Option<String>,
+    /// Size of the window for totals and `per_day`, in days.
+    days: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ReviewStats {
+    tag: Option<String>,
+    parent_id: Option<i64>,
+    total: i64,
+    correct: i64,
+    retention_rate: f64,
+    current_streak: i64,
+    longest_streak: i64,
+    per_day: Vec<DailyReviews>,
+}
+
+#[derive(Serialize)]
+pub struct DailyReviews {
+    date: NaiveDate,
+    reviews: i64,
+    correct: i64,
+}
+
+#[derive(FromRow)]
+struct DailyReviewRow {
+    card_id: i64,
+    group_tag: Option<String>,
+    group_parent_id: Option<i64>,
+    day: NaiveDate,
+    reviews: i64,
+    correct: i64,
+}
+
+pub async fn get_review_stats(
+    State(auth): State<AuthState>,
+    Query(params): Query<ReviewStatsQuery>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+) -> ApiResponse<Vec<ReviewStats>> {
+    let viewer = auth.card_reader(&headers, Scope::Flashcards).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let group_by = match params.group_by.as_deref() {
+        None | Some("") => None,
+        Some(value @ ("tag" | "parent")) => Some(value),
+        Some(_) => {
+            return ApiResponse::new_err(StatusCode::BAD_REQUEST, "group_by must be tag or parent")
+        }
+    };
+
+    let days = params.days.unwrap_or(DEFAULT_STATS_DAYS);
+    if !(1..=MAX_STATS_DAYS).contains(&days) {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("days must be 1 to {MAX_STATS_DAYS}"),
+        );
+    }
+    let today = Utc::now().date_naive();
+    let Some(since) = today.checked_sub_days(Days::new(days as u64 - 1)) else {
+        return ApiResponse::new_err(StatusCode::BAD_REQUEST, "days reaches before the calendar");
+    };
+
+    let rows = match fetch_daily_reviews(&pool, &params, group_by).await {
+        Ok(rows) => rows,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    // Rows are per card, so reviews of cards the user may not read (and
+    // parent groups they may not see) are left out before adding up.
+    let mut groups: BTreeMap<(Option<String>, Option<i64>), Vec<DailyReviews>> = BTreeMap::new();
+    let readable = rows.into_iter().filter(|row| {
+        access.can_read(row.card_id) && row.group_parent_id.is_none_or(|id| access.can_read(id))
+    });
+    for row in readable {
+        let days = groups
+            .entry((row.group_tag, row.group_parent_id))
+            .or_default();
+        match days.last_mut() {
+            Some(last) if last.date == row.day => {
+                last.reviews += row.reviews;
+                last.correct += row.correct;
+            }
+            _ => days.push(DailyReviews {
+                date: row.day,
+                reviews: row.reviews,
+                correct: row.correct,
+            }),
+        }
+    }
+    if group_by.is_none() && groups.is_empty() {
+        groups.insert((None, None), Vec::new());
+    }
+
+    let stats = groups
+        .into_iter()
+        .map(|((tag, parent_id), days)| summarize(tag, parent_id, days, since, today))
+        .collect();
+
+    ApiResponse::new_ok(StatusCode::OK, stats)
+}
+
+async fn fetch_daily_reviews(
+    pool: &Pool<MySql>,
+    params: &ReviewStatsQuery,
+    group_by: Option<&str>,
+) -> Result<Vec<DailyReviewRow>, sqlx::Error> {
+    let (group_columns, group_join) = match group_by {
+        Some("tag") => (
+            "gt.name AS group_tag, CAST(NULL AS SIGNED) AS group_parent_id",
+            "INNER JOIN card_tag gct ON gct.card_id = r.card_id \
+             INNER JOIN tags gt ON gt.id = gct.tag_id",
+        ),
+        Some("parent") => (
+            "CAST(NULL AS CHAR) AS group_tag, gcc.card_parent_id AS group_parent_id",
+            "INNER JOIN card_card gcc ON gcc.card_child_id = r.card_id",
+        ),
+        _ => (
+            "CAST(NULL AS CHAR) AS group_tag, CAST(NULL AS SIGNED) AS group_parent_id",
+            "",
+        ),
+    };
+
+    let mut query = QueryBuilder::<MySql>::new(format!(
+        "SELECT r.card_id, {group_columns}, DATE(r.reviewed_at) AS day, \
+         COUNT(*) AS reviews, CAST(SUM(r.is_ok) AS SIGNED) AS correct \
+         FROM card_reviews r \
+         {group_join} \
+         WHERE TRUE"
+    ));
+
+    if let Some(tag) = &params.tag {
+        query
+            .push(
+                " AND r.card_id IN (SELECT ct.card_id FROM card_tag ct \
+                 INNER JOIN tags t ON t.id = ct.tag_id WHERE t.name = ",
+            )
+            .push_bind(tag)
+            .push(")");
+    }
+    if let Some(parent_id) = params.parent_id {
+        query
+            .push(" AND r.card_id IN (SELECT card_child_id FROM card_card WHERE card_parent_id = ")
+            .push_bind(parent_id)
+            .push(")");
+    }
+
+    query.push(" GROUP BY r.card_id, group_tag, group_parent_id, day ORDER BY day ASC");
+
+    query
+        .build_query_as::<DailyReviewRow>()
+        .fetch_all(pool)
+        .await
+}
+
+/// Totals and `per_day` cover `since..=today`; streaks use the whole history.
+fn summarize(
+    tag: Option<String>,
+    parent_id: Option<i64>,
+    days: Vec<DailyReviews>,
+    since: NaiveDate,
+    today: NaiveDate,
+) -> ReviewStats {
+    let (current_streak, longest_streak) = streaks(&days, today);
+
+    let per_day: Vec<DailyReviews> = days.into_iter().filter(|d| d.date >= since).collect();
+    let total: i64 = per_day.iter().map(|d| d.reviews).sum();
+    let correct: i64 = per_day.iter().map(|d| d.correct).sum();
+    let retention_rate = if total > 0 {
+        correct as f64 / total as f64
+    } else {
+        0.0
+    };
+
+    ReviewStats {
+        tag,
+        parent_id,
+        total,
+        correct,
+        retention_rate,
+        current_streak,
+        longest_streak,
+        per_day,
+    }
+}
+
+/// Consecutive days with at least one review. The current streak is still
+/// alive if the last review was yesterday.
+fn streaks(days: &[DailyReviews], today: NaiveDate) -> (i64, i64) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+
+    for day in days.iter().filter(|d| d.reviews > 0) {
+        run = match previous {
+            Some(prev) if day.date - prev == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day.date);
+    }
+
+    let current = match previous {
+        Some(last) if today - last <= Duration::days(1) => run,
+        _ => 0,
+    };
+
+    (current, longest)
+}
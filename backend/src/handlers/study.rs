@@ -0,0 +1,222 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, RequestUser, Scope},
+    db::{
+        delete_study_mode, fetch_card_row_by_id, fetch_study_mode, fetch_tag_id, upsert_study_mode,
+    },
+    handlers::flash_card::{fetch_scoped_flash_cards, FlashCardQuery},
+    models::ApiResponse,
+    scheduler::{LeitnerBoxes, Scheduler},
+};
+
+/// A study mode is set on exactly one tag or one frame.
+#[derive(Deserialize)]
+pub struct StudyModeTarget {
+    tag: Option<String>,
+    parent_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct StudyModeParams {
+    tag: Option<String>,
+    parent_id: Option<i64>,
+    /// `sm2`, `fsrs` or `leitner`. `null` falls back to the server default.
+    mode: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct StudyMode {
+    tag: Option<String>,
+    parent_id: Option<i64>,
+    mode: &'static str,
+    /// True when nothing is set for the target and the server default applies.
+    is_default: bool,
+}
+
+#[derive(Serialize)]
+pub struct LeitnerOverview {
+    date: NaiveDate,
+    /// Boxes that come up for review today.
+    due_boxes: Vec<i32>,
+    boxes: Vec<LeitnerBox>,
+}
+
+#[derive(Serialize)]
+pub struct LeitnerBox {
+    #[serde(rename = "box")]
+    leitner_box: i32,
+    every_days: i64,
+    due_today: bool,
+    /// Review items currently in the box.
+    items: usize,
+    /// Of those, items whose due date has passed.
+    due: usize,
+}
+
+pub async fn get_study_mode(
+    Query(params): Query<StudyModeTarget>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(scheduler): Extension<Scheduler>,
+) -> ApiResponse<StudyMode> {
+    if params.tag.is_some() == params.parent_id.is_some() {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            "either tag or parent_id is required",
+        );
+    }
+
+    let tags: Vec<String> = params.tag.iter().cloned().collect();
+    let mode = match fetch_study_mode(&pool, params.parent_id, &tags).await {
+        Ok(mode) => mode.as_deref().and_then(Scheduler::parse),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        StudyMode {
+            tag: params.tag,
+            parent_id: params.parent_id,
+            mode: mode.unwrap_or(scheduler).as_str(),
+            is_default: mode.is_none(),
+        },
+    )
+}
+
+pub async fn set_study_mode(
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(scheduler): Extension<Scheduler>,
+    user: Option<Extension<RequestUser>>,
+    Json(params): Json<StudyModeParams>,
+) -> ApiResponse<StudyMode> {
+    if params.tag.is_some() == params.parent_id.is_some() {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            "either tag or parent_id is required",
+        );
+    }
+    let mode = match params.mode.as_deref().map(Scheduler::parse) {
+        Some(None) => {
+            return ApiResponse::new_err(
+                StatusCode::BAD_REQUEST,
+                "mode must be sm2, fsrs or leitner",
+            )
+        }
+        Some(Some(mode)) => Some(mode),
+        None => None,
+    };
+
+    let tag_id = match &params.tag {
+        Some(name) => match fetch_tag_id(&pool, name).await {
+            Ok(Some(id)) => Some(id),
+            Ok(None) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "tag not found"),
+            Err(e) => {
+                return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        },
+        None => None,
+    };
+    if let Some(parent_id) = params.parent_id {
+        match fetch_card_row_by_id(&pool, parent_id).await {
+            Ok(_) => {}
+            Err(sqlx::Error::RowNotFound) => {
+                return ApiResponse::new_err(StatusCode::NOT_FOUND, "card not found")
+            }
+            Err(e) => {
+                return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        }
+        match CardAccess::for_request(&pool, user.as_deref()).await {
+            Ok(access) => {
+                if let Err((status, message)) = access.check_write(&[parent_id]) {
+                    return ApiResponse::new_err(status, message);
+                }
+            }
+            Err(e) => {
+                return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        }
+    }
+
+    let result = match mode {
+        Some(mode) => upsert_study_mode(&pool, tag_id, params.parent_id, mode.as_str()).await,
+        None => delete_study_mode(&pool, tag_id, params.parent_id).await,
+    };
+    if let Err(e) = result {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        StudyMode {
+            tag: params.tag,
+            parent_id: params.parent_id,
+            mode: mode.unwrap_or(scheduler).as_str(),
+            is_default: mode.is_none(),
+        },
+    )
+}
+
+/// Leitner boxes of the review items in a flash-card scope, and which of
+/// them come up today.
+pub async fn get_leitner_boxes(
+    State(auth): State<AuthState>,
+    Query(params): Query<FlashCardQuery>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+    Extension(boxes): Extension<LeitnerBoxes>,
+) -> ApiResponse<LeitnerOverview> {
+    let scope = match params.scope() {
+        Ok(scope) => scope,
+        Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e),
+    };
+    let viewer = auth.card_reader(&headers, Scope::Flashcards).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let cards = match fetch_scoped_flash_cards(&pool, &scope, &access).await {
+        Ok(cards) => cards,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let now = Utc::now().naive_utc();
+    let today = now.date();
+    let due_boxes = boxes.due_boxes(today);
+
+    let overview = (1..=boxes.count())
+        .map(|leitner_box| {
+            let in_box = cards
+                .iter()
+                .filter(|card| card.leitner_box.clamp(1, boxes.count()) == leitner_box);
+            LeitnerBox {
+                leitner_box,
+                every_days: boxes.period(leitner_box),
+                due_today: due_boxes.contains(&leitner_box),
+                items: in_box.clone().count(),
+                due: in_box
+                    .filter(|card| card.due_at.is_none_or(|due_at| due_at <= now))
+                    .count(),
+            }
+        })
+        .collect();
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        LeitnerOverview {
+            date: today,
+            due_boxes,
+            boxes: overview,
+        },
+    )
+}
@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    acl::CardAccess,
+    auth::{Access, RequestUser},
+    db::{
+        complete_study_session_current, fetch_session_answers, fetch_session_totals,
+        fetch_study_session, finish_study_session, insert_study_session, lock_study_session,
+        set_study_session_current, NewCardReview,
+    },
+    handlers::flash_card::{
+        fetch_scoped_flash_cards, record_review, serialize_naive_datetime_as_utc,
+        serialize_optional_naive_datetime_as_utc, FlashCard, FlashCardQuery, FlashCardScope,
+    },
+    models::{ApiResponse, StudySessionRow},
+    scheduler::{LeitnerBoxes, Scheduler},
+};
+
+const DEFAULT_NEW_LIMIT: i32 = 20;
+const DEFAULT_LEARNING_LIMIT: i32 = 50;
+const DEFAULT_REVIEW_LIMIT: i32 = 200;
+
+/// Queue a review item is served from.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum StudyQueue {
+    /// Failed last time (or earlier in this session) and not yet relearned.
+    Learning,
+    /// Learned and due.
+    Review,
+    /// Never reviewed.
+    New,
+}
+
+impl StudyQueue {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "learning" => Some(Self::Learning),
+            "review" => Some(Self::Review),
+            "new" => Some(Self::New),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Learning => "learning",
+            Self::Review => "review",
+            Self::New => "new",
+        }
+    }
+}
+
+/// Per-queue limits of a new session, as an optional JSON body. Omitted
+/// limits use the defaults.
+#[derive(Deserialize, Default)]
+pub struct StudySessionLimits {
+    new_limit: Option<i32>,
+    learning_limit: Option<i32>,
+    review_limit: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct StudyAnswer {
+    id: i64,
+    /// Review item that was answered. Defaults to the item being served.
+    item: Option<String>,
+    #[serde(rename = "is_OK")]
+    is_ok: bool,
+    /// Time spent on the card. Defaults to the time since it was served.
+    elapsed_ms: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct QueueCounts {
+    new: i32,
+    learning: i32,
+    review: i32,
+}
+
+#[derive(Serialize)]
+pub struct StudySessionSummary {
+    id: i64,
+    #[serde(serialize_with = "serialize_naive_datetime_as_utc")]
+    started_at: NaiveDateTime,
+    #[serde(serialize_with = "serialize_optional_naive_datetime_as_utc")]
+    finished_at: Option<NaiveDateTime>,
+    /// Answers per queue so far.
+    answered: QueueCounts,
+    limits: QueueCounts,
+    total: i64,
+    correct: i64,
+    accuracy: f64,
+    total_ms: i64,
+    average_ms: f64,
+}
+
+#[derive(Serialize)]
+pub struct StudyNext {
+    /// Null once nothing is left to serve; the session is then finished and
+    /// `summary` is final.
+    card: Option<FlashCard>,
+    queue: Option<&'static str>,
+    summary: StudySessionSummary,
+}
+
+#[derive(Serialize)]
+pub struct StudyAnswerResult {
+    id: i64,
+    item: String,
+    #[serde(rename = "is_OK")]
+    is_ok: bool,
+    #[serde(
+        rename = "due",
+        serialize_with = "serialize_optional_naive_datetime_as_utc"
+    )]
+    due_at: Option<NaiveDateTime>,
+    interval_days: f64,
+    #[serde(rename = "box")]
+    leitner_box: i32,
+    summary: StudySessionSummary,
+}
+
+/// Whether `user` may read and drive `session`: its owner and admins may.
+/// Everyone else is told the session does not exist.
+fn owns_session(session: &StudySessionRow, user: Option<&RequestUser>) -> bool {
+    user.is_some_and(|user| {
+        session.user_id == Some(user.user.user_id) || user.user.can(Access::Admin)
+    })
+}
+
+/// Start a session over the same scope parameters as `/cards/flush_json`.
+pub async fn start_study_session(
+    Query(params): Query<FlashCardQuery>,
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+    body: Bytes,
+) -> ApiResponse<StudySessionSummary> {
+    let Some(user) = user else {
+        return ApiResponse::new_err(StatusCode::UNAUTHORIZED, "authentication required");
+    };
+    if let Err(e) = params.scope() {
+        return ApiResponse::new_err(StatusCode::BAD_REQUEST, e);
+    }
+    let limits = if body.iter().all(u8::is_ascii_whitespace) {
+        StudySessionLimits::default()
+    } else {
+        match serde_json::from_slice::<StudySessionLimits>(&body) {
+            Ok(limits) => limits,
+            Err(e) => return ApiResponse::new_err(StatusCode::BAD_REQUEST, e.to_string()),
+        }
+    };
+    let new_limit = limits.new_limit.unwrap_or(DEFAULT_NEW_LIMIT).max(0);
+    let learning_limit = limits
+        .learning_limit
+        .unwrap_or(DEFAULT_LEARNING_LIMIT)
+        .max(0);
+    let review_limit = limits.review_limit.unwrap_or(DEFAULT_REVIEW_LIMIT).max(0);
+
+    let scope = match serde_json::to_string(&params) {
+        Ok(scope) => scope,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let session = async {
+        let id = insert_study_session(
+            &pool,
+            user.user.user_id,
+            &scope,
+            new_limit,
+            learning_limit,
+            review_limit,
+        )
+        .await?;
+        fetch_study_session(&pool, id).await
+    }
+    .await;
+
+    match session {
+        Ok(Some(session)) => match summarize(&pool, &session).await {
+            Ok(summary) => ApiResponse::new_ok(StatusCode::CREATED, summary),
+            Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        Ok(None) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, "session not created"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn get_study_session(
+    Path(session_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+) -> ApiResponse<StudySessionSummary> {
+    let session = match fetch_study_session(&pool, session_id).await {
+        Ok(Some(session)) if owns_session(&session, user.as_deref()) => session,
+        Ok(_) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "session not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    match summarize(&pool, &session).await {
+        Ok(summary) => ApiResponse::new_ok(StatusCode::OK, summary),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// The review item to answer next. Asking again before answering returns the
+/// same item.
+pub async fn next_study_card(
+    Path(session_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+) -> ApiResponse<StudyNext> {
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let session = match lock_study_session(&mut *tx, session_id).await {
+        Ok(Some(session)) if owns_session(&session, user.as_deref()) => session,
+        Ok(_) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "session not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if session.finished_at.is_some() {
+        return ApiResponse::new_err(StatusCode::CONFLICT, "session is finished");
+    }
+    let scope = match session_scope(&session) {
+        Ok(scope) => scope,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+
+    // Only cards the user may read now; an ACL may have changed since the
+    // session started.
+    let access = match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let cards = match fetch_scoped_flash_cards(&pool, &scope, &access).await {
+        Ok(cards) => cards,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let answers = match fetch_session_answers(&mut *tx, session_id).await {
+        Ok(answers) => answers,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let now = Utc::now().naive_utc();
+    let current = current_card(&session, &cards);
+    let is_current = current.is_some();
+    let next = match current {
+        Some(next) => Some(next),
+        None => {
+            let last_results: HashMap<(i64, &str), bool> = answers
+                .iter()
+                .map(|answer| ((answer.card_id, answer.item_key.as_str()), answer.is_ok))
+                .collect();
+            pick_card(&session, cards, &last_results, now)
+        }
+    };
+
+    let result = match &next {
+        Some(_) if is_current => Ok(()),
+        Some((card, queue)) => {
+            set_study_session_current(
+                &mut *tx,
+                session_id,
+                card.id,
+                &card.item,
+                queue.as_str(),
+                now,
+            )
+            .await
+        }
+        None => finish_study_session(&mut *tx, session_id, now).await,
+    };
+    if let Err(e) = result {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    if let Err(e) = tx.commit().await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    let summary = match reload_summary(&pool, session_id).await {
+        Ok(summary) => summary,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let (card, queue) = match next {
+        Some((card, queue)) => (Some(card), Some(queue.as_str())),
+        None => (None, None),
+    };
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        StudyNext {
+            card,
+            queue,
+            summary,
+        },
+    )
+}
+
+/// Answer the item being served. The answer is recorded like a
+/// `/cards/flush_json` result, tagged with the session.
+pub async fn answer_study_card(
+    Path(session_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(scheduler): Extension<Scheduler>,
+    Extension(boxes): Extension<LeitnerBoxes>,
+    user: Option<Extension<RequestUser>>,
+    Json(answer): Json<StudyAnswer>,
+) -> ApiResponse<StudyAnswerResult> {
+    match CardAccess::for_request(&pool, user.as_deref()).await {
+        Ok(access) => {
+            if let Err((status, message)) = access.check_read(answer.id) {
+                return ApiResponse::new_err(status, message);
+            }
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let session = match lock_study_session(&mut *tx, session_id).await {
+        Ok(Some(session)) if owns_session(&session, user.as_deref()) => session,
+        Ok(_) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "session not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if session.finished_at.is_some() {
+        return ApiResponse::new_err(StatusCode::CONFLICT, "session is finished");
+    }
+    let (Some(card_id), Some(item_key)) = (session.current_card_id, &session.current_item_key)
+    else {
+        return ApiResponse::new_err(StatusCode::CONFLICT, "no card is being served");
+    };
+    if answer.id != card_id || answer.item.as_ref().is_some_and(|item| item != item_key) {
+        return ApiResponse::new_err(
+            StatusCode::CONFLICT,
+            "answer does not match the card being served",
+        );
+    }
+    let scope = match session_scope(&session) {
+        Ok(scope) => scope,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    let scheduler = match scope.scheduler(&mut *tx, scheduler).await {
+        Ok(scheduler) => scheduler,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let now = Utc::now().naive_utc();
+    let elapsed_ms = answer.elapsed_ms.map(|ms| ms.max(0)).or_else(|| {
+        session.served_at.map(|served_at| {
+            (now - served_at)
+                .num_milliseconds()
+                .clamp(0, i32::MAX as i64) as i32
+        })
+    });
+    let scope_tag = scope.review_tag();
+    let review = NewCardReview {
+        card_id,
+        item_key,
+        reviewed_at: now,
+        is_ok: answer.is_ok,
+        response_ms: elapsed_ms,
+        scope_tag: scope_tag.as_deref(),
+        scope_parent_id: scope.parent_id,
+        session_id: Some(session_id),
+    };
+
+    let schedule = match record_review(&mut tx, scheduler, &boxes, &review).await {
+        Ok(schedule) => schedule,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if let Err(e) = complete_study_session_current(&mut *tx, session_id).await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    if let Err(e) = tx.commit().await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    let summary = match reload_summary(&pool, session_id).await {
+        Ok(summary) => summary,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        StudyAnswerResult {
+            id: card_id,
+            item: item_key.clone(),
+            is_ok: answer.is_ok,
+            due_at: schedule.due_at,
+            interval_days: schedule.interval_days,
+            leitner_box: schedule.leitner_box,
+            summary,
+        },
+    )
+}
+
+/// End a session early. Finishing a finished session returns its summary.
+pub async fn finish_study(
+    Path(session_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+) -> ApiResponse<StudySessionSummary> {
+    match fetch_study_session(&pool, session_id).await {
+        Ok(Some(session)) if owns_session(&session, user.as_deref()) => {}
+        Ok(_) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "session not found"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+    if let Err(e) = finish_study_session(&pool, session_id, Utc::now().naive_utc()).await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    match fetch_study_session(&pool, session_id).await {
+        Ok(Some(session)) => match summarize(&pool, &session).await {
+            Ok(summary) => ApiResponse::new_ok(StatusCode::OK, summary),
+            Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        Ok(None) => ApiResponse::new_err(StatusCode::NOT_FOUND, "session not found"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Scope of a session. `due` is ignored: items failed in the session are
+/// served again although they are not due.
+fn session_scope(session: &StudySessionRow) -> Result<FlashCardScope, String> {
+    let mut scope = serde_json::from_str::<FlashCardQuery>(&session.scope)
+        .map_err(|e| e.to_string())?
+        .scope()?;
+    scope.due = false;
+    Ok(scope)
+}
+
+/// The item already being served, if it is still part of the scope.
+fn current_card(session: &StudySessionRow, cards: &[FlashCard]) -> Option<(FlashCard, StudyQueue)> {
+    let card_id = session.current_card_id?;
+    let item_key = session.current_item_key.as_deref()?;
+    let queue = StudyQueue::parse(session.current_queue.as_deref()?)?;
+    cards
+        .iter()
+        .find(|card| card.id == card_id && card.item == item_key)
+        .map(|card| (card.clone(), queue))
+}
+
+/// Next item to serve: items not yet seen in this session first (learning,
+/// then review, then new), then items failed in this session. Items answered
+/// correctly in this session and queues at their limit are skipped.
+fn pick_card(
+    session: &StudySessionRow,
+    cards: Vec<FlashCard>,
+    last_results: &HashMap<(i64, &str), bool>,
+    now: NaiveDateTime,
+) -> Option<(FlashCard, StudyQueue)> {
+    cards
+        .into_iter()
+        .filter_map(|card| {
+            let last_result = last_results.get(&(card.id, card.item.as_str())).copied();
+            let queue = match (last_result, card.due_at) {
+                (Some(true), _) => return None,
+                (Some(false), _) => StudyQueue::Learning,
+                (None, None) => StudyQueue::New,
+                (None, Some(due_at)) if due_at > now => return None,
+                (None, Some(_)) if card.reps == 0 => StudyQueue::Learning,
+                (None, Some(_)) => StudyQueue::Review,
+            };
+            let (count, limit) = match queue {
+                StudyQueue::Learning => (session.learning_count, session.learning_limit),
+                StudyQueue::Review => (session.review_count, session.review_limit),
+                StudyQueue::New => (session.new_count, session.new_limit),
+            };
+            (count < limit).then_some((last_result.is_some(), queue, card))
+        })
+        .min_by(|(a_seen, a_queue, a), (b_seen, b_queue, b)| {
+            (a_seen, a_queue, a.due_at, a.id, &a.item)
+                .cmp(&(b_seen, b_queue, b.due_at, b.id, &b.item))
+        })
+        .map(|(_, queue, card)| (card, queue))
+}
+
+async fn reload_summary(
+    pool: &Pool<MySql>,
+    session_id: i64,
+) -> Result<StudySessionSummary, sqlx::Error> {
+    let session = fetch_study_session(pool, session_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+    summarize(pool, &session).await
+}
+
+async fn summarize(
+    pool: &Pool<MySql>,
+    session: &StudySessionRow,
+) -> Result<StudySessionSummary, sqlx::Error> {
+    let totals = fetch_session_totals(pool, session.id).await?;
+    let ratio = |value: i64| {
+        if totals.answered == 0 {
+            0.0
+        } else {
+            value as f64 / totals.answered as f64
+        }
+    };
+
+    Ok(StudySessionSummary {
+        id: session.id,
+        started_at: session.started_at,
+        finished_at: session.finished_at,
+        answered: QueueCounts {
+            new: session.new_count,
+            learning: session.learning_count,
+            review: session.review_count,
+        },
+        limits: QueueCounts {
+            new: session.new_limit,
+            learning: session.learning_limit,
+            review: session.review_limit,
+        },
+        total: totals.answered,
+        correct: totals.correct,
+        accuracy: ratio(totals.correct),
+        total_ms: totals.total_ms,
+        average_ms: ratio(totals.total_ms),
+    })
+}
@@ -0,0 +1,47 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension,
+};
+use serde::Deserialize;
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, Scope},
+    models::ApiResponse,
+    title_index::{TitleIndex, TitleMatch},
+};
+
+const DEFAULT_SWITCHER_LIMIT: usize = 10;
+const MAX_SWITCHER_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+pub struct SwitcherParams {
+    q: String,
+    limit: Option<usize>,
+}
+
+/// Cards whose title or first H1 fuzzily matches `q`, with absolute
+/// positions so the client can move the camera to them.
+pub async fn switch_cards(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(access_cache): Extension<AccessCache>,
+    Extension(index): Extension<TitleIndex>,
+    Query(params): Query<SwitcherParams>,
+) -> ApiResponse<Vec<TitleMatch>> {
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let access = match CardAccess::load(&access_cache, viewer).await {
+        Ok(access) => access,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SWITCHER_LIMIT)
+        .clamp(1, MAX_SWITCHER_LIMIT);
+
+    ApiResponse::new_ok(
+        StatusCode::OK,
+        index.search(&params.q, limit, |id| access.can_read(id)),
+    )
+}
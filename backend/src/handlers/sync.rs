@@ -0,0 +1,750 @@
+use std::collections::HashMap;
+
+use axum::{http::StatusCode, Extension, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool, Transaction};
+
+use crate::{
+    acl::CardAccess,
+    auth::RequestUser,
+    db::{
+        delete_card_relation, fetch_applied_sync_op, fetch_card_relation, fetch_card_relations,
+        fetch_card_row_by_id, index_card, insert_card, insert_card_relation, lock_card_version,
+        prune_applied_sync_ops, record_applied_sync_op, record_card_revision, set_card_tags,
+        stamp_changes, trash_card, update_card_row, NewCardRevision,
+    },
+    handlers::{
+        batch::CardRef,
+        card_card::has_cycle,
+        cards::{check_smart_frame, check_visibility},
+        changes::{changes_since, current_state, ChangeFeed, EntityStates},
+        revisions::revision_author,
+    },
+    models::{ApiResponse, Card, CardParams, RevisionSource, CARD_TYPE_SMART_FRAME},
+    schema::Dimmension,
+};
+
+/// Most operations accepted in one upload.
+const MAX_SYNC_OPS: usize = 500;
+
+/// Longest `op_id` (`sync_applied_ops.op_id`).
+const MAX_OP_ID_LEN: usize = 64;
+
+/// Days an applied `op_id` is remembered.
+const APPLIED_OPS_RETENTION_DAYS: i64 = 30;
+
+/// How far a conflict copy is placed from its card.
+const CONFLICT_COPY_OFFSET: f64 = 24.0;
+
+/// Longest card title (`cards.title`).
+const MAX_TITLE_CHARS: usize = 100;
+
+const CONFLICT_COPY_SUFFIX: &str = " (conflict copy)";
+
+#[derive(Deserialize)]
+pub struct SyncRequest {
+    /// `cursor` of the client's last `/changes`. When given, the response
+    /// also carries everything changed since.
+    since: Option<i64>,
+    ops: Vec<QueuedOp>,
+}
+
+#[derive(Deserialize)]
+pub struct QueuedOp {
+    /// Made by the client, unique per operation. An operation whose id was
+    /// already applied is not applied again.
+    op_id: String,
+    #[serde(flatten)]
+    op: SyncOp,
+}
+
+/// Fields of a card that can be edited offline.
+#[derive(Deserialize, Default)]
+pub struct CardFields {
+    position: Option<Dimmension>,
+    size: Option<Dimmension>,
+    title: Option<String>,
+    contents: Option<String>,
+}
+
+/// An operation made offline, against the card as it was at `base_version`.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SyncOp {
+    Create {
+        temp_id: Option<String>,
+        card: CardParams,
+    },
+    /// `changes` holds the edited fields, `base` their values before the
+    /// edit.
+    Update {
+        id: CardRef,
+        base_version: i32,
+        #[serde(default)]
+        base: CardFields,
+        changes: CardFields,
+    },
+    Delete {
+        id: CardRef,
+        base_version: i32,
+    },
+    Connect {
+        parent_id: CardRef,
+        child_id: CardRef,
+        connector: String,
+    },
+    Disconnect {
+        parent_id: CardRef,
+        child_id: CardRef,
+    },
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncOutcome {
+    /// Applied as sent; the card had not changed on the server.
+    Applied,
+    /// Applied on top of changes made on the server in the meantime.
+    Merged,
+    /// Title or contents were edited on both sides. The server's text stays
+    /// and the client's went to a new card, `conflict_copy_id`.
+    ConflictCopy,
+    /// Applied by an earlier upload.
+    Duplicate,
+    /// Not applied; `message` says why.
+    Skipped,
+}
+
+#[derive(Serialize)]
+pub struct SyncOpResult {
+    op_id: String,
+    outcome: SyncOutcome,
+    card_id: Option<i64>,
+    conflict_copy_id: Option<i64>,
+    message: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SyncResult {
+    /// One per operation, in the order they were sent.
+    results: Vec<SyncOpResult>,
+    /// Ids of the cards created for each `temp_id`.
+    temp_ids: HashMap<String, i64>,
+    /// The cards and relations the operations touched, as they are now.
+    #[serde(flatten)]
+    state: EntityStates,
+    changes: Option<ChangeFeed>,
+}
+
+/// How one operation was resolved.
+struct Resolution {
+    outcome: SyncOutcome,
+    card_id: Option<i64>,
+    conflict_copy_id: Option<i64>,
+    message: Option<String>,
+    /// Card the operation created, kept with its `op_id`.
+    created: Option<i64>,
+}
+
+impl Resolution {
+    fn new(outcome: SyncOutcome, card_id: Option<i64>) -> Self {
+        Self {
+            outcome,
+            card_id,
+            conflict_copy_id: None,
+            message: None,
+            created: None,
+        }
+    }
+
+    fn skipped(message: impl Into<String>) -> Self {
+        Self {
+            message: Some(message.into()),
+            ..Self::new(SyncOutcome::Skipped, None)
+        }
+    }
+}
+
+/// State shared by the operations of one upload.
+struct Sync<'c> {
+    tx: Transaction<'c, MySql>,
+    access: CardAccess,
+    temp_ids: HashMap<String, i64>,
+    user_id: Option<i64>,
+    source: RevisionSource,
+    touched_cards: Vec<i64>,
+    touched_relations: Vec<(i64, i64)>,
+}
+
+/// Apply a queue of operations made offline and return the rebased state.
+///
+/// Operations are applied in order, each resolved on its own instead of
+/// failing the upload:
+/// - position and size take the client's value;
+/// - title and contents take the client's value unless the card changed
+///   since `base_version` and the server's text is no longer `base`, in which
+///   case the server's text stays and the client's is saved as a conflict
+///   copy next to the card, under the same parents;
+/// - a delete is skipped when the card changed since `base_version`;
+/// - operations on cards deleted on the server are skipped, as are ones on
+///   cards the user may not write and ones the database rejects.
+pub async fn apply_sync(
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+    Json(request): Json<SyncRequest>,
+) -> ApiResponse<SyncResult> {
+    if request.ops.len() > MAX_SYNC_OPS {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("an upload takes at most {MAX_SYNC_OPS} operations"),
+        );
+    }
+    if request
+        .ops
+        .iter()
+        .any(|queued| queued.op_id.is_empty() || queued.op_id.len() > MAX_OP_ID_LEN)
+    {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("op_id must be 1 to {MAX_OP_ID_LEN} bytes"),
+        );
+    }
+
+    match sync(&pool, user.as_deref(), request).await {
+        Ok(result) => ApiResponse::new_ok(StatusCode::OK, result),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn sync(
+    pool: &Pool<MySql>,
+    user: Option<&RequestUser>,
+    request: SyncRequest,
+) -> Result<SyncResult, sqlx::Error> {
+    let (user_id, source) = revision_author(user);
+    let mut sync = Sync {
+        access: CardAccess::for_request(pool, user).await?,
+        tx: pool.begin().await?,
+        temp_ids: HashMap::new(),
+        user_id,
+        source,
+        touched_cards: Vec::new(),
+        touched_relations: Vec::new(),
+    };
+
+    let mut results = Vec::with_capacity(request.ops.len());
+    for queued in request.ops {
+        let resolution = sync.apply_or_skip(&queued.op_id, queued.op).await?;
+        results.push(SyncOpResult {
+            op_id: queued.op_id,
+            outcome: resolution.outcome,
+            card_id: resolution.card_id,
+            conflict_copy_id: resolution.conflict_copy_id,
+            message: resolution.message,
+        });
+    }
+    let Sync {
+        tx,
+        access,
+        temp_ids,
+        mut touched_cards,
+        mut touched_relations,
+        ..
+    } = sync;
+    tx.commit().await?;
+
+    if let Err(e) = prune_applied_sync_ops(pool, APPLIED_OPS_RETENTION_DAYS).await {
+        eprintln!("pruning applied sync ops failed: {e}");
+    }
+
+    touched_cards.sort_unstable();
+    touched_cards.dedup();
+    touched_relations.sort_unstable();
+    touched_relations.dedup();
+    let state = current_state(pool, touched_cards, touched_relations, Vec::new(), &access).await?;
+    let changes = match request.since {
+        Some(since) => {
+            stamp_changes(pool).await?;
+            Some(changes_since(pool, since, &access).await?)
+        }
+        None => None,
+    };
+
+    Ok(SyncResult {
+        results,
+        temp_ids,
+        state,
+        changes,
+    })
+}
+
+impl Sync<'_> {
+    /// `apply`, with an operation the database rejects (an unknown tag id,
+    /// say) undone and skipped instead of failing the whole upload.
+    async fn apply_or_skip(&mut self, op_id: &str, op: SyncOp) -> Result<Resolution, sqlx::Error> {
+        let new_temp_id = match &op {
+            SyncOp::Create {
+                temp_id: Some(temp_id),
+                ..
+            } if !self.temp_ids.contains_key(temp_id) => Some(temp_id.clone()),
+            _ => None,
+        };
+        let touched = (self.touched_cards.len(), self.touched_relations.len());
+
+        sqlx::query("SAVEPOINT sync_op")
+            .execute(&mut *self.tx)
+            .await?;
+        match self.apply(op_id, op).await {
+            Ok(resolution) => {
+                sqlx::query("RELEASE SAVEPOINT sync_op")
+                    .execute(&mut *self.tx)
+                    .await?;
+                Ok(resolution)
+            }
+            Err(sqlx::Error::Database(e)) => {
+                sqlx::query("ROLLBACK TO SAVEPOINT sync_op")
+                    .execute(&mut *self.tx)
+                    .await?;
+                if let Some(temp_id) = new_temp_id {
+                    self.temp_ids.remove(&temp_id);
+                }
+                self.touched_cards.truncate(touched.0);
+                self.touched_relations.truncate(touched.1);
+                Ok(Resolution::skipped(e.message()))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn apply(&mut self, op_id: &str, op: SyncOp) -> Result<Resolution, sqlx::Error> {
+        if let Some(created) = fetch_applied_sync_op(&mut *self.tx, self.user_id, op_id).await? {
+            return Ok(self.duplicate(&op, created));
+        }
+
+        let resolution = match op {
+            SyncOp::Create { temp_id, card } => self.create(temp_id, card).await?,
+            SyncOp::Update {
+                id,
+                base_version,
+                base,
+                changes,
+            } => match self.writable(&id) {
+                Ok(card_id) => self.update(card_id, base_version, base, changes).await?,
+                Err(resolution) => resolution,
+            },
+            SyncOp::Delete { id, base_version } => match self.writable(&id) {
+                Ok(card_id) => self.delete(card_id, base_version).await?,
+                Err(resolution) => resolution,
+            },
+            SyncOp::Connect {
+                parent_id,
+                child_id,
+                connector,
+            } => match (self.writable(&parent_id), self.writable(&child_id)) {
+                (Ok(parent_id), Ok(child_id)) => {
+                    self.connect(parent_id, child_id, &connector).await?
+                }
+                (Err(resolution), _) | (_, Err(resolution)) => resolution,
+            },
+            SyncOp::Disconnect {
+                parent_id,
+                child_id,
+            } => match (self.writable(&parent_id), self.writable(&child_id)) {
+                (Ok(parent_id), Ok(child_id)) => {
+                    delete_card_relation(&mut *self.tx, parent_id, child_id).await?;
+                    self.touched_relations.push((parent_id, child_id));
+                    Resolution::new(SyncOutcome::Applied, None)
+                }
+                (Err(resolution), _) | (_, Err(resolution)) => resolution,
+            },
+        };
+
+        if resolution.outcome != SyncOutcome::Skipped {
+            record_applied_sync_op(&mut *self.tx, self.user_id, op_id, resolution.created).await?;
+        }
+        Ok(resolution)
+    }
+
+    /// An operation applied by an earlier upload. `created` is the card it
+    /// created, if any.
+    fn duplicate(&mut self, op: &SyncOp, created: Option<i64>) -> Resolution {
+        match op {
+            SyncOp::Create { temp_id, .. } => {
+                if let (Some(temp_id), Some(card_id)) = (temp_id, created) {
+                    self.temp_ids.insert(temp_id.clone(), card_id);
+                }
+                if let Some(card_id) = created {
+                    self.touched_cards.push(card_id);
+                }
+                Resolution::new(SyncOutcome::Duplicate, created)
+            }
+            _ => Resolution {
+                conflict_copy_id: created,
+                ..Resolution::new(SyncOutcome::Duplicate, None)
+            },
+        }
+    }
+
+    fn resolve(&self, card: &CardRef) -> Result<i64, Resolution> {
+        match card {
+            CardRef::Id(id) => Ok(*id),
+            CardRef::Temp(temp_id) => self
+                .temp_ids
+                .get(temp_id)
+                .copied()
+                .ok_or_else(|| Resolution::skipped(format!("unknown temp_id {temp_id:?}"))),
+        }
+    }
+
+    /// `resolve`, for a card the user may write.
+    fn writable(&self, card: &CardRef) -> Result<i64, Resolution> {
+        let card_id = self.resolve(card)?;
+        self.access
+            .check_write(&[card_id])
+            .map(|()| card_id)
+            .map_err(|(_, message)| Resolution::skipped(message))
+    }
+
+    async fn create(
+        &mut self,
+        temp_id: Option<String>,
+        card: CardParams,
+    ) -> Result<Resolution, sqlx::Error> {
+        if let Some(temp_id) = &temp_id {
+            if self.temp_ids.contains_key(temp_id) {
+                return Ok(Resolution::skipped(format!(
+                    "temp_id {temp_id:?} is used twice"
+                )));
+            }
+        }
+        if let Err((_, message)) = check_visibility(&card.visibility) {
+            return Ok(Resolution::skipped(message));
+        }
+        if let Err((_, message)) = check_smart_frame(&mut *self.tx, &card).await {
+            return Ok(Resolution::skipped(message));
+        }
+
+        let card_id = self.insert_card(&card).await?;
+        if let Some(temp_id) = temp_id {
+            self.temp_ids.insert(temp_id, card_id);
+        }
+        Ok(Resolution {
+            created: Some(card_id),
+            ..Resolution::new(SyncOutcome::Applied, Some(card_id))
+        })
+    }
+
+    async fn update(
+        &mut self,
+        card_id: i64,
+        base_version: i32,
+        base: CardFields,
+        changes: CardFields,
+    ) -> Result<Resolution, sqlx::Error> {
+        let Some(version) = lock_card_version(&mut *self.tx, card_id).await? else {
+            return Ok(Resolution::skipped(format!(
+                "card {card_id} was deleted on the server"
+            )));
+        };
+        let stale = version != base_version;
+        let current = card_params(self.fetch_card(card_id).await?);
+
+        let mut card = current.clone();
+        if let Some(position) = changes.position {
+            card.position = position;
+        }
+        if let Some(size) = changes.size {
+            card.size = size;
+        }
+        let title_conflict = merge_text(&mut card.title, base.title, &changes.title, stale);
+        let contents_conflict =
+            merge_text(&mut card.contents, base.contents, &changes.contents, stale);
+
+        // `update_card_row` writes every column back, visibility included.
+        if let Err((_, message)) = check_visibility(&card.visibility) {
+            return Ok(Resolution::skipped(message));
+        }
+
+        let text_changed = card.title != current.title || card.contents != current.contents;
+        if text_changed || card.position != current.position || card.size != current.size {
+            update_card_row(&mut *self.tx, &card).await?;
+            if text_changed {
+                index_card(&mut self.tx, card_id, &card.title, &card.contents).await?;
+                self.record_revision(card_id, &card).await?;
+            }
+        }
+        self.touched_cards.push(card_id);
+
+        if !(title_conflict || contents_conflict) {
+            let outcome = if stale {
+                SyncOutcome::Merged
+            } else {
+                SyncOutcome::Applied
+            };
+            return Ok(Resolution::new(outcome, Some(card_id)));
+        }
+
+        // The copy holds the client's text, so nothing edited offline is lost.
+        let copy = CardParams {
+            id: 0,
+            position: Dimmension {
+                x: card.position.x + CONFLICT_COPY_OFFSET,
+                y: card.position.y + CONFLICT_COPY_OFFSET,
+            },
+            size: card.size,
+            title: conflict_copy_title(changes.title.as_deref().unwrap_or(&card.title)),
+            contents: changes.contents.unwrap_or_else(|| card.contents.clone()),
+            parent_id: card.parent_id,
+            tag_ids: card.tag_ids.clone(),
+            visibility: card.visibility.clone(),
+            card_type: "normal".to_string(),
+            saved_query_id: None,
+        };
+        let copy_id = self.insert_card(&copy).await?;
+        for relation in fetch_card_relations(&mut *self.tx, &[], &[card_id]).await? {
+            if relation.card_child_id == card_id {
+                let parent_id = relation.card_parent_id;
+                insert_card_relation(&mut *self.tx, parent_id, copy_id, &relation.connector)
+                    .await?;
+                self.touched_relations.push((parent_id, copy_id));
+            }
+        }
+
+        Ok(Resolution {
+            conflict_copy_id: Some(copy_id),
+            created: Some(copy_id),
+            ..Resolution::new(SyncOutcome::ConflictCopy, Some(card_id))
+        })
+    }
+
+    async fn delete(&mut self, card_id: i64, base_version: i32) -> Result<Resolution, sqlx::Error> {
+        let Some(version) = lock_card_version(&mut *self.tx, card_id).await? else {
+            // Deleted on both sides.
+            return Ok(Resolution::new(SyncOutcome::Applied, Some(card_id)));
+        };
+        if version != base_version {
+            return Ok(Resolution::skipped(format!(
+                "card {card_id} was changed on the server, so it was kept"
+            )));
+        }
+
+        trash_card(&mut self.tx, card_id).await?;
+        self.touched_cards.push(card_id);
+        Ok(Resolution::new(SyncOutcome::Applied, Some(card_id)))
+    }
+
+    async fn connect(
+        &mut self,
+        parent_id: i64,
+        child_id: i64,
+        connector: &str,
+    ) -> Result<Resolution, sqlx::Error> {
+        if parent_id == child_id {
+            return Ok(Resolution::skipped("a card cannot be its own parent"));
+        }
+        for card_id in [parent_id, child_id] {
+            if lock_card_version(&mut *self.tx, card_id).await?.is_none() {
+                return Ok(Resolution::skipped(format!(
+                    "card {card_id} was deleted on the server"
+                )));
+            }
+        }
+        if fetch_card_relation(&mut *self.tx, parent_id, child_id)
+            .await?
+            .is_some()
+        {
+            return Ok(Resolution::new(SyncOutcome::Applied, None));
+        }
+        // Children of a smart frame come from its saved query.
+        if self.fetch_card(parent_id).await?.card_type == CARD_TYPE_SMART_FRAME {
+            return Ok(Resolution::skipped(
+                "smart frame children come from its saved query",
+            ));
+        }
+        if has_cycle(&mut *self.tx, parent_id, child_id).await? {
+            return Ok(Resolution::skipped("connecting would create a cycle"));
+        }
+
+        insert_card_relation(&mut *self.tx, parent_id, child_id, connector).await?;
+        self.touched_relations.push((parent_id, child_id));
+        Ok(Resolution::new(SyncOutcome::Applied, None))
+    }
+
+    async fn insert_card(&mut self, card: &CardParams) -> Result<i64, sqlx::Error> {
+        let card_id = insert_card(&mut *self.tx, card, self.user_id).await?;
+        self.access.add_created(card_id);
+        set_card_tags(&mut self.tx, card_id, &card.tag_ids).await?;
+        index_card(&mut self.tx, card_id, &card.title, &card.contents).await?;
+        self.record_revision(card_id, card).await?;
+        self.touched_cards.push(card_id);
+        Ok(card_id)
+    }
+
+    async fn record_revision(
+        &mut self,
+        card_id: i64,
+        card: &CardParams,
+    ) -> Result<(), sqlx::Error> {
+        let revision = NewCardRevision {
+            card_id,
+            title: &card.title,
+            contents: &card.contents,
+            user_id: self.user_id,
+            source: self.source,
+            restored_from: None,
+        };
+        record_card_revision(&mut self.tx, &revision).await?;
+        Ok(())
+    }
+
+    async fn fetch_card(&mut self, card_id: i64) -> Result<Card, sqlx::Error> {
+        Ok(Card::from(
+            fetch_card_row_by_id(&mut *self.tx, card_id).await?,
+        ))
+    }
+}
+
+/// Merge one text field. The client's value wins unless the card is `stale`
+/// and the server's text is no longer `base`: answers bump the version
+/// without touching the text, so the version alone does not mean the field
+/// was edited. Then the server's text stays and this returns true. Without a
+/// base value the client's text wins; the server's stays in the revisions.
+fn merge_text(
+    server: &mut String,
+    base: Option<String>,
+    client: &Option<String>,
+    stale: bool,
+) -> bool {
+    let Some(client) = client else {
+        return false;
+    };
+    if client == server {
+        return false;
+    }
+    if !stale || base.is_none_or(|base| base == *server) {
+        server.clone_from(client);
+        return false;
+    }
+    true
+}
+
+fn conflict_copy_title(title: &str) -> String {
+    let keep = MAX_TITLE_CHARS - CONFLICT_COPY_SUFFIX.chars().count();
+    let title: String = title.chars().take(keep).collect();
+    format!("{title}{CONFLICT_COPY_SUFFIX}")
+}
+
+fn card_params(card: Card) -> CardParams {
+    CardParams {
+        id: card.id,
+        position: card.position,
+        size: card.size,
+        title: card.title,
+        contents: card.contents,
+        parent_id: card.parent_id,
+        tag_ids: card.tag_ids,
+        visibility: card.visibility,
+        card_type: card.card_type,
+        saved_query_id: card.saved_query_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Merge `client` into `server` and return the merged text and whether
+    /// it was a conflict.
+    fn merge(
+        server: &str,
+        base: Option<&str>,
+        client: Option<&str>,
+        stale: bool,
+    ) -> (String, bool) {
+        let mut merged = server.to_string();
+        let conflict = merge_text(
+            &mut merged,
+            base.map(str::to_string),
+            &client.map(str::to_string),
+            stale,
+        );
+        (merged, conflict)
+    }
+
+    #[test]
+    fn untouched_field_keeps_the_server_text() {
+        assert_eq!(
+            merge("server", Some("base"), None, true),
+            ("server".to_string(), false)
+        );
+        assert_eq!(merge("", None, None, false), (String::new(), false));
+    }
+
+    #[test]
+    fn identical_edits_are_not_conflicts() {
+        assert_eq!(
+            merge("same", Some("base"), Some("same"), true),
+            ("same".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn client_wins_when_the_server_text_is_still_the_base() {
+        // An answer bumped the version but left the text alone.
+        assert_eq!(
+            merge("base", Some("base"), Some("client"), true),
+            ("client".to_string(), false)
+        );
+        assert_eq!(
+            merge("base", Some("other"), Some("client"), false),
+            ("client".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn client_wins_without_a_base() {
+        assert_eq!(
+            merge("server", None, Some("client"), true),
+            ("client".to_string(), false)
+        );
+        assert_eq!(
+            merge("server", None, Some(""), true),
+            (String::new(), false)
+        );
+    }
+
+    #[test]
+    fn edits_on_both_sides_conflict() {
+        assert_eq!(
+            merge("server", Some("base"), Some("client"), true),
+            ("server".to_string(), true)
+        );
+        assert_eq!(
+            merge("", Some("base"), Some("client"), true),
+            (String::new(), true)
+        );
+    }
+
+    #[test]
+    fn unicode_is_compared_as_is() {
+        assert_eq!(
+            merge("日本語", Some("日本語"), Some("日本語の勉強"), true),
+            ("日本語の勉強".to_string(), false)
+        );
+        // Full-width and half-width letters are different text.
+        assert_eq!(
+            merge("ＡＢＣ", Some("ABC"), Some("abc"), true),
+            ("ＡＢＣ".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn conflict_copy_titles_fit_the_column() {
+        assert_eq!(conflict_copy_title("Notes"), "Notes (conflict copy)");
+        let long = "長".repeat(MAX_TITLE_CHARS);
+        let title = conflict_copy_title(&long);
+        assert_eq!(title.chars().count(), MAX_TITLE_CHARS);
+        assert!(title.ends_with(CONFLICT_COPY_SUFFIX));
+        assert_eq!(conflict_copy_title(""), CONFLICT_COPY_SUFFIX);
+    }
+}
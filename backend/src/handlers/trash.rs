@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Extension,
+};
+use sqlx::{MySql, Pool, Transaction};
+
+use crate::{
+    acl::{AccessCache, CardAccess},
+    auth::{AuthState, RequestUser, Scope},
+    db::{
+        delete_trashed_relation, fetch_card_row_by_id, fetch_trashed_cards,
+        fetch_trashed_parent_edges, fetch_trashed_relations, hand_over_trashed_relation,
+        restore_trashed_relation, untrash_card,
+    },
+    handlers::card_card::has_cycle,
+    models::{ApiResponse, Card, TrashedCard},
+    trash::TrashRetention,
+};
+
+/// Cards in the trash, most recently deleted first. Each is checked as if it
+/// were back under the parents it was trashed from.
+pub async fn get_trash(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    Extension(pool): Extension<Pool<MySql>>,
+    Extension(access_cache): Extension<AccessCache>,
+    Extension(retention): Extension<TrashRetention>,
+) -> ApiResponse<Vec<TrashedCard>> {
+    let viewer = auth.card_reader(&headers, Scope::Read).await;
+    let result = async {
+        let mut access = CardAccess::load(&access_cache, viewer).await?;
+        let mut parents: HashMap<i64, Vec<i64>> = HashMap::new();
+        for (parent_id, child_id) in fetch_trashed_parent_edges(&pool).await? {
+            parents.entry(child_id).or_default().push(parent_id);
+        }
+        for (card_id, parents) in parents {
+            access.set_parents(card_id, parents);
+        }
+        let cards = fetch_trashed_cards(&pool).await?;
+        Ok::<_, sqlx::Error>((access, cards))
+    }
+    .await;
+    match result {
+        Ok((access, cards)) => {
+            let cards = cards
+                .into_iter()
+                .filter(|card| access.can_read(card.id))
+                .map(|card| TrashedCard {
+                    purge_at: retention.purge_at(card.deleted_at),
+                    ..card
+                })
+                .collect();
+            ApiResponse::new_ok(StatusCode::OK, cards)
+        }
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Take a card out of the trash together with its tags and relations. The
+/// user needs write access to the card as it will be once its parents are
+/// back.
+pub async fn restore_trashed_card(
+    Path(card_id): Path<i64>,
+    Extension(pool): Extension<Pool<MySql>>,
+    user: Option<Extension<RequestUser>>,
+) -> ApiResponse<Card> {
+    let checked = async {
+        let mut access = CardAccess::for_request(&pool, user.as_deref()).await?;
+        let parents = fetch_trashed_relations(&pool, card_id)
+            .await?
+            .into_iter()
+            .filter(|relation| relation.card_child_id == card_id)
+            .map(|relation| relation.card_parent_id)
+            .collect();
+        access.set_parents(card_id, parents);
+        Ok::<_, sqlx::Error>(access.check_write(&[card_id]))
+    }
+    .await;
+    match checked {
+        Ok(Ok(())) => {}
+        Ok(Err((status, message))) => return ApiResponse::new_err(status, message),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    match untrash_card(&mut tx, card_id).await {
+        Ok(true) => {}
+        Ok(false) => return ApiResponse::new_err(StatusCode::NOT_FOUND, "card not in the trash"),
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+    if let Err(e) = restore_relations(&mut tx, card_id).await {
+        let _ = tx.rollback().await;
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    if let Err(e) = tx.commit().await {
+        return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    match fetch_card_row_by_id(&pool, card_id).await {
+        Ok(row) => {
+            let version = row.version;
+            ApiResponse::new_ok(StatusCode::OK, Card::from(row)).with_etag(version)
+        }
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Put back the relations trashed with the card. One whose other card is
+/// still in the trash is handed to that card and comes back with it; one
+/// that would now close a cycle is dropped.
+async fn restore_relations(
+    tx: &mut Transaction<'_, MySql>,
+    card_id: i64,
+) -> Result<(), sqlx::Error> {
+    for relation in fetch_trashed_relations(&mut **tx, card_id).await? {
+        if relation.other_deleted {
+            let other_id = if relation.card_parent_id == card_id {
+                relation.card_child_id
+            } else {
+                relation.card_parent_id
+            };
+            hand_over_trashed_relation(&mut **tx, &relation, other_id).await?;
+        } else if has_cycle(&mut **tx, relation.card_parent_id, relation.card_child_id).await? {
+            delete_trashed_relation(&mut **tx, &relation).await?;
+        } else {
+            restore_trashed_relation(tx, &relation).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use sqlx::{MySql, Pool};
+
+use crate::{
+    auth::{AuthState, ROLES},
+    db::{
+        count_other_active_admins, delete_user_api_tokens, delete_user_sessions, fetch_user,
+        fetch_users, insert_user, set_user_password, update_user,
+    },
+    models::{ApiResponse, NewUserParams, PasswordResetParams, User, UserUpdateParams},
+};
+
+/// Longest username the `users.username` column holds.
+const MAX_USERNAME_CHARS: usize = 100;
+
+enum UserUpdate {
+    Updated(User),
+    NotFound,
+    LastAdmin,
+}
+
+fn validate_role<T>(role: &str) -> Result<(), ApiResponse<T>> {
+    if ROLES.contains(&role) {
+        Ok(())
+    } else {
+        Err(ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("role must be one of {}", ROLES.join(", ")),
+        ))
+    }
+}
+
+/// All users. `require_write_auth` lets only admins reach these handlers.
+pub async fn get_users(Extension(pool): Extension<Pool<MySql>>) -> ApiResponse<Vec<User>> {
+    match fetch_users(&pool).await {
+        Ok(users) => ApiResponse::new_ok(StatusCode::OK, users),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn create_user(
+    State(auth): State<AuthState>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Json(params): Json<NewUserParams>,
+) -> ApiResponse<User> {
+    let username = params.username.trim();
+    if username.is_empty() || username.chars().count() > MAX_USERNAME_CHARS {
+        return ApiResponse::new_err(
+            StatusCode::BAD_REQUEST,
+            format!("username must be 1 to {MAX_USERNAME_CHARS} characters"),
+        );
+    }
+    if let Err(res) = validate_role(&params.role) {
+        return res;
+    }
+    let hash = match auth.hash_new_password(&params.password) {
+        Ok(hash) => hash,
+        Err((status, message)) => return ApiResponse::new_err(status, message),
+    };
+
+    let user_id = match insert_user(&pool, username, &hash, &params.role).await {
+        Ok(user_id) => user_id,
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return ApiResponse::new_err(StatusCode::CONFLICT, "username is already taken");
+        }
+        Err(e) => return ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    match fetch_user(&pool, user_id).await {
+        Ok(Some(user)) => ApiResponse::new_ok(StatusCode::CREATED, user),
+        Ok(None) => ApiResponse::new_err(StatusCode::NOT_FOUND, "user not found"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Change a user's role or deactivate them. The last active admin can be
+/// neither demoted nor deactivated. Deactivated users are logged out.
+pub async fn update_user_by_id(
+    Extension(pool): Extension<Pool<MySql>>,
+    Path(user_id): Path<i64>,
+    Json(params): Json<UserUpdateParams>,
+) -> ApiResponse<User> {
+    if let Some(role) = &params.role {
+        if let Err(res) = validate_role(role) {
+            return res;
+        }
+    }
+
+    let result: Result<UserUpdate, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        let Some(user) = fetch_user(&mut *tx, user_id).await? else {
+            return Ok(UserUpdate::NotFound);
+        };
+
+        let stays_admin = params.role.as_deref().is_none_or(|role| role == "admin")
+            && params.is_active != Some(false);
+        if user.role == "admin"
+            && user.is_active
+            && !stays_admin
+            && count_other_active_admins(&mut tx, user_id).await? == 0
+        {
+            return Ok(UserUpdate::LastAdmin);
+        }
+
+        update_user(&mut *tx, user_id, params.role.as_deref(), params.is_active).await?;
+        if params.is_active == Some(false) {
+            delete_user_sessions(&mut *tx, user_id, None).await?;
+        }
+        let user = fetch_user(&mut *tx, user_id).await?;
+        tx.commit().await?;
+        Ok(user.map_or(UserUpdate::NotFound, UserUpdate::Updated))
+    }
+    .await;
+
+    match result {
+        Ok(UserUpdate::Updated(user)) => ApiResponse::new_ok(StatusCode::OK, user),
+        Ok(UserUpdate::NotFound) => ApiResponse::new_err(StatusCode::NOT_FOUND, "user not found"),
+        Ok(UserUpdate::LastAdmin) => ApiResponse::new_err(
+            StatusCode::CONFLICT,
+            "the last active admin cannot be demoted or deactivated",
+        ),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Set a new password for a user, log out all of their sessions and revoke
+/// their API tokens. A reset usually means the account was compromised, and
+/// a token would keep working without the password.
+pub async fn reset_user_password(
+    State(auth): State<AuthState>,
+    Extension(pool): Extension<Pool<MySql>>,
+    Path(user_id): Path<i64>,
+    Json(params): Json<PasswordResetParams>,
+) -> ApiResponse<()> {
+    let hash = match auth.hash_new_password(&params.password) {
+        Ok(hash) => hash,
+        Err((status, message)) => return ApiResponse::new_err(status, message),
+    };
+
+    let result = async {
+        let mut tx = pool.begin().await?;
+        if !set_user_password(&mut *tx, user_id, &hash).await? {
+            return Ok(false);
+        }
+        delete_user_sessions(&mut *tx, user_id, None).await?;
+        delete_user_api_tokens(&mut *tx, user_id).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(true)
+    }
+    .await;
+
+    match result {
+        Ok(true) => ApiResponse::new_ok(StatusCode::OK, ()),
+        Ok(false) => ApiResponse::new_err(StatusCode::NOT_FOUND, "user not found"),
+        Err(e) => ApiResponse::new_err(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
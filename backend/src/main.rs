@@ -1,21 +1,32 @@
 use axum::{
     http::{
-        header::{AUTHORIZATION, CONTENT_TYPE},
+        header::{AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH},
         Method,
     },
-    Extension,
+    middleware, Extension,
 };
 use std::net::SocketAddr;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 
 // mod config;
+mod acl;
+mod anki;
 mod auth;
+mod card_query;
 mod db;
+mod diff;
+mod etag;
 mod handlers;
+mod markdown;
 mod models;
+mod review_items;
 mod routes;
+mod scheduler;
 mod schema;
+mod search;
+mod title_index;
+mod trash;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -25,23 +36,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // DB プール
     let pool = db::create_pool().await;
     sqlx::migrate!("./migrations").run(&pool).await?;
-    let auth_state = auth::AuthState::from_env();
+    let auth_state = auth::AuthState::from_env(pool.clone());
+    auth_state.start_session_sweeper();
     // Seed the admin user from MEMOAPP_ADMIN_PASSWORD on first run.
     auth::bootstrap_admin(&pool, &auth_state).await?;
+    // Index cards created before full-text search existed.
+    handlers::cards::index_unindexed_cards(&pool).await?;
+    let scheduler = scheduler::Scheduler::from_env();
+    let leitner_boxes = scheduler::LeitnerBoxes::from_env();
+    let title_index = title_index::TitleIndex::start(pool.clone()).await?;
+    let access_cache = acl::AccessCache::new(pool.clone());
+    let trash_retention = trash::TrashRetention::from_env();
+    trash_retention.start_purge(pool.clone());
 
     // CORS
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
-        .allow_headers([CONTENT_TYPE, AUTHORIZATION]);
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([CONTENT_TYPE, AUTHORIZATION, IF_MATCH])
+        .expose_headers([ETAG]);
 
     let trace = TraceLayer::new_for_http();
 
     // ルーター組み立て
     let app = routes::router(auth_state)
+        .layer(middleware::from_fn(title_index::refresh_after_writes))
+        .layer(middleware::from_fn(acl::refresh_after_writes))
+        .layer(middleware::from_fn(handlers::changes::stamp_after_writes))
         .layer(cors)
         .layer(trace)
-        .layer(Extension(pool));
+        .layer(Extension(pool))
+        .layer(Extension(scheduler))
+        .layer(Extension(leitner_boxes))
+        .layer(Extension(title_index))
+        .layer(Extension(access_cache))
+        .layer(Extension(trash_retention));
 
     // サーバ起動
     let addr = SocketAddr::from(([0, 0, 0, 0], 8082));
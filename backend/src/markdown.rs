@@ -0,0 +1,137 @@
+use pulldown_cmark::{html, Options, Parser};
+
+/// Split card markdown into its first H1 and the remaining body, the same
+/// way the frontend derives a card title (`extractFirstH1`).
+/// Headings inside fenced code blocks are ignored.
+pub fn split_first_h1(contents: &str) -> (Option<String>, String) {
+    let mut in_fence = false;
+    let mut heading = None;
+    let mut body = Vec::new();
+
+    for line in contents.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+
+        if heading.is_none() && !in_fence {
+            if let Some(text) = trimmed.strip_prefix("# ") {
+                heading = Some(text.trim().trim_end_matches('#').trim().to_string());
+                continue;
+            }
+        }
+        body.push(line);
+    }
+
+    (heading, body.join("\n").trim().to_string())
+}
+
+/// Render markdown to HTML (CommonMark plus tables and strikethrough).
+pub fn render_html(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(markdown, options));
+    out
+}
+
+/// Escape text for use inside HTML.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Reduce an HTML fragment (e.g. an Anki note field) to plain text: block
+/// tags and `<br>` become line breaks, other tags are dropped and entities
+/// are decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find(['<', '&']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with('<') {
+            let Some(end) = rest.find('>') else {
+                out.push_str(rest);
+                rest = "";
+                break;
+            };
+            let tag = rest[1..end].trim().to_ascii_lowercase();
+            let name: String = tag
+                .trim_start_matches('/')
+                .chars()
+                .take_while(|ch| ch.is_ascii_alphanumeric())
+                .collect();
+            rest = &rest[end + 1..];
+
+            match name.as_str() {
+                "style" | "script" if !tag.starts_with('/') => {
+                    let close = format!("</{name}");
+                    let skip = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+                    rest = &rest[skip..];
+                }
+                "br" => out.push('\n'),
+                "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6"
+                    if !out.is_empty() && !out.ends_with('\n') =>
+                {
+                    out.push('\n');
+                }
+                _ => {}
+            }
+        } else {
+            let end = rest.find(';').filter(|&end| end <= 10);
+            let decoded = end.and_then(|end| decode_entity(&rest[1..end]));
+            match (end, decoded) {
+                (Some(end), Some(ch)) => {
+                    out.push(ch);
+                    rest = &rest[end + 1..];
+                }
+                _ => {
+                    out.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = if let Some(hex) = entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                u32::from_str_radix(hex, 16).ok()?
+            } else {
+                entity.strip_prefix('#')?.parse().ok()?
+            };
+            char::from_u32(code)
+        }
+    }
+}
@@ -1,5 +1,7 @@
 mod card;
-pub use card::{Card, CardParams, CardRow};
+pub use card::{
+    Card, CardParams, CardRow, CardSearchHit, CARD_TYPE_FRAME, CARD_TYPE_SMART_FRAME,
+};
 
 mod tag;
 pub use tag::{Tag, TagRow};
@@ -8,7 +10,28 @@ mod card_card;
 pub use card_card::{CardCardParams, CardRelation};
 
 mod response;
-pub use response::ApiResponse;
+pub use response::{error_response, ApiResponse};
 
 mod user;
-pub use user::UserRow;
+pub use user::{NewUserParams, PasswordResetParams, User, UserRow, UserUpdateParams};
+
+mod schedule;
+pub use schedule::{CardItemSchedule, CardSchedule};
+
+mod study_session;
+pub use study_session::StudySessionRow;
+
+mod saved_query;
+pub use saved_query::{SavedQuery, SavedQueryParams};
+
+mod revision;
+pub use revision::{CardRevision, RevisionSource};
+
+mod trash;
+pub use trash::TrashedCard;
+
+mod card_acl;
+pub use card_acl::{CardAcl, CardAclEntry, CardAclParams};
+
+mod api_token;
+pub use api_token::{ApiToken, ApiTokenParams, ApiTokenRow, CreatedApiToken};
@@ -0,0 +1,64 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A row from the `api_tokens` table, without the hash.
+#[derive(FromRow)]
+pub struct ApiTokenRow {
+    pub id: i64,
+    pub name: String,
+    pub token_prefix: String,
+    /// Comma-separated, as MariaDB returns a `SET` column.
+    pub scopes: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+/// An API token as listed to its owner. The token itself is only shown once,
+/// when it is created.
+#[derive(Serialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<ApiTokenRow> for ApiToken {
+    fn from(r: ApiTokenRow) -> Self {
+        ApiToken {
+            id: r.id,
+            name: r.name,
+            prefix: r.token_prefix,
+            scopes: r
+                .scopes
+                .split(',')
+                .filter(|scope| !scope.is_empty())
+                .map(str::to_string)
+                .collect(),
+            expires_at: r.expires_at,
+            last_used_at: r.last_used_at,
+            created_at: r.created_at,
+        }
+    }
+}
+
+/// A token that was just created, with its secret.
+#[derive(Serialize)]
+pub struct CreatedApiToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+}
+
+#[derive(Deserialize)]
+pub struct ApiTokenParams {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Days until the token stops working. Never, when missing.
+    pub expires_in_days: Option<i64>,
+}
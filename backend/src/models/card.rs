@@ -25,13 +25,24 @@ pub struct Card {
     pub visibility: String,
     #[serde(default = "default_card_type")]
     pub card_type: String,
+    /// Query whose results are the children of a `smart_frame` card.
+    #[serde(default)]
+    pub saved_query_id: Option<i64>,
     pub ok_count: i32,
+    /// Bumped on every change; sent as the `ETag` of single-card responses.
+    #[serde(default)]
+    pub version: i32,
+    /// User who created the card, if known.
+    #[serde(default)]
+    pub owner_id: Option<i64>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CardParams {
+    /// Ignored when creating a card.
+    #[serde(default)]
     pub id: i64,
     pub position: Dimmension,
     pub size: Dimmension,
@@ -43,6 +54,38 @@ pub struct CardParams {
     pub visibility: String,
     #[serde(default = "default_card_type")]
     pub card_type: String,
+    /// Required for `smart_frame` cards, ignored for others.
+    #[serde(default)]
+    pub saved_query_id: Option<i64>,
+}
+
+/// Card type whose ACL, if any, governs the cards under it.
+pub const CARD_TYPE_FRAME: &str = "frame";
+
+/// Card type whose children are the current results of a saved query.
+pub const CARD_TYPE_SMART_FRAME: &str = "smart_frame";
+
+impl CardParams {
+    /// `saved_query_id` if the card is a smart frame. Other card types never
+    /// keep one.
+    pub fn smart_frame_query_id(&self) -> Option<i64> {
+        if self.card_type == CARD_TYPE_SMART_FRAME {
+            self.saved_query_id
+        } else {
+            None
+        }
+    }
+}
+
+/// A `/cards/search` result: the card plus its score and highlighted text.
+/// `title_html` and `snippet` are HTML-escaped with matches in `<mark>`.
+#[derive(Serialize)]
+pub struct CardSearchHit {
+    #[serde(flatten)]
+    pub card: Card,
+    pub score: f64,
+    pub title_html: String,
+    pub snippet: String,
 }
 
 #[derive(FromRow)]
@@ -58,7 +101,10 @@ pub struct CardRow {
     pub tag_ids: serde_json::Value,
     pub visibility: String,
     pub card_type: String,
+    pub saved_query_id: Option<i64>,
     pub ok_count: i32,
+    pub version: i32,
+    pub owner_id: Option<i64>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
@@ -82,7 +128,10 @@ impl From<CardRow> for Card {
             tag_ids,
             visibility: r.visibility,
             card_type: r.card_type,
+            saved_query_id: r.saved_query_id,
             ok_count: r.ok_count,
+            version: r.version,
+            owner_id: r.owner_id,
             created_at: r.created_at,
             updated_at: r.updated_at,
         }
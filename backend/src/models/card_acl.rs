@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// One grant of a frame's ACL: `user_id` or `role` (exactly one of them)
+/// gets `read` or `write` access to the frame and the cards under it.
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct CardAclEntry {
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    #[serde(default)]
+    pub role: Option<String>,
+    pub permission: String,
+}
+
+/// A frame's owner and ACL. No entries means the frame inherits access from
+/// the frames above it.
+#[derive(Serialize)]
+pub struct CardAcl {
+    pub card_id: i64,
+    pub owner_id: Option<i64>,
+    pub entries: Vec<CardAclEntry>,
+}
+
+/// Replaces a frame's whole ACL.
+#[derive(Deserialize)]
+pub struct CardAclParams {
+    pub entries: Vec<CardAclEntry>,
+}
@@ -14,6 +14,8 @@ pub struct CardRelation {
     pub card_parent_id: i64,
     pub card_child_id: i64,
     pub connector: String,
+    /// Bumped on every change; sent as the `ETag` by `update_connector`.
+    pub version: i32,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
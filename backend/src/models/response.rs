@@ -1,15 +1,21 @@
 use axum::{
-    http::StatusCode,
+    http::{header::ETAG, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
+use serde_json::json;
+
+use crate::etag::etag;
 
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
     code: u16,
     message: String,
     data: Option<T>,
+    /// Sent as the `ETag` header.
+    #[serde(skip)]
+    version: Option<i32>,
 }
 
 impl<T> ApiResponse<T> {
@@ -18,6 +24,7 @@ impl<T> ApiResponse<T> {
             code: status.as_u16(),
             message: "OK".into(),
             data: Some(data),
+            version: None,
         }
     }
 
@@ -26,8 +33,39 @@ impl<T> ApiResponse<T> {
             code: status.as_u16(),
             message: msg.into(),
             data: None,
+            version: None,
         }
     }
+
+    /// An error that still carries data, such as the server's copy of a row
+    /// with a `412`.
+    pub fn new_err_with(status: StatusCode, msg: impl Into<String>, data: T) -> Self {
+        Self {
+            code: status.as_u16(),
+            message: msg.into(),
+            data: Some(data),
+            version: None,
+        }
+    }
+
+    /// Send `version` of the returned row as the `ETag`.
+    pub fn with_etag(mut self, version: i32) -> Self {
+        self.version = Some(version);
+        self
+    }
+}
+
+/// A bare `{code, message}` error, for handlers that answer with a plain
+/// `Response` rather than an `ApiResponse`.
+pub fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(json!({
+            "code": status.as_u16(),
+            "message": message.into(),
+        })),
+    )
+        .into_response()
 }
 
 impl<T> IntoResponse for ApiResponse<T>
@@ -35,10 +73,10 @@ where
     T: Serialize,
 {
     fn into_response(self) -> Response {
-        (
-            StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-            Json(self),
-        )
-            .into_response()
+        let status = StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        match self.version {
+            Some(version) => (status, [(ETAG, etag(version))], Json(self)).into_response(),
+            None => (status, Json(self)).into_response(),
+        }
     }
 }
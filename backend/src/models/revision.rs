@@ -0,0 +1,44 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// The path a card change came through, stored in `card_revisions.source`.
+#[derive(Clone, Copy)]
+pub enum RevisionSource {
+    /// A browser session.
+    Ui,
+    ApiToken,
+    /// A `/cards/flush_json` result that replaced the title or contents.
+    FlashCard,
+    /// A deck import.
+    Import,
+}
+
+impl RevisionSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RevisionSource::Ui => "ui",
+            RevisionSource::ApiToken => "api_token",
+            RevisionSource::FlashCard => "flash_card",
+            RevisionSource::Import => "import",
+        }
+    }
+}
+
+/// A row from `card_revisions`: the card's title and contents after one
+/// change, with who made it.
+#[derive(Serialize, FromRow)]
+pub struct CardRevision {
+    pub id: i64,
+    pub card_id: i64,
+    pub title: String,
+    pub contents: String,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    /// `ui`, `api_token`, `flash_card`, `import`, or `initial` for the text
+    /// cards had when history started.
+    pub source: String,
+    /// The revision this one restored, if any.
+    pub restored_from: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
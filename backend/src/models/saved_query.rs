@@ -0,0 +1,19 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A named card query. `query` uses the `card_query` syntax.
+#[derive(Serialize, FromRow)]
+pub struct SavedQuery {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct SavedQueryParams {
+    pub name: String,
+    pub query: String,
+}
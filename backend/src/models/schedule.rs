@@ -0,0 +1,43 @@
+use chrono::NaiveDateTime;
+use sqlx::FromRow;
+
+/// Spaced-repetition state of a review item, as stored in `card_schedules`.
+/// An item without a row is new and uses `CardSchedule::default()`.
+#[derive(Clone, FromRow)]
+pub struct CardSchedule {
+    pub ease_factor: f64,
+    pub interval_days: f64,
+    pub stability: f64,
+    pub difficulty: f64,
+    pub reps: i32,
+    pub lapses: i32,
+    /// Leitner box, 1-based. Only advanced by the Leitner scheduler.
+    pub leitner_box: i32,
+    pub due_at: Option<NaiveDateTime>,
+    pub last_reviewed_at: Option<NaiveDateTime>,
+}
+
+impl Default for CardSchedule {
+    fn default() -> Self {
+        Self {
+            ease_factor: 2.5,
+            interval_days: 0.0,
+            stability: 0.0,
+            difficulty: 0.0,
+            reps: 0,
+            lapses: 0,
+            leitner_box: 1,
+            due_at: None,
+            last_reviewed_at: None,
+        }
+    }
+}
+
+/// Schedule of one review item of a card (see `review_items`).
+#[derive(FromRow)]
+pub struct CardItemSchedule {
+    pub card_id: i64,
+    pub item_key: String,
+    #[sqlx(flatten)]
+    pub schedule: CardSchedule,
+}
@@ -0,0 +1,25 @@
+use chrono::NaiveDateTime;
+use sqlx::FromRow;
+
+/// A row from the `study_sessions` table.
+#[derive(FromRow)]
+pub struct StudySessionRow {
+    pub id: i64,
+    /// User who started the session. `None` for sessions from before
+    /// sessions had owners; only admins may use those.
+    pub user_id: Option<i64>,
+    /// The `FlashCardQuery` the session was started with, as JSON.
+    pub scope: String,
+    pub new_limit: i32,
+    pub learning_limit: i32,
+    pub review_limit: i32,
+    pub new_count: i32,
+    pub learning_count: i32,
+    pub review_count: i32,
+    pub current_card_id: Option<i64>,
+    pub current_item_key: Option<String>,
+    pub current_queue: Option<String>,
+    pub served_at: Option<NaiveDateTime>,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,20 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// A card in the trash. `tag_count` and `relation_count` are the tags and
+/// `card_card` rows a restore brings back.
+#[derive(Serialize, FromRow)]
+pub struct TrashedCard {
+    pub id: i64,
+    pub title: String,
+    pub visibility: String,
+    pub card_type: String,
+    pub deleted_at: NaiveDateTime,
+    pub tag_count: i64,
+    pub relation_count: i64,
+    /// When the background purge deletes the card for good. `None` when
+    /// trashed cards are kept until restored.
+    #[sqlx(skip)]
+    pub purge_at: Option<NaiveDateTime>,
+}
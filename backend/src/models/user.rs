@@ -1,3 +1,5 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
 /// A row from the `users` table, used internally for authentication.
@@ -5,7 +7,36 @@ use sqlx::FromRow;
 #[derive(FromRow)]
 pub struct UserRow {
     pub id: i64,
-    pub username: String,
+    pub role: String,
     pub password_hash: String,
+}
+
+/// A user account as shown to admins. Never includes the password hash.
+#[derive(Serialize, FromRow)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub role: String,
+    pub is_active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct NewUserParams {
+    pub username: String,
+    pub password: String,
     pub role: String,
 }
+
+/// Fields an admin can change; missing ones are left alone.
+#[derive(Deserialize)]
+pub struct UserUpdateParams {
+    pub role: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetParams {
+    pub password: String,
+}
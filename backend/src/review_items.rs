@@ -0,0 +1,258 @@
+//! Review items: the question/answer pairs a card produces for flash-card
+//! review.
+//!
+//! - Cloze deletions (`{{c1::answer}}` or `{{c1::answer::hint}}`) produce one
+//!   item per cloze number, keyed `c1`, `c2`, ...
+//! - Otherwise a line containing only `?` separates the question (above) from
+//!   the answer (below).
+//! - Otherwise the first H1 is the question and the rest is the answer.
+//!
+//! Text below a `?` separator on a cloze card is appended to every answer.
+
+use crate::markdown::split_first_h1;
+
+/// Key of the single item of a card without cloze deletions.
+pub const BASIC_ITEM: &str = "basic";
+
+/// Line separating the question from the answer.
+const SEPARATOR: &str = "?";
+
+pub struct ReviewItem {
+    pub key: String,
+    pub question: String,
+    pub answer: String,
+}
+
+/// Review items of a card, in order. Every card produces at least one item.
+pub fn review_items(title: &str, contents: &str) -> Vec<ReviewItem> {
+    let (front, back) = match split_separator(contents) {
+        Some((front, back)) => (front, Some(back)),
+        None => (contents.to_string(), None),
+    };
+
+    let clozes = parse_clozes(&front);
+    if clozes.is_empty() {
+        let (question, answer) = match back {
+            Some(back) => (front, back),
+            None => {
+                let (heading, body) = split_first_h1(contents);
+                (heading.unwrap_or_else(|| title.to_string()), body)
+            }
+        };
+        return vec![ReviewItem {
+            key: BASIC_ITEM.to_string(),
+            question,
+            answer,
+        }];
+    }
+
+    let mut numbers: Vec<u32> = clozes.iter().map(|cloze| cloze.number).collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let revealed = render_clozes(&front, &clozes, None);
+    let answer = match &back {
+        Some(back) if !back.is_empty() => format!("{revealed}\n\n{back}"),
+        _ => revealed,
+    };
+
+    numbers
+        .into_iter()
+        .map(|number| ReviewItem {
+            key: format!("c{number}"),
+            question: render_clozes(&front, &clozes, Some(number)),
+            answer: answer.clone(),
+        })
+        .collect()
+}
+
+/// Split at the first separator line outside a code fence.
+fn split_separator(contents: &str) -> Option<(String, String)> {
+    let mut in_fence = false;
+    let mut front = Vec::new();
+    let mut lines = contents.lines();
+
+    for line in lines.by_ref() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if !in_fence && trimmed == SEPARATOR {
+            let back = lines.collect::<Vec<_>>().join("\n");
+            return Some((front.join("\n").trim().to_string(), back.trim().to_string()));
+        }
+        front.push(line);
+    }
+
+    None
+}
+
+struct Cloze {
+    /// Byte range of the whole `{{cN::...}}` in the source.
+    start: usize,
+    end: usize,
+    number: u32,
+    text: String,
+    hint: Option<String>,
+}
+
+fn parse_clozes(text: &str) -> Vec<Cloze> {
+    let mut clozes = Vec::new();
+    let mut offset = 0;
+
+    while let Some(found) = text[offset..].find("{{c") {
+        let start = offset + found;
+        let rest = &text[start + 3..];
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+
+        let parsed = rest[digits..]
+            .strip_prefix("::")
+            .and_then(|body| body.find("}}").map(|close| &body[..close]))
+            .filter(|_| digits > 0)
+            .and_then(|body| Some((rest[..digits].parse::<u32>().ok()?, body)));
+
+        match parsed {
+            Some((number, body)) => {
+                let end = start + 3 + digits + 2 + body.len() + 2;
+                let (text, hint) = match body.split_once("::") {
+                    Some((text, hint)) => (text, Some(hint.to_string())),
+                    None => (body, None),
+                };
+                clozes.push(Cloze {
+                    start,
+                    end,
+                    number,
+                    text: text.to_string(),
+                    hint,
+                });
+                offset = end;
+            }
+            None => offset = start + 3,
+        }
+    }
+
+    clozes
+}
+
+/// Replace every cloze with its text, except those numbered `hidden`, which
+/// become `[...]` (or `[hint]`).
+fn render_clozes(text: &str, clozes: &[Cloze], hidden: Option<u32>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for cloze in clozes {
+        out.push_str(&text[last..cloze.start]);
+        if Some(cloze.number) == hidden {
+            out.push('[');
+            out.push_str(cloze.hint.as_deref().unwrap_or("..."));
+            out.push(']');
+        } else {
+            out.push_str(&cloze.text);
+        }
+        last = cloze.end;
+    }
+    out.push_str(&text[last..]);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(title: &str, contents: &str) -> Vec<(String, String, String)> {
+        review_items(title, contents)
+            .into_iter()
+            .map(|item| (item.key, item.question, item.answer))
+            .collect()
+    }
+
+    fn item(key: &str, question: &str, answer: &str) -> (String, String, String) {
+        (key.to_string(), question.to_string(), answer.to_string())
+    }
+
+    #[test]
+    fn separator_splits_question_from_answer() {
+        assert_eq!(
+            items("Sum", "What is 2+2?\n  ?  \n4\n"),
+            vec![item(BASIC_ITEM, "What is 2+2?", "4")]
+        );
+    }
+
+    #[test]
+    fn first_h1_is_the_question_without_separator() {
+        assert_eq!(
+            items("Title", "# Capital of Japan\nTokyo"),
+            vec![item(BASIC_ITEM, "Capital of Japan", "Tokyo")]
+        );
+        assert_eq!(
+            items("Title", "just a body"),
+            vec![item(BASIC_ITEM, "Title", "just a body")]
+        );
+    }
+
+    #[test]
+    fn empty_card_still_has_an_item() {
+        assert_eq!(items("", ""), vec![item(BASIC_ITEM, "", "")]);
+    }
+
+    #[test]
+    fn separator_inside_code_fence_is_ignored() {
+        assert_eq!(
+            items("Title", "```\n?\n```\nbody"),
+            vec![item(BASIC_ITEM, "Title", "```\n?\n```\nbody")]
+        );
+    }
+
+    #[test]
+    fn each_cloze_number_is_an_item() {
+        let answer = "Tokyo is the capital of Japan.";
+        assert_eq!(
+            items(
+                "",
+                "{{c1::Tokyo}} is the capital of {{c2::Japan::country}}."
+            ),
+            vec![
+                item("c1", "[...] is the capital of Japan.", answer),
+                item("c2", "Tokyo is the capital of [country].", answer),
+            ]
+        );
+    }
+
+    #[test]
+    fn cloze_numbers_are_sorted_and_shared() {
+        let keys: Vec<String> = items("", "{{c2::b}} {{c1::a}} {{c2::c}}")
+            .into_iter()
+            .map(|(key, question, _)| format!("{key}: {question}"))
+            .collect();
+        assert_eq!(keys, vec!["c1: b [...] c", "c2: [...] a [...]"]);
+    }
+
+    #[test]
+    fn text_below_separator_is_added_to_cloze_answers() {
+        assert_eq!(
+            items("", "{{c1::x}} and y\n?\nextra"),
+            vec![item("c1", "[...] and y", "x and y\n\nextra")]
+        );
+    }
+
+    #[test]
+    fn malformed_clozes_are_plain_text() {
+        let contents = "{{c::x}} {{cx::y}} {{c1:z}} {{c1::unclosed";
+        assert_eq!(
+            items("Title", contents),
+            vec![item(BASIC_ITEM, "Title", contents)]
+        );
+    }
+
+    #[test]
+    fn clozes_in_multibyte_text() {
+        assert_eq!(
+            items("", "{{c1::東京}}は日本の{{c2::首都::しゅと}}"),
+            vec![
+                item("c1", "[...]は日本の首都", "東京は日本の首都"),
+                item("c2", "東京は日本の[しゅと]", "東京は日本の首都"),
+            ]
+        );
+    }
+}
@@ -1,29 +1,77 @@
-use crate::auth::{generate_api_token, login, logout, require_write_auth, status, AuthState};
+use crate::auth::{change_password, login, logout, require_write_auth, status, AuthState};
+use crate::handlers::anki::{export_apkg, import_deck};
+use crate::handlers::api_tokens::{create_api_token, get_api_tokens, revoke_api_token};
+use crate::handlers::batch::apply_batch;
+use crate::handlers::card_acl::{get_card_acl, set_card_acl};
 use crate::handlers::card_card::{
     connect_card_to_card, disconnect_card_to_card, get_connectors, update_connector,
 };
 use crate::handlers::cards::{
-    create_card, delete_card, get_cards, get_cards_in_range, update_card,
+    create_card, delete_card, get_card_children, get_cards, get_cards_in_range, query_cards,
+    search_cards, update_card,
 };
+use crate::handlers::changes::get_changes;
 use crate::handlers::flash_card::{get_flash_cards_by_tag, post_flash_card_result};
+use crate::handlers::quiz::{get_quiz, post_quiz_answer};
+use crate::handlers::revisions::{
+    diff_card_revisions, get_card_revisions, restore_card_revision,
+};
+use crate::handlers::saved_queries::{
+    create_saved_query, delete_saved_query_by_id, get_saved_queries, get_saved_query_cards,
+    update_saved_query_by_id,
+};
+use crate::handlers::stats::get_review_stats;
+use crate::handlers::study::{get_leitner_boxes, get_study_mode, set_study_mode};
+use crate::handlers::study_session::{
+    answer_study_card, finish_study, get_study_session, next_study_card, start_study_session,
+};
+use crate::handlers::switcher::switch_cards;
+use crate::handlers::sync::apply_sync;
 use crate::handlers::tags::{create_tag, delete_tag, get_tags, update_tag};
+use crate::handlers::trash::{get_trash, restore_trashed_card};
+use crate::handlers::users::{create_user, get_users, reset_user_password, update_user_by_id};
+use axum::extract::DefaultBodyLimit;
 use axum::middleware::from_fn_with_state;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, patch, post};
 use axum::Router;
 
+/// Decks with media can be far larger than axum's default 2 MB body limit.
+const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
 pub fn router(auth_state: AuthState) -> Router {
     Router::new()
         .route("/", get(|| async { "Hello, World! 🎉" }))
         .route("/auth/status", get(status))
         .route("/auth/login", post(login))
         .route("/auth/logout", post(logout))
-        .route("/auth/api-token", post(generate_api_token))
+        .route("/auth/password", post(change_password))
+        .route("/auth/api-tokens", get(get_api_tokens).post(create_api_token))
+        .route("/auth/api-tokens/:id", delete(revoke_api_token))
+        .route("/batch", post(apply_batch))
         .route("/cards", get(get_cards))
+        .route("/changes", get(get_changes))
         .route("/cards/in_range", get(get_cards_in_range))
+        .route("/cards/search", get(search_cards))
+        .route("/cards/query", get(query_cards))
+        .route("/cards/switcher", get(switch_cards))
+        .route("/cards/:id/children", get(get_card_children))
+        .route("/cards/:id/acl", get(get_card_acl).put(set_card_acl))
+        .route("/cards/:id/revisions", get(get_card_revisions))
+        .route("/cards/:id/revisions/diff", get(diff_card_revisions))
+        .route(
+            "/cards/:id/revisions/:revision_id/restore",
+            post(restore_card_revision),
+        )
         .route(
             "/cards/flush_json",
             get(get_flash_cards_by_tag).post(post_flash_card_result),
         )
+        .route("/cards/quiz", get(get_quiz).post(post_quiz_answer))
+        .route("/cards/export/apkg", get(export_apkg))
+        .route(
+            "/cards/import",
+            post(import_deck).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
         .route(
             "/cards_connect",
             get(get_connectors)
@@ -35,11 +83,34 @@ pub fn router(auth_state: AuthState) -> Router {
             "/card",
             post(create_card).patch(update_card).delete(delete_card),
         )
+        .route(
+            "/saved_queries",
+            get(get_saved_queries).post(create_saved_query),
+        )
+        .route(
+            "/saved_queries/:id",
+            patch(update_saved_query_by_id).delete(delete_saved_query_by_id),
+        )
+        .route("/saved_queries/:id/cards", get(get_saved_query_cards))
+        .route("/stats/reviews", get(get_review_stats))
+        .route("/study/mode", get(get_study_mode).patch(set_study_mode))
+        .route("/study/leitner", get(get_leitner_boxes))
+        .route("/study/sessions", post(start_study_session))
+        .route("/study/sessions/:id", get(get_study_session))
+        .route("/study/sessions/:id/next", post(next_study_card))
+        .route("/study/sessions/:id/answer", post(answer_study_card))
+        .route("/study/sessions/:id/finish", post(finish_study))
+        .route("/sync", post(apply_sync))
         .route("/tags", get(get_tags))
         .route(
             "/tag",
             post(create_tag).patch(update_tag).delete(delete_tag),
         )
+        .route("/trash", get(get_trash))
+        .route("/trash/:id/restore", post(restore_trashed_card))
+        .route("/users", get(get_users).post(create_user))
+        .route("/users/:id", patch(update_user_by_id))
+        .route("/users/:id/password", post(reset_user_password))
         .layer(from_fn_with_state(auth_state.clone(), require_write_auth))
         .with_state(auth_state)
 }
@@ -0,0 +1,422 @@
+use std::{env, sync::Arc};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use crate::models::CardSchedule;
+
+/// Lower bound of the SM-2 ease factor.
+const SM2_MIN_EASE: f64 = 1.3;
+
+/// FSRS-4.5 default parameters.
+const FSRS_WEIGHTS: [f64; 17] = [
+    0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474, 0.1367, 1.0461, 2.1072,
+    0.0793, 0.3246, 1.587, 0.2272, 2.8755,
+];
+const FSRS_DECAY: f64 = -0.5;
+const FSRS_FACTOR: f64 = 19.0 / 81.0;
+/// Probability of recall the next interval is aimed at.
+const FSRS_REQUEST_RETENTION: f64 = 0.9;
+const MAX_INTERVAL_DAYS: f64 = 36500.0;
+
+/// Review period of each Leitner box, in days, unless MEMOAPP_LEITNER_DAYS
+/// is set.
+const DEFAULT_LEITNER_DAYS: [i64; 5] = [1, 2, 4, 8, 16];
+
+/// Spaced-repetition algorithm used by `post_flash_card_result`.
+/// The default is selected with MEMOAPP_SCHEDULER (`sm2`, `fsrs` or
+/// `leitner`, default `sm2`); a tag or frame can override it through
+/// `study_modes`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scheduler {
+    Sm2,
+    Fsrs,
+    Leitner,
+}
+
+impl Scheduler {
+    pub fn from_env() -> Self {
+        env::var("MEMOAPP_SCHEDULER")
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or(Scheduler::Sm2)
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sm2" | "sm-2" => Some(Scheduler::Sm2),
+            "fsrs" => Some(Scheduler::Fsrs),
+            "leitner" => Some(Scheduler::Leitner),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scheduler::Sm2 => "sm2",
+            Scheduler::Fsrs => "fsrs",
+            Scheduler::Leitner => "leitner",
+        }
+    }
+
+    /// Next schedule for a card answered at `now`. The API only knows
+    /// pass/fail, so `is_ok` maps to "good" and a failure to "again".
+    pub fn review(
+        self,
+        state: &CardSchedule,
+        is_ok: bool,
+        now: NaiveDateTime,
+        boxes: &LeitnerBoxes,
+    ) -> CardSchedule {
+        let mut next = match self {
+            Scheduler::Sm2 => sm2(state, is_ok),
+            Scheduler::Fsrs => fsrs(state, is_ok, now),
+            // Leitner due dates follow the box calendar, not an interval.
+            Scheduler::Leitner => return boxes.review(state, is_ok, now),
+        };
+
+        next.interval_days = next.interval_days.clamp(1.0, MAX_INTERVAL_DAYS);
+        next.due_at = Some(now + Duration::seconds((next.interval_days * 86400.0) as i64));
+        next.last_reviewed_at = Some(now);
+        next
+    }
+}
+
+/// Leitner box schedule: box `n` (1-based) is reviewed on the days whose
+/// number since 1970-01-01 is a multiple of its period, so box 1 comes up
+/// every day and higher boxes less often. Set with MEMOAPP_LEITNER_DAYS as a
+/// comma-separated list of periods, e.g. `1,2,4,8,16`.
+#[derive(Clone)]
+pub struct LeitnerBoxes {
+    days: Arc<[i64]>,
+}
+
+impl LeitnerBoxes {
+    pub fn from_env() -> Self {
+        let days: Vec<i64> = env::var("MEMOAPP_LEITNER_DAYS")
+            .ok()
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter_map(|day| day.trim().parse().ok())
+                    .filter(|day: &i64| *day > 0)
+                    .collect()
+            })
+            .filter(|days: &Vec<i64>| !days.is_empty())
+            .unwrap_or_else(|| DEFAULT_LEITNER_DAYS.to_vec());
+        Self { days: days.into() }
+    }
+
+    /// Number of boxes.
+    pub fn count(&self) -> i32 {
+        self.days.len() as i32
+    }
+
+    /// Review period of `leitner_box`, in days.
+    pub fn period(&self, leitner_box: i32) -> i64 {
+        let index = (leitner_box.clamp(1, self.count()) - 1) as usize;
+        self.days[index]
+    }
+
+    /// Whether `leitner_box` comes up for review on `date`.
+    pub fn is_due_on(&self, leitner_box: i32, date: NaiveDate) -> bool {
+        day_number(date).rem_euclid(self.period(leitner_box)) == 0
+    }
+
+    /// Boxes that come up for review on `date`.
+    pub fn due_boxes(&self, date: NaiveDate) -> Vec<i32> {
+        (1..=self.count())
+            .filter(|leitner_box| self.is_due_on(*leitner_box, date))
+            .collect()
+    }
+
+    /// Promote to the next box on success, back to box 1 on failure. The
+    /// card is due again on the next day its new box comes up.
+    fn review(&self, state: &CardSchedule, is_ok: bool, now: NaiveDateTime) -> CardSchedule {
+        let mut next = state.clone();
+        if is_ok {
+            next.leitner_box = (state.leitner_box + 1).clamp(1, self.count());
+            next.reps = state.reps + 1;
+        } else {
+            if state.reps > 0 {
+                next.lapses = state.lapses + 1;
+            }
+            next.leitner_box = 1;
+            next.reps = 0;
+        }
+
+        let today = now.date();
+        let period = self.period(next.leitner_box);
+        let due = today + Duration::days(period - day_number(today).rem_euclid(period));
+        next.interval_days = (due - today).num_days() as f64;
+        next.due_at = due.and_hms_opt(0, 0, 0);
+        next.last_reviewed_at = Some(now);
+        next
+    }
+}
+
+fn day_number(date: NaiveDate) -> i64 {
+    (date - NaiveDate::default()).num_days()
+}
+
+fn sm2(state: &CardSchedule, is_ok: bool) -> CardSchedule {
+    let quality: f64 = if is_ok { 4.0 } else { 1.0 };
+    let ease = (state.ease_factor + 0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02))
+        .max(SM2_MIN_EASE);
+
+    let mut next = state.clone();
+    next.ease_factor = ease;
+    if is_ok {
+        next.interval_days = match state.reps {
+            0 => 1.0,
+            1 => 6.0,
+            _ => (state.interval_days * state.ease_factor).round(),
+        };
+        next.reps = state.reps + 1;
+    } else {
+        // Failing a card that was never learned is not a lapse.
+        if state.reps > 0 {
+            next.lapses = state.lapses + 1;
+        }
+        next.interval_days = 1.0;
+        next.reps = 0;
+    }
+    next
+}
+
+fn fsrs(state: &CardSchedule, is_ok: bool, now: NaiveDateTime) -> CardSchedule {
+    let w = &FSRS_WEIGHTS;
+    let grade: f64 = if is_ok { 3.0 } else { 1.0 };
+
+    let mut next = state.clone();
+    if state.stability <= 0.0 {
+        // First review (or a card scheduled by SM-2 until now).
+        next.stability = w[grade as usize - 1];
+        next.difficulty = fsrs_initial_difficulty(grade);
+    } else {
+        let elapsed_days = state
+            .last_reviewed_at
+            .map(|last| (now - last).num_seconds() as f64 / 86400.0)
+            .unwrap_or(0.0)
+            .max(0.0);
+        let stability = state.stability;
+        let difficulty = if state.difficulty > 0.0 {
+            state.difficulty
+        } else {
+            fsrs_initial_difficulty(3.0)
+        };
+        let retrievability = (1.0 + FSRS_FACTOR * elapsed_days / stability).powf(FSRS_DECAY);
+
+        next.stability = if is_ok {
+            stability
+                * (1.0
+                    + w[8].exp()
+                        * (11.0 - difficulty)
+                        * stability.powf(-w[9])
+                        * ((w[10] * (1.0 - retrievability)).exp() - 1.0))
+        } else {
+            (w[11]
+                * difficulty.powf(-w[12])
+                * ((stability + 1.0).powf(w[13]) - 1.0)
+                * (w[14] * (1.0 - retrievability)).exp())
+            .min(stability)
+        };
+
+        // Mean reversion towards the difficulty of an "easy" first answer.
+        let difficulty = difficulty - w[6] * (grade - 3.0);
+        next.difficulty =
+            (w[7] * fsrs_initial_difficulty(4.0) + (1.0 - w[7]) * difficulty).clamp(1.0, 10.0);
+    }
+
+    if is_ok {
+        next.reps = state.reps + 1;
+    } else {
+        if state.reps > 0 {
+            next.lapses = state.lapses + 1;
+        }
+        next.reps = 0;
+    }
+    next.interval_days = (next.stability / FSRS_FACTOR
+        * (FSRS_REQUEST_RETENTION.powf(1.0 / FSRS_DECAY) - 1.0))
+        .round();
+    next
+}
+
+fn fsrs_initial_difficulty(grade: f64) -> f64 {
+    (FSRS_WEIGHTS[4] - (grade - 3.0) * FSRS_WEIGHTS[5]).clamp(1.0, 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxes() -> LeitnerBoxes {
+        LeitnerBoxes {
+            days: DEFAULT_LEITNER_DAYS.to_vec().into(),
+        }
+    }
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_accepts_known_names_only() {
+        assert!(Scheduler::parse(" SM-2 ") == Some(Scheduler::Sm2));
+        assert!(Scheduler::parse("Fsrs") == Some(Scheduler::Fsrs));
+        assert!(Scheduler::parse("leitner") == Some(Scheduler::Leitner));
+        assert!(Scheduler::parse("").is_none());
+        assert!(Scheduler::parse("anki").is_none());
+        for scheduler in [Scheduler::Sm2, Scheduler::Fsrs, Scheduler::Leitner] {
+            assert!(Scheduler::parse(scheduler.as_str()) == Some(scheduler));
+        }
+    }
+
+    #[test]
+    fn sm2_grows_interval_on_success() {
+        let now = at(2024, 1, 1);
+        let first = Scheduler::Sm2.review(&CardSchedule::default(), true, now, &boxes());
+        assert_eq!(first.interval_days, 1.0);
+        assert_eq!(first.reps, 1);
+        assert_eq!(first.due_at, Some(now + Duration::days(1)));
+        assert_eq!(first.last_reviewed_at, Some(now));
+
+        let second = Scheduler::Sm2.review(&first, true, now, &boxes());
+        assert_eq!(second.interval_days, 6.0);
+        let third = Scheduler::Sm2.review(&second, true, now, &boxes());
+        assert_eq!(third.interval_days, 15.0);
+        assert_eq!(third.reps, 3);
+    }
+
+    #[test]
+    fn sm2_resets_on_failure() {
+        let now = at(2024, 1, 1);
+        let learned = CardSchedule {
+            interval_days: 15.0,
+            reps: 3,
+            ..CardSchedule::default()
+        };
+        let next = Scheduler::Sm2.review(&learned, false, now, &boxes());
+        assert_eq!(next.interval_days, 1.0);
+        assert_eq!(next.reps, 0);
+        assert_eq!(next.lapses, 1);
+        assert!((next.ease_factor - 1.96).abs() < 1e-9);
+
+        // A card that was never learned does not lapse.
+        let new = Scheduler::Sm2.review(&CardSchedule::default(), false, now, &boxes());
+        assert_eq!(new.lapses, 0);
+    }
+
+    #[test]
+    fn sm2_ease_never_drops_below_floor() {
+        let mut state = CardSchedule::default();
+        for _ in 0..10 {
+            state = Scheduler::Sm2.review(&state, false, at(2024, 1, 1), &boxes());
+        }
+        assert_eq!(state.ease_factor, SM2_MIN_EASE);
+    }
+
+    #[test]
+    fn interval_is_capped() {
+        let now = at(2024, 1, 1);
+        let state = CardSchedule {
+            interval_days: 30000.0,
+            reps: 5,
+            ..CardSchedule::default()
+        };
+        let next = Scheduler::Sm2.review(&state, true, now, &boxes());
+        assert_eq!(next.interval_days, MAX_INTERVAL_DAYS);
+        assert_eq!(
+            next.due_at,
+            Some(now + Duration::days(MAX_INTERVAL_DAYS as i64))
+        );
+    }
+
+    #[test]
+    fn fsrs_first_review_uses_initial_stability() {
+        let now = at(2024, 1, 1);
+        let good = Scheduler::Fsrs.review(&CardSchedule::default(), true, now, &boxes());
+        assert_eq!(good.stability, FSRS_WEIGHTS[2]);
+        assert_eq!(good.difficulty, fsrs_initial_difficulty(3.0));
+        assert_eq!(good.interval_days, 4.0);
+
+        // Stability below a day still schedules the card for tomorrow.
+        let again = Scheduler::Fsrs.review(&CardSchedule::default(), false, now, &boxes());
+        assert_eq!(again.stability, FSRS_WEIGHTS[0]);
+        assert_eq!(again.interval_days, 1.0);
+    }
+
+    #[test]
+    fn fsrs_success_grows_and_failure_shrinks_stability() {
+        let first =
+            Scheduler::Fsrs.review(&CardSchedule::default(), true, at(2024, 1, 1), &boxes());
+        let later = at(2024, 1, 5);
+
+        let good = Scheduler::Fsrs.review(&first, true, later, &boxes());
+        assert!(good.stability > first.stability);
+        assert!(good.interval_days > first.interval_days);
+        assert_eq!(good.reps, 2);
+
+        let again = Scheduler::Fsrs.review(&first, false, later, &boxes());
+        assert!(again.stability <= first.stability);
+        assert!((1.0..=10.0).contains(&again.difficulty));
+        assert_eq!(again.reps, 0);
+        assert_eq!(again.lapses, 1);
+    }
+
+    #[test]
+    fn leitner_period_clamps_box() {
+        let boxes = boxes();
+        assert_eq!(boxes.count(), 5);
+        assert_eq!(boxes.period(0), 1);
+        assert_eq!(boxes.period(3), 4);
+        assert_eq!(boxes.period(99), 16);
+    }
+
+    #[test]
+    fn leitner_due_boxes_follow_calendar() {
+        let boxes = boxes();
+        // Day 0 is due for every box.
+        assert_eq!(boxes.due_boxes(NaiveDate::default()), vec![1, 2, 3, 4, 5]);
+        // 1970-01-05 is day 4.
+        let date = NaiveDate::from_ymd_opt(1970, 1, 5).unwrap();
+        assert_eq!(boxes.due_boxes(date), vec![1, 2, 3]);
+        assert!(boxes.is_due_on(3, date));
+        assert!(!boxes.is_due_on(4, date));
+        // Days before 1970 still land on the same calendar.
+        let before = NaiveDate::from_ymd_opt(1969, 12, 30).unwrap();
+        assert_eq!(boxes.due_boxes(before), vec![1, 2]);
+    }
+
+    #[test]
+    fn leitner_promotes_and_demotes() {
+        // 1970-01-05 is day 4.
+        let now = at(1970, 1, 5);
+        let promoted = Scheduler::Leitner.review(&CardSchedule::default(), true, now, &boxes());
+        assert_eq!(promoted.leitner_box, 2);
+        assert_eq!(promoted.interval_days, 2.0);
+        assert_eq!(
+            promoted.due_at,
+            NaiveDate::from_ymd_opt(1970, 1, 7)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+        );
+
+        let top = CardSchedule {
+            leitner_box: 5,
+            reps: 4,
+            ..CardSchedule::default()
+        };
+        let stays = Scheduler::Leitner.review(&top, true, now, &boxes());
+        assert_eq!(stays.leitner_box, 5);
+        assert_eq!(stays.interval_days, 12.0);
+
+        let demoted = Scheduler::Leitner.review(&top, false, now, &boxes());
+        assert_eq!(demoted.leitner_box, 1);
+        assert_eq!(demoted.interval_days, 1.0);
+        assert_eq!(demoted.lapses, 1);
+        assert_eq!(demoted.reps, 0);
+    }
+}
@@ -1,4 +1,8 @@
 mod dimmension;
+mod search;
+mod tag_filter;
 
 pub use dimmension::Dimmension;
-pub use dimmension::RangeParams;
\ No newline at end of file
+pub use dimmension::RangeParams;
+pub use search::{CardQueryParams, SearchParams};
+pub use tag_filter::{TagFilter, TagFilterParams};
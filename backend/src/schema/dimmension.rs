@@ -7,5 +7,5 @@ pub struct RangeParams {
     pub max_y: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct Dimmension { pub x: f64, pub y: f64 }
\ No newline at end of file
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+/// Query of `/cards/search`.
+#[derive(Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    /// Hits per page (default 20, at most 100).
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Query of `/cards/query`. See `card_query` for the syntax.
+#[derive(Deserialize)]
+pub struct CardQueryParams {
+    #[serde(default)]
+    pub q: String,
+}
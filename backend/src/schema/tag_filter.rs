@@ -0,0 +1,107 @@
+use serde::Deserialize;
+use sqlx::{MySql, QueryBuilder};
+
+/// Tag filter query parameters, for listings without other parameters.
+#[derive(Deserialize)]
+pub struct TagFilterParams {
+    tag: Option<String>,
+    tags: Option<String>,
+    mode: Option<String>,
+    exclude: Option<String>,
+}
+
+impl TagFilterParams {
+    pub fn to_filter(&self) -> Result<TagFilter, String> {
+        TagFilter::from_params(
+            self.tag.as_deref(),
+            self.tags.as_deref(),
+            self.mode.as_deref(),
+            self.exclude.as_deref(),
+        )
+    }
+}
+
+/// Boolean tag filter, e.g. `tags=rust,async&mode=all&exclude=done`.
+/// Tag names are matched exactly; lists are comma-separated.
+pub struct TagFilter {
+    pub include: Vec<String>,
+    /// `mode=all`: a card needs every included tag. Otherwise any one.
+    pub match_all: bool,
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Build a filter from query parameters. The legacy single `tag` is
+    /// treated as one more entry of `tags`.
+    pub fn from_params(
+        tag: Option<&str>,
+        tags: Option<&str>,
+        mode: Option<&str>,
+        exclude: Option<&str>,
+    ) -> Result<Self, String> {
+        let match_all = match mode.map(str::trim) {
+            None | Some("") | Some("any") => false,
+            Some("all") => true,
+            Some(_) => return Err("mode must be all or any".to_string()),
+        };
+
+        let mut include = split_names(tags);
+        if let Some(tag) = tag.map(str::trim).filter(|tag| !tag.is_empty()) {
+            if !include.iter().any(|name| name == tag) {
+                include.insert(0, tag.to_string());
+            }
+        }
+
+        Ok(Self {
+            include,
+            match_all,
+            exclude: split_names(exclude),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Append ` AND ...` conditions on `c.id` (the queried card) to `query`.
+    pub fn push_conditions(&self, query: &mut QueryBuilder<'_, MySql>) {
+        if !self.include.is_empty() {
+            query.push(" AND c.id IN (");
+            push_tagged_card_ids(query, &self.include);
+            if self.match_all {
+                query
+                    .push(" GROUP BY ct.card_id HAVING COUNT(DISTINCT t.name) = ")
+                    .push_bind(self.include.len() as i64);
+            }
+            query.push(")");
+        }
+        if !self.exclude.is_empty() {
+            query.push(" AND c.id NOT IN (");
+            push_tagged_card_ids(query, &self.exclude);
+            query.push(")");
+        }
+    }
+}
+
+// names のいずれかのタグが付いたカード ID
+fn push_tagged_card_ids(query: &mut QueryBuilder<'_, MySql>, names: &[String]) {
+    query.push(
+        "SELECT ct.card_id FROM card_tag ct \
+         INNER JOIN tags t ON t.id = ct.tag_id WHERE t.name IN (",
+    );
+    let mut separated = query.separated(", ");
+    for name in names {
+        separated.push_bind(name.clone());
+    }
+    query.push(")");
+}
+
+fn split_names(value: Option<&str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in value.unwrap_or_default().split(',').map(str::trim) {
+        if !name.is_empty() && !names.iter().any(|existing| existing == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
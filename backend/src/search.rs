@@ -0,0 +1,452 @@
+//! Full-text search over card titles and contents.
+//!
+//! MariaDB's FULLTEXT parser splits on spaces and has no n-gram parser, so
+//! Japanese text is tokenized here and the terms are stored in
+//! `card_search_terms`:
+//!
+//! - Runs of letters and digits (Latin, Cyrillic, ...) become one term each.
+//!   Query words match terms by prefix.
+//! - Runs of CJK characters (kanji, kana, hangul) are indexed as unigrams and
+//!   bigrams. A query run matches through its bigrams (or its single
+//!   character), so `東京都` finds documents containing `東京` and `京都`.
+//!
+//! Text is normalized first: full-width ASCII becomes half-width and letters
+//! are lowercased.
+
+use std::collections::HashMap;
+
+use crate::markdown::escape_html;
+
+/// Longest term stored (`card_search_terms.term` is VARCHAR(64)).
+const MAX_TERM_CHARS: usize = 64;
+
+/// Query terms beyond this are ignored.
+const MAX_QUERY_TERMS: usize = 32;
+
+/// Title matches count this many times a contents match.
+pub const TITLE_WEIGHT: f64 = 3.0;
+
+// BM25 parameters.
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+const SNIPPET_CHARS: usize = 120;
+/// Characters of context kept before the first match in a snippet.
+const SNIPPET_LEAD: usize = 30;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryTerm {
+    pub term: String,
+    /// Matches any term starting with `term`.
+    pub prefix: bool,
+}
+
+impl QueryTerm {
+    pub fn matches(&self, term: &str) -> bool {
+        if self.prefix {
+            term.starts_with(&self.term)
+        } else {
+            term == self.term
+        }
+    }
+}
+
+/// Occurrences of one indexed term in a card, with the card's lengths.
+pub struct TermHit {
+    pub term: String,
+    pub title_count: i32,
+    pub contents_count: i32,
+    pub title_length: i32,
+    pub contents_length: i32,
+}
+
+/// Term counts of `text`, for the index.
+pub fn index_terms(text: &str) -> HashMap<String, i32> {
+    let mut counts = HashMap::new();
+    for run in runs(text) {
+        match run {
+            Run::Word(word) => *counts.entry(truncate(&word)).or_insert(0) += 1,
+            Run::Cjk(chars) => {
+                for c in &chars {
+                    *counts.entry(c.to_string()).or_insert(0) += 1;
+                }
+                for pair in chars.windows(2) {
+                    *counts.entry(pair.iter().collect()).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// Terms of a search query. Every term must match for a card to be a hit.
+pub fn query_terms(query: &str) -> Vec<QueryTerm> {
+    let mut terms: Vec<QueryTerm> = Vec::new();
+    for run in runs(query) {
+        let run_terms = match run {
+            Run::Word(word) => vec![QueryTerm {
+                term: truncate(&word),
+                prefix: true,
+            }],
+            Run::Cjk(chars) if chars.len() == 1 => vec![QueryTerm {
+                term: chars[0].to_string(),
+                prefix: false,
+            }],
+            Run::Cjk(chars) => chars
+                .windows(2)
+                .map(|pair| QueryTerm {
+                    term: pair.iter().collect(),
+                    prefix: false,
+                })
+                .collect(),
+        };
+        for term in run_terms {
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+    }
+    terms.truncate(MAX_QUERY_TERMS);
+    terms
+}
+
+/// BM25 score of one card. `hits` are the card's indexed terms matching
+/// `terms`; `document_frequency[i]` is the number of cards matching
+/// `terms[i]`. Returns `None` unless every term matches.
+pub fn score(
+    terms: &[QueryTerm],
+    hits: &[TermHit],
+    document_frequency: &[usize],
+    document_count: usize,
+    average_length: f64,
+) -> Option<f64> {
+    let length = hits
+        .first()
+        .map(|hit| TITLE_WEIGHT * hit.title_length as f64 + hit.contents_length as f64)
+        .unwrap_or(0.0);
+    let norm = 1.0 - BM25_B + BM25_B * length / average_length.max(1.0);
+
+    let mut total = 0.0;
+    for (term, df) in terms.iter().zip(document_frequency) {
+        let frequency: f64 = hits
+            .iter()
+            .filter(|hit| term.matches(&hit.term))
+            .map(|hit| TITLE_WEIGHT * hit.title_count as f64 + hit.contents_count as f64)
+            .sum();
+        if frequency == 0.0 {
+            return None;
+        }
+        let n = document_count.max(*df) as f64;
+        let idf = (1.0 + (n - *df as f64 + 0.5) / (*df as f64 + 0.5)).ln();
+        total += idf * frequency * (BM25_K1 + 1.0) / (frequency + BM25_K1 * norm);
+    }
+    Some(total)
+}
+
+/// `text` as HTML-escaped plain text with every match of `terms` wrapped in
+/// `<mark>`. Whitespace is collapsed.
+pub fn highlight(text: &str, terms: &[QueryTerm]) -> String {
+    let chars = collapse_whitespace(text);
+    let marks = match_ranges(&chars, terms);
+    render(&chars, &marks, 0, chars.len())
+}
+
+/// About `SNIPPET_CHARS` characters of `text` around the first match, like
+/// `highlight`. Starts at the beginning when nothing matches.
+pub fn snippet(text: &str, terms: &[QueryTerm]) -> String {
+    let chars = collapse_whitespace(text);
+    let marks = match_ranges(&chars, terms);
+
+    let start = marks
+        .first()
+        .map(|(start, _)| start.saturating_sub(SNIPPET_LEAD))
+        .unwrap_or(0);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+    let start = end.saturating_sub(SNIPPET_CHARS);
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    out.push_str(&render(&chars, &marks, start, end));
+    if end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+enum Run {
+    Word(String),
+    Cjk(Vec<char>),
+}
+
+fn runs(text: &str) -> Vec<Run> {
+    let mut runs = Vec::new();
+    let mut word = String::new();
+    let mut cjk = Vec::new();
+
+    for c in text.chars().map(normalize) {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                runs.push(Run::Word(std::mem::take(&mut word)));
+            }
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            if !cjk.is_empty() {
+                runs.push(Run::Cjk(std::mem::take(&mut cjk)));
+            }
+            word.push(c);
+        } else {
+            if !word.is_empty() {
+                runs.push(Run::Word(std::mem::take(&mut word)));
+            }
+            if !cjk.is_empty() {
+                runs.push(Run::Cjk(std::mem::take(&mut cjk)));
+            }
+        }
+    }
+    if !word.is_empty() {
+        runs.push(Run::Word(word));
+    }
+    if !cjk.is_empty() {
+        runs.push(Run::Cjk(cjk));
+    }
+
+    runs
+}
+
+/// One character in, one character out, so positions in normalized text are
+/// positions in the original.
+pub fn normalize(c: char) -> char {
+    let c = match c {
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    };
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'   // hiragana, katakana
+        | '\u{3400}'..='\u{4dbf}' // CJK extension A
+        | '\u{4e00}'..='\u{9fff}' // CJK unified ideographs
+        | '\u{f900}'..='\u{faff}' // CJK compatibility ideographs
+        | '\u{ac00}'..='\u{d7af}' // hangul syllables
+        | '\u{ff66}'..='\u{ff9f}' // half-width katakana
+        | '\u{20000}'..='\u{2ffff}')
+}
+
+fn truncate(term: &str) -> String {
+    term.chars().take(MAX_TERM_CHARS).collect()
+}
+
+fn collapse_whitespace(text: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(text.len());
+    for c in text.trim().chars() {
+        if c.is_whitespace() {
+            if chars.last() != Some(&' ') {
+                chars.push(' ');
+            }
+        } else {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+/// Sorted, merged character ranges of `chars` matching `terms`. Prefix terms
+/// only match at the start of a word.
+fn match_ranges(chars: &[char], terms: &[QueryTerm]) -> Vec<(usize, usize)> {
+    let normalized: Vec<char> = chars.iter().copied().map(normalize).collect();
+    let mut ranges = Vec::new();
+
+    for term in terms {
+        let needle: Vec<char> = term.term.chars().collect();
+        if needle.is_empty() || needle.len() > normalized.len() {
+            continue;
+        }
+        for start in 0..=normalized.len() - needle.len() {
+            if normalized[start..start + needle.len()] != needle[..] {
+                continue;
+            }
+            let at_word_start = start == 0 || {
+                let prev = normalized[start - 1];
+                !prev.is_alphanumeric() || is_cjk(prev)
+            };
+            if term.prefix && !at_word_start {
+                continue;
+            }
+            ranges.push((start, start + needle.len()));
+        }
+    }
+
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn render(chars: &[char], marks: &[(usize, usize)], start: usize, end: usize) -> String {
+    let mut out = String::new();
+    let mut position = start;
+
+    for &(mark_start, mark_end) in marks {
+        let mark_start = mark_start.clamp(start, end);
+        let mark_end = mark_end.clamp(start, end);
+        if mark_start >= mark_end || mark_start < position {
+            continue;
+        }
+        out.push_str(&escape_html(
+            &chars[position..mark_start].iter().collect::<String>(),
+        ));
+        out.push_str("<mark>");
+        out.push_str(&escape_html(
+            &chars[mark_start..mark_end].iter().collect::<String>(),
+        ));
+        out.push_str("</mark>");
+        position = mark_end;
+    }
+    out.push_str(&escape_html(
+        &chars[position..end].iter().collect::<String>(),
+    ));
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exact(term: &str) -> QueryTerm {
+        QueryTerm {
+            term: term.to_string(),
+            prefix: false,
+        }
+    }
+
+    fn prefix(term: &str) -> QueryTerm {
+        QueryTerm {
+            term: term.to_string(),
+            prefix: true,
+        }
+    }
+
+    #[test]
+    fn cjk_runs_are_indexed_as_unigrams_and_bigrams() {
+        let terms = index_terms("東京都");
+        let mut keys: Vec<&str> = terms.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec!["京", "京都", "東", "東京", "都"]);
+        assert!(terms.values().all(|count| *count == 1));
+
+        assert_eq!(index_terms("東京と東京")["東京"], 2);
+    }
+
+    #[test]
+    fn cjk_queries_match_through_bigrams() {
+        let terms = query_terms("東京都");
+        assert_eq!(terms, vec![exact("東京"), exact("京都")]);
+
+        let index = index_terms("京都と東京");
+        assert!(terms
+            .iter()
+            .all(|term| index.keys().any(|key| term.matches(key))));
+
+        assert_eq!(query_terms("都"), vec![exact("都")]);
+    }
+
+    #[test]
+    fn words_are_prefix_terms_split_from_cjk() {
+        assert_eq!(
+            query_terms("Rust入門 rust"),
+            vec![prefix("rust"), exact("入門")]
+        );
+        assert_eq!(index_terms("Rust入門")["rust"], 1);
+        assert!(prefix("rus").matches("rust"));
+        assert!(!exact("rus").matches("rust"));
+    }
+
+    #[test]
+    fn full_width_text_is_normalized() {
+        assert_eq!(normalize('Ａ'), 'a');
+        assert_eq!(normalize('１'), '1');
+        assert_eq!(normalize('\u{3000}'), ' ');
+        assert_eq!(normalize('東'), '東');
+
+        assert_eq!(index_terms("ＡＢＣ１２３")["abc123"], 1);
+        assert_eq!(
+            query_terms("ＡＢＣ　ｄｅｆ"),
+            vec![prefix("abc"), prefix("def")]
+        );
+    }
+
+    #[test]
+    fn empty_and_punctuation_only_text_has_no_terms() {
+        assert!(index_terms("").is_empty());
+        assert!(query_terms("").is_empty());
+        assert!(query_terms(" !? 、。").is_empty());
+    }
+
+    #[test]
+    fn long_terms_and_queries_are_cut() {
+        let long = "a".repeat(MAX_TERM_CHARS + 10);
+        assert_eq!(query_terms(&long)[0].term.chars().count(), MAX_TERM_CHARS);
+
+        let many: Vec<String> = (0..MAX_QUERY_TERMS + 8).map(|i| format!("w{i}")).collect();
+        assert_eq!(query_terms(&many.join(" ")).len(), MAX_QUERY_TERMS);
+    }
+
+    #[test]
+    fn highlight_marks_matches_and_escapes_html() {
+        assert_eq!(
+            highlight("Hello <world>", &query_terms("wor")),
+            "Hello &lt;<mark>wor</mark>ld&gt;"
+        );
+        // Prefix terms only match at the start of a word.
+        assert_eq!(
+            highlight("rust trust", &query_terms("rust")),
+            "<mark>rust</mark> trust"
+        );
+        // Overlapping bigrams become one mark.
+        assert_eq!(
+            highlight("東京都庁", &query_terms("東京都")),
+            "<mark>東京都</mark>庁"
+        );
+        assert_eq!(highlight(" a \n\t b ", &[]), "a b");
+    }
+
+    #[test]
+    fn snippet_is_a_window_around_the_first_match() {
+        let text = format!("{}東京都{}", "a ".repeat(100), " b".repeat(100));
+        let window = snippet(&text, &query_terms("東京都"));
+        assert!(window.starts_with('…'));
+        assert!(window.ends_with('…'));
+        assert!(window.contains("<mark>東京都</mark>"));
+        let plain = window.replace("<mark>", "").replace("</mark>", "");
+        assert_eq!(plain.chars().count(), SNIPPET_CHARS + 2);
+        assert!(plain.starts_with(&format!("…{}", "a ".repeat(SNIPPET_LEAD / 2))));
+    }
+
+    #[test]
+    fn snippet_window_stays_inside_the_text() {
+        let text = format!("{}end", "a ".repeat(100));
+        let window = snippet(&text, &query_terms("end"));
+        assert!(window.starts_with('…'));
+        assert!(window.ends_with("<mark>end</mark>"));
+        let plain = window.replace("<mark>", "").replace("</mark>", "");
+        assert_eq!(plain.chars().count(), SNIPPET_CHARS + 1);
+
+        // Without a match the snippet starts at the beginning.
+        assert_eq!(snippet("short text", &query_terms("zzz")), "short text");
+    }
+}
@@ -0,0 +1,558 @@
+//! In-memory fuzzy matcher over card titles and first H1s, for the
+//! quick-switcher.
+//!
+//! The index is a snapshot of every card, rebuilt in the background after
+//! each successful write to cards (`refresh_after_writes`). Lookups never
+//! touch the database; a rebuild triggered while one is running is coalesced
+//! into one more rebuild.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use axum::{extract::Request, http::Method, middleware::Next, response::Response, Extension};
+use serde::Serialize;
+use sqlx::{FromRow, MySql, Pool};
+use tokio::sync::Notify;
+
+use crate::{markdown::split_first_h1, schema::Dimmension, search::normalize};
+
+/// Queries are cut to this many characters.
+const MAX_QUERY_CHARS: usize = 64;
+
+// 全カードのタイトル・位置（位置は親からの相対座標）と親（複数あれば最小の ID）
+const SELECT_TITLE_ENTRIES: &str = r#"
+SELECT
+    c.id,
+    COALESCE(c.title, '') AS title,
+    COALESCE(c.contents, '') AS contents,
+    c.card_type,
+    ST_X(ST_PointN(ST_ExteriorRing(c.shape), 1)) AS pos_x,
+    ST_Y(ST_PointN(ST_ExteriorRing(c.shape), 1)) AS pos_y,
+    (ST_X(ST_PointN(ST_ExteriorRing(c.shape), 3)) - ST_X(ST_PointN(ST_ExteriorRing(c.shape), 1))) AS size_x,
+    (ST_Y(ST_PointN(ST_ExteriorRing(c.shape), 3)) - ST_Y(ST_PointN(ST_ExteriorRing(c.shape), 1))) AS size_y,
+    (SELECT MIN(cc.card_parent_id) FROM card_card cc WHERE cc.card_child_id = c.id) AS parent_id
+FROM cards c
+WHERE c.deleted_at IS NULL
+"#;
+
+#[derive(FromRow)]
+struct TitleEntryRow {
+    id: i64,
+    title: String,
+    contents: String,
+    card_type: String,
+    pos_x: f64,
+    pos_y: f64,
+    size_x: f64,
+    size_y: f64,
+    parent_id: Option<i64>,
+}
+
+struct TitleEntry {
+    id: i64,
+    title: String,
+    /// Title, then the first H1 if it differs.
+    keys: Vec<Key>,
+    card_type: String,
+    position: (f64, f64),
+    size: (f64, f64),
+}
+
+/// A quick-switcher result. `position` is absolute canvas coordinates, with
+/// the offsets of all parent cards added.
+#[derive(Serialize)]
+pub struct TitleMatch {
+    pub id: i64,
+    pub title: String,
+    pub card_type: String,
+    pub position: Dimmension,
+    pub size: Dimmension,
+    pub score: u32,
+}
+
+#[derive(Clone, Default)]
+pub struct TitleIndex {
+    entries: Arc<RwLock<Arc<Vec<TitleEntry>>>>,
+    refresh: Arc<Notify>,
+}
+
+impl TitleIndex {
+    /// Load the index now, then rebuild it whenever `invalidate` is called.
+    pub async fn start(pool: Pool<MySql>) -> Result<Self, sqlx::Error> {
+        let index = Self::default();
+        index.reload(&pool).await?;
+
+        let background = index.clone();
+        tokio::spawn(async move {
+            loop {
+                background.refresh.notified().await;
+                if let Err(e) = background.reload(&pool).await {
+                    eprintln!("title index refresh failed: {e}");
+                }
+            }
+        });
+
+        Ok(index)
+    }
+
+    /// Schedule a rebuild.
+    pub fn invalidate(&self) {
+        self.refresh.notify_one();
+    }
+
+    async fn reload(&self, pool: &Pool<MySql>) -> Result<(), sqlx::Error> {
+        let rows = sqlx::query_as::<_, TitleEntryRow>(SELECT_TITLE_ENTRIES)
+            .fetch_all(pool)
+            .await?;
+        let entries = build_entries(rows);
+        *self.entries.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(entries);
+        Ok(())
+    }
+
+    /// Best matches for `query` among the cards `can_read` lets through, best
+    /// first.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        can_read: impl Fn(i64) -> bool,
+    ) -> Vec<TitleMatch> {
+        let query: Vec<char> = query
+            .trim()
+            .chars()
+            .map(normalize)
+            .take(MAX_QUERY_CHARS)
+            .collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let query_mask = char_mask(&query);
+        let entries = self
+            .entries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        let mut matches: Vec<(u32, &TitleEntry)> = entries
+            .iter()
+            .filter(|entry| can_read(entry.id))
+            .filter_map(|entry| {
+                entry
+                    .keys
+                    .iter()
+                    .filter_map(|key| match_score(&query, query_mask, key))
+                    .max()
+                    .map(|score| (score, entry))
+            })
+            .collect();
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then(a.keys[0].chars.len().cmp(&b.keys[0].chars.len()))
+                .then(a.id.cmp(&b.id))
+        });
+
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(score, entry)| TitleMatch {
+                id: entry.id,
+                title: entry.title.clone(),
+                card_type: entry.card_type.clone(),
+                position: Dimmension {
+                    x: entry.position.0,
+                    y: entry.position.1,
+                },
+                size: Dimmension {
+                    x: entry.size.0,
+                    y: entry.size.1,
+                },
+                score,
+            })
+            .collect()
+    }
+}
+
+/// Routes whose writes can change a card's title, contents, position,
+/// parent or trash state. Answers, sessions, tags, users and the like leave
+/// the index alone.
+const CARD_WRITE_ROUTES: &[&str] = &[
+    "/batch",
+    "/card",
+    "/cards_connect",
+    "/cards/flush_json",
+    "/cards/import",
+    "/sync",
+];
+
+pub(crate) fn writes_cards(method: &Method, path: &str) -> bool {
+    if matches!(method, &Method::GET | &Method::HEAD | &Method::OPTIONS) {
+        return false;
+    }
+    CARD_WRITE_ROUTES.contains(&path)
+        || (path.starts_with("/trash/") && path.ends_with("/restore"))
+        || (path.starts_with("/cards/")
+            && path.contains("/revisions/")
+            && path.ends_with("/restore"))
+}
+
+/// Rebuild the title index after every successful write to cards. Hooked on
+/// routes rather than in each card handler so the batch, sync and import
+/// paths cannot be missed.
+pub async fn refresh_after_writes(
+    Extension(index): Extension<TitleIndex>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_write = writes_cards(req.method(), req.uri().path());
+    let response = next.run(req).await;
+    if is_write && response.status().is_success() {
+        index.invalidate();
+    }
+    response
+}
+
+fn build_entries(rows: Vec<TitleEntryRow>) -> Vec<TitleEntry> {
+    let relative: HashMap<i64, ((f64, f64), Option<i64>)> = rows
+        .iter()
+        .map(|row| (row.id, ((row.pos_x, row.pos_y), row.parent_id)))
+        .collect();
+
+    rows.into_iter()
+        .map(|row| {
+            let mut keys = vec![Key::new(&row.title)];
+            if let (Some(heading), _) = split_first_h1(&row.contents) {
+                let heading = Key::new(&heading);
+                if !heading.chars.is_empty() && heading.chars != keys[0].chars {
+                    keys.push(heading);
+                }
+            }
+
+            TitleEntry {
+                id: row.id,
+                position: absolute_position(row.id, &relative),
+                title: row.title,
+                keys,
+                card_type: row.card_type,
+                size: (row.size_x, row.size_y),
+            }
+        })
+        .collect()
+}
+
+/// Add up the offsets along the parent chain. Stops at a repeated card, in
+/// case `card_card` ever contains a cycle.
+fn absolute_position(
+    card_id: i64,
+    relative: &HashMap<i64, ((f64, f64), Option<i64>)>,
+) -> (f64, f64) {
+    let mut position = (0.0, 0.0);
+    let mut seen = Vec::new();
+    let mut current = Some(card_id);
+
+    while let Some(id) = current {
+        if seen.contains(&id) {
+            break;
+        }
+        seen.push(id);
+        let Some(((x, y), parent_id)) = relative.get(&id) else {
+            break;
+        };
+        position.0 += x;
+        position.1 += y;
+        current = *parent_id;
+    }
+
+    position
+}
+
+/// A normalized title or heading, with what matching needs precomputed.
+struct Key {
+    chars: Vec<char>,
+    /// Indexes where a word starts.
+    word_starts: Vec<usize>,
+    /// Bloom filter of the characters in `chars`.
+    mask: u64,
+}
+
+impl Key {
+    fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.trim().chars().map(normalize).collect();
+        let word_starts = (0..chars.len())
+            .filter(|&i| i == 0 || (!chars[i - 1].is_alphanumeric() && chars[i].is_alphanumeric()))
+            .collect();
+        let mask = char_mask(&chars);
+        Self {
+            chars,
+            word_starts,
+            mask,
+        }
+    }
+}
+
+fn char_mask(chars: &[char]) -> u64 {
+    chars.iter().fold(0, |mask, c| mask | 1 << (*c as u32 % 64))
+}
+
+/// Score of `query` against one key, or `None` when it does not match.
+/// Tiers, best first: exact, prefix, word prefix, substring, subsequence,
+/// then a prefix (of the key or one of its words) within a few typos.
+fn match_score(query: &[char], query_mask: u64, key: &Key) -> Option<u32> {
+    let chars = &key.chars;
+    let max_typos = match query.len() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    };
+    // Each typo can bring in at most one character the key does not have.
+    if (query_mask & !key.mask).count_ones() > max_typos as u32 || chars.is_empty() {
+        return None;
+    }
+
+    if chars == query {
+        return Some(1000);
+    }
+    if chars.starts_with(query) {
+        return Some(900);
+    }
+    if key
+        .word_starts
+        .iter()
+        .any(|&i| chars[i..].starts_with(query))
+    {
+        return Some(800);
+    }
+    if let Some(position) = chars
+        .windows(query.len())
+        .position(|window| window == query)
+    {
+        return Some(700 - position.min(100) as u32);
+    }
+    if let Some(gaps) = subsequence_gaps(query, chars) {
+        return Some(600 - gaps.min(100) as u32);
+    }
+
+    if max_typos == 0 {
+        return None;
+    }
+    key.word_starts
+        .iter()
+        .filter_map(|&i| prefix_distance(query, &chars[i..], max_typos))
+        .min()
+        .map(|typos| 500 - 100 * typos as u32)
+}
+
+/// Characters skipped between the matched characters when `query` is a
+/// subsequence of `key`.
+fn subsequence_gaps(query: &[char], key: &[char]) -> Option<usize> {
+    let start = key.iter().position(|c| *c == query[0])?;
+    let mut gaps = 0;
+    let mut position = start;
+    for c in &query[1..] {
+        let offset = key[position + 1..].iter().position(|k| k == c)?;
+        gaps += offset;
+        position += offset + 1;
+    }
+    Some(gaps)
+}
+
+/// Smallest edit distance (with transpositions) between `query` and any
+/// prefix of `key`, if at most `max` (at most 2). Only a band of width
+/// `2 * max + 1` is computed, so this stays linear in the query length.
+fn prefix_distance(query: &[char], key: &[char], max: usize) -> Option<usize> {
+    const WIDTH: usize = MAX_QUERY_CHARS + 3;
+
+    let key = &key[..key.len().min(query.len() + max)];
+    // Distances are capped at `max + 1`, so they fit in a byte.
+    let far = (max + 1) as u8;
+    let width = key.len() + 1;
+
+    let mut rows = [[far; WIDTH]; 3];
+    let [before, previous, current] = &mut rows;
+    let (mut before, mut previous, mut current) = (
+        &mut before[..width],
+        &mut previous[..width],
+        &mut current[..width],
+    );
+    for (j, value) in previous.iter_mut().enumerate() {
+        *value = j.min(max + 1) as u8;
+    }
+
+    for i in 1..=query.len() {
+        current.fill(far);
+        current[0] = i.min(max + 1) as u8;
+        let from = i.saturating_sub(max).max(1);
+        let to = (i + max).min(key.len());
+        let mut best = current[0];
+        for j in from..=to {
+            let cost = u8::from(query[i - 1] != key[j - 1]);
+            let mut value = (previous[j - 1] + cost)
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1);
+            if i > 1 && j > 1 && query[i - 1] == key[j - 2] && query[i - 2] == key[j - 1] {
+                value = value.min(before[j - 2] + 1);
+            }
+            current[j] = value.min(far);
+            best = best.min(current[j]);
+        }
+        if best == far {
+            return None;
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous
+        .iter()
+        .copied()
+        .min()
+        .filter(|distance| *distance < far)
+        .map(usize::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().map(normalize).collect()
+    }
+
+    fn score(query: &str, key: &str) -> Option<u32> {
+        let query = chars(query);
+        match_score(&query, char_mask(&query), &Key::new(key))
+    }
+
+    fn row(id: i64, title: &str, parent_id: Option<i64>) -> TitleEntryRow {
+        TitleEntryRow {
+            id,
+            title: title.to_string(),
+            contents: String::new(),
+            card_type: "normal".to_string(),
+            pos_x: 10.0,
+            pos_y: 20.0,
+            size_x: 100.0,
+            size_y: 50.0,
+            parent_id,
+        }
+    }
+
+    fn index(rows: Vec<TitleEntryRow>) -> TitleIndex {
+        let index = TitleIndex::default();
+        *index.entries.write().unwrap() = Arc::new(build_entries(rows));
+        index
+    }
+
+    #[test]
+    fn prefix_distance_counts_typos_against_the_closest_prefix() {
+        assert_eq!(
+            prefix_distance(&chars("tokio"), &chars("tokio runtime"), 2),
+            Some(0)
+        );
+        assert_eq!(
+            prefix_distance(&chars("tokyo"), &chars("tokio"), 2),
+            Some(1)
+        );
+        assert_eq!(
+            prefix_distance(&chars("tokoi"), &chars("tokio"), 2),
+            Some(1)
+        );
+        assert_eq!(prefix_distance(&chars("tkio"), &chars("tokio"), 2), Some(1));
+        assert_eq!(
+            prefix_distance(&chars("toxyo"), &chars("tokio"), 2),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn prefix_distance_gives_up_past_max() {
+        assert_eq!(prefix_distance(&chars("abcdef"), &chars("uvwxyz"), 2), None);
+        assert_eq!(prefix_distance(&chars("tokyo"), &chars("tokio"), 0), None);
+        assert_eq!(prefix_distance(&chars("rust"), &chars(""), 2), None);
+    }
+
+    #[test]
+    fn prefix_distance_handles_the_longest_query() {
+        let query = vec!['a'; MAX_QUERY_CHARS];
+        let mut key = query.clone();
+        key.extend(['b'; 10]);
+        assert_eq!(prefix_distance(&query, &key, 2), Some(0));
+        key[0] = 'z';
+        assert_eq!(prefix_distance(&query, &key, 2), Some(1));
+    }
+
+    #[test]
+    fn match_score_ranks_tiers_in_order() {
+        let exact = score("rust", "Rust").unwrap();
+        let prefix = score("rust", "Rust async").unwrap();
+        let word_prefix = score("async", "Rust async").unwrap();
+        let substring = score("sync", "Rust async").unwrap();
+        let subsequence = score("rsy", "Rust async").unwrap();
+        let typo = score("asinc", "Rust async").unwrap();
+        assert_eq!(exact, 1000);
+        assert_eq!(prefix, 900);
+        assert_eq!(word_prefix, 800);
+        assert!(exact > prefix && prefix > word_prefix && word_prefix > substring);
+        assert!(substring > subsequence && subsequence > typo);
+    }
+
+    #[test]
+    fn match_score_rejects_typos_in_short_queries_and_empty_keys() {
+        assert_eq!(score("rx", "rust"), None);
+        assert_eq!(score("rust", ""), None);
+        assert_eq!(score("rust", "   "), None);
+        assert_eq!(score("python", "rust"), None);
+    }
+
+    #[test]
+    fn match_score_normalizes_case_and_full_width() {
+        assert_eq!(score("ＲＵＳＴ", "rust"), Some(1000));
+        assert_eq!(score("日本", "日本語の勉強"), Some(900));
+        assert_eq!(score("勉強", "日本語の勉強"), Some(700 - 4));
+    }
+
+    #[test]
+    fn search_ignores_blank_queries() {
+        let index = index(vec![row(1, "Rust", None)]);
+        assert!(index.search("", 10, |_| true).is_empty());
+        assert!(index.search("   ", 10, |_| true).is_empty());
+    }
+
+    #[test]
+    fn search_orders_by_score_and_skips_unreadable_cards() {
+        let index = index(vec![
+            row(1, "Rust async", None),
+            row(2, "Rust", None),
+            row(3, "Rust secrets", None),
+        ]);
+        let ids: Vec<i64> = index
+            .search("rust", 10, |id| id != 3)
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec![2, 1]);
+        assert_eq!(index.search("rust", 1, |_| true).len(), 1);
+    }
+
+    #[test]
+    fn search_reports_absolute_positions() {
+        let index = index(vec![row(1, "Frame", None), row(2, "Child", Some(1))]);
+        let found = index.search("child", 10, |_| true);
+        assert_eq!((found[0].position.x, found[0].position.y), (20.0, 40.0));
+    }
+
+    #[test]
+    fn only_card_writes_refresh_the_index() {
+        assert!(writes_cards(&Method::POST, "/card"));
+        assert!(writes_cards(&Method::PATCH, "/card"));
+        assert!(writes_cards(&Method::POST, "/sync"));
+        assert!(writes_cards(&Method::POST, "/trash/5/restore"));
+        assert!(writes_cards(&Method::POST, "/cards/5/revisions/9/restore"));
+        assert!(!writes_cards(&Method::GET, "/card"));
+        assert!(!writes_cards(&Method::POST, "/cards/quiz"));
+        assert!(!writes_cards(&Method::POST, "/study/sessions/1/answer"));
+        assert!(!writes_cards(&Method::PATCH, "/tag"));
+    }
+}
@@ -0,0 +1,63 @@
+//! How long deleted cards stay in the trash, and the background purge of
+//! the ones past that.
+
+use std::{env, time::Duration as StdDuration};
+
+use chrono::{Duration, NaiveDateTime};
+use sqlx::{MySql, Pool};
+
+use crate::db::{purge_trashed_cards, stamp_changes};
+
+/// Days a card stays in the trash unless MEMOAPP_TRASH_RETENTION_DAYS is set.
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+const PURGE_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+
+/// Retention of trashed cards. MEMOAPP_TRASH_RETENTION_DAYS=0 keeps them
+/// until they are restored.
+#[derive(Clone, Copy)]
+pub struct TrashRetention {
+    days: Option<i64>,
+}
+
+impl TrashRetention {
+    pub fn from_env() -> Self {
+        let days = env::var("MEMOAPP_TRASH_RETENTION_DAYS")
+            .ok()
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|days| *days >= 0)
+            .unwrap_or(DEFAULT_RETENTION_DAYS);
+        Self {
+            days: (days > 0).then_some(days),
+        }
+    }
+
+    /// When a card trashed at `deleted_at` is purged.
+    pub fn purge_at(self, deleted_at: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.days.map(|days| deleted_at + Duration::days(days))
+    }
+
+    /// Purge expired cards now and then every hour.
+    pub fn start_purge(self, pool: Pool<MySql>) {
+        let Some(days) = self.days else {
+            return;
+        };
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PURGE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match purge_trashed_cards(&pool, days).await {
+                    Ok(0) => {}
+                    Ok(count) => {
+                        println!("purged {count} card(s) from the trash");
+                        // No request follows a purge to stamp its deletes.
+                        if let Err(e) = stamp_changes(&pool).await {
+                            eprintln!("stamping changes failed: {e}");
+                        }
+                    }
+                    Err(e) => eprintln!("trash purge failed: {e}"),
+                }
+            }
+        });
+    }
+}
@@ -24,7 +24,13 @@ import { CardConnector } from "./schema/Connrctor.js";
 import { Dimmension } from "./schema/Point.js";
 // import { DataCheck } from "./DataCheck/DataCheck.jsx";
 import SideCardTree from "./SideCardTree/SideCardTree.jsx";
-import { generateApiToken, getAuthStatus, login, logout } from "./hooks/useAuthAPI.js";
+import {
+  AuthStatus,
+  generateApiToken,
+  getAuthStatus,
+  login,
+  logout,
+} from "./hooks/useAuthAPI.js";
 
 const LOGIN_QUERY_KEY = import.meta.env.VITE_LOGIN_QUERY_KEY ?? "";
 const LOGIN_QUERY_VALUE = import.meta.env.VITE_LOGIN_QUERY_VALUE ?? "";
@@ -57,8 +63,10 @@ function App() {
     x: 0,
     y: 0,
   });
+  const [authenticated, setAuthenticated] = createSignal(false);
   const [canEdit, setCanEdit] = createSignal(false);
   const [authEnabled, setAuthEnabled] = createSignal(true);
+  const [username, setUsername] = createSignal("");
   const [password, setPassword] = createSignal("");
   const [authError, setAuthError] = createSignal("");
   const [apiToken, setApiToken] = createSignal("");
@@ -216,11 +224,18 @@ function App() {
     return false;
   };
 
-  // Viewers (not authenticated) only see public cards. Admins see everything.
+  // Anonymous visitors only see public cards. Any logged-in user sees
+  // everything, but only editors and admins may change it.
   const displayCards = createMemo(() =>
-    canEdit() ? cards() : cards().filter((c) => c.visibility !== "private"),
+    authenticated() ? cards() : cards().filter((c) => c.visibility !== "private"),
   );
 
+  const applyAuthStatus = (status: AuthStatus) => {
+    setAuthenticated(status.authenticated);
+    setCanEdit(status.role === "admin" || status.role === "editor");
+    setAuthEnabled(status.auth_enabled);
+  };
+
   // NodeTree removed for simplicity; render from cards directly
   const loadCards = async () => {
     const rels = await getCardRelations();
@@ -245,11 +260,11 @@ function App() {
 
     getAuthStatus()
       .then((status) => {
-        setCanEdit(status.authenticated);
-        setAuthEnabled(status.auth_enabled);
+        applyAuthStatus(status);
       })
       .catch((e) => {
         console.error("failed to load auth status", e);
+        setAuthenticated(false);
         setCanEdit(false);
       });
 
@@ -263,9 +278,8 @@ function App() {
     setAuthError("");
 
     try {
-      const status = await login(password());
-      setCanEdit(status.authenticated);
-      setAuthEnabled(status.auth_enabled);
+      const status = await login(username(), password());
+      applyAuthStatus(status);
       setPassword("");
       setApiToken("");
       setApiTokenError("");
@@ -273,6 +287,7 @@ function App() {
     } catch (e) {
       console.error(e);
       setAuthError("ログインできませんでした");
+      setAuthenticated(false);
       setCanEdit(false);
     }
   };
@@ -282,8 +297,7 @@ function App() {
 
     try {
       const status = await logout();
-      setCanEdit(status.authenticated);
-      setAuthEnabled(status.auth_enabled);
+      applyAuthStatus(status);
       setEdittingCard(null);
       setApiToken("");
       setApiTokenError("");
@@ -297,7 +311,11 @@ function App() {
     setApiTokenError("");
 
     try {
-      const res = await generateApiToken();
+      const res = await generateApiToken(`ブラウザで発行 ${new Date().toLocaleString()}`, [
+        "read",
+        "write",
+        "flashcards",
+      ]);
       setApiToken(res.token);
     } catch (e) {
       console.error(e);
@@ -318,9 +336,12 @@ function App() {
     >
       <SideCardTree
         cards={displayCards}
+        authenticated={authenticated}
         canEdit={canEdit}
         authEnabled={authEnabled}
         showLoginControls={showLoginControls}
+        username={username}
+        setUsername={setUsername}
         password={password}
         setPassword={setPassword}
         authError={authError}
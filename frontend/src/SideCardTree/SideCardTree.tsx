@@ -11,9 +11,12 @@ type SideCardTreeProps = {
   onClose?: () => void;
   onOpen?: () => void;
   // Auth controls (rendered inside the sidebar)
+  authenticated?: Accessor<boolean>;
   canEdit?: Accessor<boolean>;
   authEnabled?: Accessor<boolean>;
   showLoginControls?: Accessor<boolean>;
+  username?: Accessor<string>;
+  setUsername?: (value: string) => void;
   password?: Accessor<string>;
   setPassword?: (value: string) => void;
   authError?: Accessor<string>;
@@ -57,23 +60,42 @@ export const SideCardTree = (props: SideCardTreeProps) => {
           <ScrollArea>
             <TreeList items={tree()} level={0} onReveal={(id) => { setSelectedId(id); props.onReveal(id); }} selectedId={selectedId} />
           </ScrollArea>
-          {/* Only admins, or visitors who arrived with the secret login query,
-              ever see this section. Plain viewers get no hint that an edit mode
-              exists at all. */}
-          <Show when={props.canEdit?.() || (props.authEnabled?.() && props.showLoginControls?.())}>
+          {/* Only logged-in users, or visitors who arrived with the secret login
+              query, ever see this section. Anonymous visitors get no hint that
+              an edit mode exists at all. */}
+          <Show
+            when={props.authenticated?.() || (props.authEnabled?.() && props.showLoginControls?.())}
+          >
             <AuthSection>
               <Show
-                when={props.canEdit?.()}
+                when={props.authenticated?.()}
                 fallback={
                   <form
                     onSubmit={(e) => props.onLogin?.(e)}
                     style={{ display: "flex", "flex-direction": "column", gap: "6px" }}
                   >
+                    <input
+                      type="text"
+                      autocomplete="username"
+                      value={props.username?.() ?? ""}
+                      onInput={(e) => props.setUsername?.(e.currentTarget.value)}
+                      placeholder="ユーザー名"
+                      style={{
+                        width: "100%",
+                        padding: "6px 8px",
+                        border: "1px solid #3a3a3a",
+                        "border-radius": "4px",
+                        background: "#2a2a2a",
+                        color: "#eee",
+                        "box-sizing": "border-box",
+                      }}
+                    />
                     <input
                       type="password"
+                      autocomplete="current-password"
                       value={props.password?.() ?? ""}
                       onInput={(e) => props.setPassword?.(e.currentTarget.value)}
-                      placeholder="編集パスワード"
+                      placeholder="パスワード"
                       style={{
                         width: "100%",
                         padding: "6px 8px",
@@ -95,7 +117,7 @@ export const SideCardTree = (props: SideCardTreeProps) => {
               >
                 <div style={{ display: "flex", "flex-direction": "column", gap: "8px" }}>
                   <StatusRow>
-                    <StatusText>編集モード</StatusText>
+                    <StatusText>{props.canEdit?.() ? "編集モード" : "閲覧モード"}</StatusText>
                     <button onClick={() => props.onLogout?.()}>ログアウト</button>
                   </StatusRow>
                   <button onClick={() => props.onGenerateApiToken?.()}>
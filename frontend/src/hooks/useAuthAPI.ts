@@ -3,11 +3,20 @@ import { fetchAPI } from "./useAPI.js";
 export interface AuthStatus {
   authenticated: boolean;
   auth_enabled: boolean;
+  role: string | null;
 }
 
+export type ApiTokenScope = "read" | "write" | "flashcards" | "admin";
+
 export interface ApiTokenResponse {
+  id: number;
   token: string;
+  name: string;
   prefix: string;
+  scopes: ApiTokenScope[];
+  expires_at: string | null;
+  last_used_at: string | null;
+  created_at: string;
 }
 
 export const getAuthStatus = async (): Promise<AuthStatus> => {
@@ -17,13 +26,13 @@ export const getAuthStatus = async (): Promise<AuthStatus> => {
   return res.data as AuthStatus;
 };
 
-export const login = async (password: string): Promise<AuthStatus> => {
+export const login = async (username: string, password: string): Promise<AuthStatus> => {
   const res = await fetchAPI("auth/login", {
     method: "POST",
     headers: {
       "Content-Type": "application/json",
     },
-    body: JSON.stringify({ password }),
+    body: JSON.stringify({ username, password }),
   });
   return res.data as AuthStatus;
 };
@@ -35,9 +44,16 @@ export const logout = async (): Promise<AuthStatus> => {
   return res.data as AuthStatus;
 };
 
-export const generateApiToken = async (): Promise<ApiTokenResponse> => {
-  const res = await fetchAPI("auth/api-token", {
+export const generateApiToken = async (
+  name: string,
+  scopes: ApiTokenScope[],
+): Promise<ApiTokenResponse> => {
+  const res = await fetchAPI("auth/api-tokens", {
     method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ name, scopes }),
   });
   return res.data as ApiTokenResponse;
 };
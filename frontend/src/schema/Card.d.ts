@@ -3,7 +3,7 @@ import { Dimmension } from "./Point.js";
 export type CardVisibility = "public" | "private";
 
 // Card rendering type. Extend this union as new types are added.
-export type CardType = "normal" | "frame";
+export type CardType = "normal" | "frame" | "smart_frame";
 
 export interface Card {
   id: number;
@@ -17,7 +17,13 @@ export interface Card {
   visibility?: CardVisibility;
   /** rendering type; "frame" shows a large region and auto-parents cards created inside it */
   card_type?: CardType;
+  /** saved query whose current results are the children of a "smart_frame" */
+  saved_query_id?: number | null;
   ok_count?: number;
+  /** bumped on every change; send as If-Match ("<version>") to avoid overwriting newer edits */
+  version?: number;
+  /** user who created the card, if known */
+  owner_id?: number | null;
   created_at: string;
   updated_at: string;
 }